@@ -0,0 +1,298 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use crate::pathspec;
+use crate::storage::Storage;
+use crate::structs::git_index::{GitIndex, GitIndexEntry};
+use crate::utils::{files, hash};
+use crate::utils::process::die;
+use crate::worktree::{FileState, StatChecker};
+use crate::worktree;
+
+// Mode and blob hash of every file in a tree, keyed by path
+pub type Files = BTreeMap<String, (u32, String)>;
+
+// Paths whose local state would be lost by a switch
+#[derive(Debug, Default)]
+pub struct Blocked {
+    pub modified: Vec<String>,
+    pub untracked: Vec<String>,
+}
+
+// Where `restore` writes to
+#[derive(Debug, Clone, Copy)]
+pub struct RestoreTarget {
+    pub worktree: bool,
+    pub staged: bool,
+    // In overlay mode paths missing from the source are left alone instead of being removed
+    pub overlay: bool,
+}
+
+pub fn tree_files(storage: &Storage, tree: Option<&str>) -> Files {
+    match tree {
+        Some(tree) => storage
+            .read_tree_files(tree)
+            .into_iter()
+            .map(|(path, object_ref)| (path, (object_ref.permissions, object_ref.hash)))
+            .collect(),
+        None => Files::new(),
+    }
+}
+
+fn side(entry: Option<&GitIndexEntry>) -> Option<(u32, String)> {
+    entry.map(|e| (e.mode, e.object_hash()))
+}
+
+// Moves the index, and the work tree with `update`, from the `current` tree to the `target` one,
+// like `git read-tree -m [-u]`. Paths the two trees agree on keep their local changes, other paths
+// are only touched when they have none. With `force` everything is reset to the target
+pub fn switch(storage: &Storage, index: &mut GitIndex, current: &Files, target: &Files, force: bool, update: bool) -> Result<(), Blocked> {
+    let checker = StatChecker::new(storage, index);
+    let mut blocked = Blocked::default();
+    let mut updates: Vec<(String, Option<(u32, String)>)> = vec![];
+
+    let paths: BTreeSet<String> =
+        current.keys().chain(target.keys()).cloned().chain(index.entries.iter().map(|e| e.path.clone())).collect();
+
+    for path in paths {
+        let (head, wanted) = (current.get(&path), target.get(&path));
+        let staged = side(index.entry(&path));
+        let clean = index.entry(&path).map(|entry| checker.check(entry));
+
+        if force {
+            let up_to_date = staged.as_ref() == wanted && matches!(clean, Some(FileState::Unchanged));
+            if (wanted.is_some() && !up_to_date) || (wanted.is_none() && head.is_some()) {
+                updates.push((path, wanted.cloned()));
+            }
+            continue;
+        }
+
+        if head == wanted || staged.as_ref() == wanted { continue; }
+
+        if staged.as_ref() != head {
+            blocked.modified.push(path);
+            continue;
+        }
+
+        match clean {
+            _ if !update => {}
+            Some(FileState::Unchanged) => {}
+            Some(_) => {
+                blocked.modified.push(path);
+                continue;
+            }
+            // Untracked files may only be overwritten by the same content
+            None if untracked_differs(storage, index, &path, wanted) => {
+                blocked.untracked.push(path);
+                continue;
+            }
+            None => {}
+        }
+
+        updates.push((path, wanted.cloned()));
+    }
+
+    if !blocked.modified.is_empty() || !blocked.untracked.is_empty() { return Err(blocked); }
+
+    apply(storage, index, updates, update);
+
+    Ok(())
+}
+
+// Like `git reset --merge`: only the paths where the index differs from `target`, as a merge leaves
+// them, are reset in the index and the work tree. Local changes to other files are kept
+pub fn reset_merge(storage: &Storage, index: &mut GitIndex, target: &Files) {
+    let paths: BTreeSet<String> = target.keys().cloned().chain(index.entries.iter().map(|e| e.path.clone())).collect();
+
+    let updates =
+        paths
+            .into_iter()
+            .filter(|path| match index.stages(path) {
+                [] => target.contains_key(path),
+                [entry] if entry.stage() == 0 => target.get(path) != Some(&(entry.mode, entry.object_hash())),
+                _ => true,
+            })
+            .map(|path| {
+                let wanted = target.get(&path).cloned();
+                (path, wanted)
+            })
+            .collect();
+
+    apply(storage, index, updates, true);
+}
+
+// Writes resolved paths into the index, and into the work tree with `update`
+fn apply(storage: &Storage, index: &mut GitIndex, mut updates: Vec<(String, Option<(u32, String)>)>, update: bool) {
+    // Removals go first so files can take the place of directories and the other way around
+    updates.sort_by_key(|(_, wanted)| wanted.is_some());
+
+    for (path, wanted) in updates {
+        match wanted {
+            Some((mode, blob)) if update => index.add_entry(worktree::checkout_file(storage, &path, mode, &blob)),
+            Some((mode, blob)) => index.add_entry(GitIndexEntry::new(&path, mode, &blob, Default::default())),
+            None => {
+                index.remove_entry(&path);
+                if update { worktree::remove_file(storage, &path); }
+            }
+        }
+    }
+}
+
+// Fills the index from a tree. Without a prefix the index is replaced, keeping the stat data of
+// entries that did not change; with one the tree is read into that directory, which must not be in
+// the index yet
+pub fn read_tree(index: &mut GitIndex, files: &Files, prefix: &str) -> Result<(), String> {
+    if prefix.is_empty() {
+        let mut previous: HashMap<String, GitIndexEntry> =
+            std::mem::take(&mut index.entries)
+                .into_iter()
+                .filter(|e| e.stage() == 0)
+                .map(|e| (e.path.clone(), e))
+                .collect();
+
+        for (path, (mode, blob)) in files {
+            let mut entry = GitIndexEntry::new(path, *mode, blob, Default::default());
+            if let Some(old) = previous.remove(path).filter(|e| e.mode == *mode && e.object_hash() == *blob) {
+                entry.stats = old.stats;
+            }
+            index.add_entry(entry);
+        }
+        return Ok(());
+    }
+
+    let prefix = format!("{}/", prefix.trim_end_matches('/'));
+    if index.entries.iter().any(|e| e.path.starts_with(&prefix) || format!("{}/", e.path) == prefix) {
+        return Err(format!("subdirectory '{}' already exists.", prefix));
+    }
+
+    for (path, (mode, blob)) in files {
+        index.add_entry(GitIndexEntry::new(&format!("{}{}", prefix, path), *mode, blob, Default::default()));
+    }
+
+    Ok(())
+}
+
+// Three-way merge of trees into the index, like `git read-tree -m <base> <ours> <theirs>`. Paths
+// changed on one side only take that side, anything else is left as conflict stages 1, 2 and 3.
+// Deletions are only resolved when `aggressive`. Returns the paths whose local state is in the way
+pub fn three_way(storage: &Storage, index: &mut GitIndex, trees: [&Files; 3], aggressive: bool, update: bool) -> Result<(), Vec<String>> {
+    let [base, ours, theirs] = trees;
+    let checker = StatChecker::new(storage, index);
+    let paths: BTreeSet<&String> = base.keys().chain(ours.keys()).chain(theirs.keys()).collect();
+    let mut blocked = vec![];
+    let mut updates = vec![];
+    let mut conflicts = vec![];
+
+    for path in paths {
+        let (b, o, t) = (base.get(path), ours.get(path), theirs.get(path));
+        let merged =
+            if o == t {
+                Some(o)
+            } else if b == o {
+                Some(t)
+            } else if b == t {
+                Some(o)
+            } else {
+                None
+            };
+        let merged = merged.filter(|side| side.is_some() || b.is_none() || aggressive);
+
+        let staged = side(index.entry(path));
+        if merged == Some(staged.as_ref()) { continue; }
+
+        // The index has to match our tree wherever the merge changes it
+        if staged.as_ref() != o {
+            blocked.push(path.clone());
+            continue;
+        }
+        // Conflicts leave the work tree alone, resolved paths need a clean file to replace
+        let dirty =
+            match (index.entry(path), merged) {
+                (_, None) => false,
+                (Some(entry), _) => checker.check(entry) != FileState::Unchanged,
+                (None, Some(wanted)) => wanted.is_some() && untracked_differs(storage, index, path, wanted),
+            };
+        if update && dirty {
+            blocked.push(path.clone());
+            continue;
+        }
+
+        match merged {
+            Some(side) => updates.push((path.clone(), side.cloned())),
+            None => conflicts.push((path.clone(), [b, o, t])),
+        }
+    }
+
+    if !blocked.is_empty() { return Err(blocked); }
+
+    apply(storage, index, updates, update);
+    for (path, sides) in conflicts {
+        index.remove_entry(&path);
+
+        for (stage, side) in sides.iter().enumerate() {
+            if let Some((mode, blob)) = side {
+                let mut entry = GitIndexEntry::new(&path, *mode, blob, Default::default());
+                entry.set_stage(stage as u16 + 1);
+                index.add_entry(entry);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn untracked_differs(storage: &Storage, index: &GitIndex, path: &str, wanted: Option<&(u32, String)>) -> bool {
+    let full_path = storage.working_root.join(path);
+    let Ok(meta) = fs::symlink_metadata(&full_path) else { return false };
+
+    // Tracked files in a directory that makes way for a file are removed by the switch itself
+    if meta.is_dir() {
+        return wanted.is_some()
+            && files::walk(&full_path).iter().any(|file| !index.contains(&format!("{}/{}", path, file)));
+    }
+
+    wanted.is_none_or(|(_, blob)| hash::from_bytes(&worktree::read_content(&full_path)) != *blob)
+}
+
+// Copies paths matching `specs` from `source` into the index and/or the work tree. Returns the
+// specs that matched nothing
+pub fn restore(storage: &Storage, index: &mut GitIndex, source: &Files, specs: &[String], target: RestoreTarget) -> Vec<String> {
+    let known: BTreeSet<String> =
+        source.keys().cloned().chain(index.entries.iter().map(|e| e.path.clone())).collect();
+    let unmatched: Vec<String> =
+        specs.iter().filter(|spec| !known.iter().any(|path| pathspec::matches(spec, path))).cloned().collect();
+
+    let matched: Vec<&String> = known.iter().filter(|path| pathspec::matches_any(specs, path)).collect();
+
+    // Leaving the index alone, an unmerged path has no single version to go back to
+    if !target.staged {
+        let unmerged = matched.iter().find(|path| !source.contains_key(**path) && index.stages(path).iter().any(|e| e.stage() != 0));
+        if let Some(path) = unmerged { die(format!("path '{}' is unmerged", path)); }
+    }
+
+    for path in matched {
+        match source.get(path) {
+            Some((mode, blob)) => {
+                let entry =
+                    if target.worktree {
+                        worktree::checkout_file(storage, path, *mode, blob)
+                    } else {
+                        GitIndexEntry::new(path, *mode, blob, Default::default())
+                    };
+
+                if target.staged {
+                    index.add_entry(entry);
+                } else if let Some(current) = index.entry_mut(path).filter(|e| e.object_hash() == *blob && e.mode == *mode) {
+                    // The file now matches the index again, so its stat data can be refreshed
+                    current.stats = entry.stats;
+                }
+            }
+            None if target.overlay => {}
+            None => {
+                if target.staged { index.remove_entry(path); }
+                if target.worktree { worktree::remove_file(storage, path); }
+            }
+        }
+    }
+
+    unmatched
+}
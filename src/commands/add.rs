@@ -0,0 +1,111 @@
+use std::process;
+use crate::commands::AddArgs;
+use crate::ignore::IgnoreRules;
+use crate::pathspec;
+use crate::storage::Storage;
+use crate::utils::files;
+use crate::utils::process::die;
+use crate::worktree::{self, FileState, StatChecker};
+
+// rinit add src/ README.md
+// rinit add -A --dry-run
+pub fn call(storage: Storage, args: AddArgs) {
+    if args.pathspecs.is_empty() && !args.all && !args.update {
+        die("Nothing specified, nothing added.");
+    }
+
+    let specs = &args.pathspecs;
+    let rules = IgnoreRules::load(&storage);
+    let mut index = storage.read_index();
+    let work_files =
+        if args.force {
+            files::walk(&storage.working_root)
+        } else {
+            files::walk_pruned(&storage.working_root, &|dir| rules.is_ignored_dir(dir))
+        };
+
+    let is_ignored_spec = |spec: &str| {
+        let path = spec.trim_end_matches('/');
+        if storage.working_root.join(path).is_dir() { rules.is_ignored_dir(path) } else { rules.is_ignored(path) }
+    };
+
+    let mut ignored = vec![];
+    for spec in specs {
+        let matched =
+            work_files.iter().any(|path| pathspec::matches(spec, path))
+                || index.entries.iter().any(|e| pathspec::matches(spec, &e.path));
+
+        if matched { continue; }
+        if !args.force && storage.working_root.join(spec).exists() && is_ignored_spec(spec) {
+            ignored.push(spec.to_string());
+        } else {
+            die(format!("pathspec '{}' did not match any files", spec));
+        }
+    }
+
+    // Tracked files stay tracked even when they live in an ignored directory
+    let mut candidates: Vec<String> = index.entries.iter().map(|e| e.path.clone()).collect();
+    if !args.update { candidates.extend(work_files); }
+    candidates.sort();
+    candidates.dedup();
+
+    let checker = StatChecker::new(&storage, &index);
+    let mut added = vec![];
+    for path in candidates.into_iter().filter(|path| pathspec::matches_any(specs, path)) {
+        let tracked = index.contains(&path);
+
+        if !tracked && !args.force && rules.is_ignored(&path) {
+            // Only pathspecs naming ignored files explicitly are worth complaining about
+            for spec in specs.iter().filter(|s| pathspec::matches(s, &path) && is_ignored_spec(s)) {
+                if !ignored.contains(spec) { ignored.push(spec.to_string()); }
+            }
+            continue;
+        }
+
+        let state = match index.entry(&path) {
+            Some(entry) => checker.check(entry),
+            None if storage.working_root.join(&path).symlink_metadata().is_ok() => FileState::Modified,
+            None => FileState::Deleted,
+        };
+        if state == FileState::Modified { added.push(path); }
+    }
+
+    let mut removed: Vec<String> =
+        index.entries
+            .iter()
+            .filter(|e| pathspec::matches_any(specs, &e.path))
+            .filter(|e| checker.check(e) == FileState::Deleted)
+            .map(|e| e.path.clone())
+            .collect();
+    removed.dedup();
+
+    // A file that cannot be stored is reported and skipped, the others are still added
+    let mut failed = false;
+    for path in &added {
+        if args.dry_run || args.verbose { println!("add '{}'", path); }
+        if args.dry_run { continue; }
+
+        match worktree::stage_file(&storage, path) {
+            Ok(entry) => index.add_entry(entry),
+            Err(error) => {
+                eprintln!("error: {}", error);
+                failed = true;
+            }
+        }
+    }
+
+    for path in &removed {
+        if args.dry_run || args.verbose { println!("remove '{}'", path); }
+        if !args.dry_run { index.remove_entry(path); }
+    }
+
+    if !args.dry_run { storage.save_index(&mut index); }
+
+    if !ignored.is_empty() {
+        eprintln!("The following paths are ignored by one of your .gitignore files:");
+        for path in &ignored { eprintln!("{}", path); }
+        eprintln!("hint: Use -f if you really want to add them.");
+    }
+    if failed { die("adding files failed"); }
+    if !ignored.is_empty() { process::exit(1); }
+}
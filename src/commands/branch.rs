@@ -0,0 +1,175 @@
+use std::process;
+use crate::commands::BranchArgs;
+use crate::revision;
+use crate::reachability::CommitGraph;
+use crate::storage::Storage;
+use crate::utils::{hash, wildmatch};
+use crate::utils::process::die;
+
+const HEADS: &str = "refs/heads/";
+
+// rinit branch -v
+// rinit branch feature HEAD~2
+// rinit branch -m feature topic
+// rinit branch -d topic
+pub fn call(storage: Storage, args: BranchArgs) {
+    if args.delete || args.force_delete {
+        delete(&storage, &args.names, args.force_delete || args.force);
+    } else if args.rename || args.force_rename {
+        rename(&storage, &args.names, args.force_rename || args.force);
+    } else if args.names.is_empty() || args.list || args.contains.is_some() || args.merged.is_some() || args.no_merged.is_some() {
+        list(&storage, &args);
+    } else {
+        create(&storage, &args.names, args.force);
+    }
+}
+
+fn branch_ref(name: &str) -> String {
+    format!("{}{}", HEADS, name)
+}
+
+fn current_branch(storage: &Storage) -> Option<String> {
+    storage.head_target().and_then(|target| target.strip_prefix(HEADS).map(str::to_string))
+}
+
+fn check_name(storage: &Storage, name: &str) {
+    if !Storage::is_valid_ref_name(name) { die(format!("'{}' is not a valid branch name", name)); }
+
+    // "a/b" can not coexist with "a", refs are files and directories
+    let conflict =
+        storage
+            .list_refs(HEADS)
+            .into_iter()
+            .map(|(existing, _)| existing[HEADS.len()..].to_string())
+            .find(|existing| existing.starts_with(&format!("{}/", name)) || name.starts_with(&format!("{}/", existing)));
+    if let Some(existing) = conflict {
+        die(format!("cannot create '{}': '{}' exists", branch_ref(name), branch_ref(&existing)));
+    }
+}
+
+fn list(storage: &Storage, args: &BranchArgs) {
+    let resolve = |rev: &String| revision::resolve(storage, rev).unwrap_or_else(|| die(format!("malformed object name {}", rev)));
+    let contains = args.contains.as_ref().map(resolve);
+    let merged = args.merged.as_ref().map(resolve);
+    let no_merged = args.no_merged.as_ref().map(resolve);
+    let current = current_branch(storage);
+
+    let mut lines: Vec<(bool, String, String)> =
+        storage
+            .list_refs(HEADS)
+            .into_iter()
+            .map(|(name, hash)| (current.as_deref() == name.strip_prefix(HEADS), name[HEADS.len()..].to_string(), hash))
+            .filter(|(_, name, _)| args.names.is_empty() || args.names.iter().any(|pattern| wildmatch::matches(pattern, name)))
+            .collect();
+
+    // A detached HEAD is listed first, in place of a branch
+    if current.is_none() {
+        if let Some(head) = storage.read_ref("HEAD") {
+            lines.insert(0, (true, format!("(HEAD detached at {})", hash::short(&head)), head));
+        }
+    }
+
+    let mut graph = CommitGraph::new(storage);
+    if let Some(commit) = &contains {
+        lines.retain(|(_, _, tip)| graph.is_ancestor(commit, tip));
+    }
+    if let Some(commit) = &merged {
+        lines.retain(|(_, _, tip)| graph.is_ancestor(tip, commit));
+    }
+    if let Some(commit) = &no_merged {
+        lines.retain(|(_, _, tip)| !graph.is_ancestor(tip, commit));
+    }
+
+    let width = lines.iter().map(|(_, name, _)| name.chars().count()).max().unwrap_or(0);
+    for (is_current, name, tip) in lines {
+        let marker = if is_current { '*' } else { ' ' };
+
+        if args.verbose {
+            let subject = storage.read_commit(&tip).subject().to_string();
+            println!("{} {:<width$} {} {}", marker, name, hash::short(&tip), subject, width = width);
+        } else {
+            println!("{} {}", marker, name);
+        }
+    }
+}
+
+fn create(storage: &Storage, names: &[String], force: bool) {
+    let (name, start) =
+        match names {
+            [name] => (name, "HEAD"),
+            [name, start] => (name, start.as_str()),
+            _ => die("too many arguments to create a branch"),
+        };
+    check_name(storage, name);
+
+    if storage.ref_exists(&branch_ref(name)) {
+        if !force { die(format!("a branch named '{}' already exists", name)); }
+        if current_branch(storage).as_deref() == Some(name) {
+            die(format!("cannot force update the current branch '{}'", name));
+        }
+    }
+
+    let commit =
+        revision::resolve(storage, start).unwrap_or_else(|| die(format!("not a valid object name: '{}'", start)));
+    storage.update_ref(&branch_ref(name), &commit);
+}
+
+// Branches not merged into HEAD are kept unless forced
+fn delete(storage: &Storage, names: &[String], force: bool) {
+    if names.is_empty() { die("branch name required"); }
+
+    let current = current_branch(storage);
+    let head = storage.read_ref("HEAD");
+    let mut failed = false;
+    let mut graph = CommitGraph::new(storage);
+
+    for name in names {
+        let Some(tip) = storage.read_ref(&branch_ref(name)) else {
+            eprintln!("error: branch '{}' not found.", name);
+            failed = true;
+            continue;
+        };
+
+        if current.as_deref() == Some(name) {
+            eprintln!("error: Cannot delete branch '{}' checked out at '{}'", name, storage.working_root.display());
+            failed = true;
+            continue;
+        }
+
+        let merged = head.as_ref().is_some_and(|head| graph.is_ancestor(&tip, head));
+        if !force && !merged {
+            eprintln!("error: the branch '{}' is not fully merged.", name);
+            eprintln!("If you are sure you want to delete it, run 'rinit branch -D {}'.", name);
+            failed = true;
+            continue;
+        }
+
+        storage.delete_ref(&branch_ref(name));
+        println!("Deleted branch {} (was {}).", name, hash::short(&tip));
+    }
+
+    if failed { process::exit(1); }
+}
+
+// Renames a branch, or the current one when a single name is given, and keeps HEAD on it
+fn rename(storage: &Storage, names: &[String], force: bool) {
+    let current = current_branch(storage);
+    let (old, new) =
+        match names {
+            [new] => (current.clone().unwrap_or_else(|| die("cannot rename the current branch while not on any")), new.clone()),
+            [old, new] => (old.clone(), new.clone()),
+            _ => die("too many arguments for a rename operation"),
+        };
+    if old != new { check_name(storage, &new); }
+
+    let on_unborn = current.as_deref() == Some(old.as_str()) && !storage.ref_exists(&branch_ref(&old));
+    if !storage.ref_exists(&branch_ref(&old)) && !on_unborn {
+        die(format!("no branch named '{}'", old));
+    }
+    if old != new && storage.ref_exists(&branch_ref(&new)) && !force {
+        die(format!("a branch named '{}' already exists", new));
+    }
+
+    if !on_unborn && old != new { storage.rename_ref(&branch_ref(&old), &branch_ref(&new)); }
+    if current.as_deref() == Some(old.as_str()) { storage.set_head(&branch_ref(&new)); }
+}
@@ -0,0 +1,42 @@
+use std::process;
+use crate::commands::CheckIgnoreArgs;
+use crate::ignore::IgnoreRules;
+use crate::storage::Storage;
+use crate::utils::files::read_stdin;
+use crate::utils::process::die;
+
+// rinit check-ignore -v target/debug/rinit src/main.rs
+pub fn call(storage: Storage, args: CheckIgnoreArgs) {
+    let paths: Vec<String> =
+        if args.stdin {
+            read_stdin().lines().filter(|l| !l.is_empty()).map(str::to_string).collect()
+        } else {
+            args.paths.clone()
+        };
+
+    if paths.is_empty() { die("no path specified"); }
+    if args.non_matching && !args.verbose { die("--non-matching is only valid with --verbose"); }
+
+    let rules = IgnoreRules::load(&storage);
+    let index = if args.no_index { None } else { Some(storage.read_index()) };
+    let mut any_ignored = false;
+
+    for path in &paths {
+        let path = path.trim_start_matches("./");
+        let tracked = index.as_ref().is_some_and(|index| index.contains(path));
+        let is_dir = storage.working_root.join(path).is_dir();
+
+        // Tracked files are never reported as ignored unless --no-index is given
+        let pattern = if tracked { None } else { rules.explain(path, is_dir) };
+        any_ignored |= pattern.as_ref().is_some_and(|p| !p.negated);
+
+        match (&pattern, args.verbose) {
+            (Some(p), true) => println!("{}:{}:{}\t{}", p.source, p.line, p.original, path),
+            (Some(p), false) if !p.negated => println!("{}", path),
+            (None, true) if args.non_matching => println!("::\t{}", path),
+            _ => {}
+        }
+    }
+
+    if !any_ignored { process::exit(1); }
+}
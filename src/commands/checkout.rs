@@ -0,0 +1,182 @@
+use std::process;
+use crate::checkout::{self, Files, RestoreTarget};
+use crate::commands::CheckoutArgs;
+use crate::revision;
+use crate::storage::Storage;
+use crate::utils::hash;
+use crate::utils::process::die;
+
+// Where HEAD ends up after a switch
+pub enum Destination {
+    Branch(String),
+    NewBranch(String, Option<String>),
+    Detached(String, String),
+}
+
+// rinit checkout feature
+// rinit checkout -b topic HEAD~2
+// rinit checkout HEAD~1 -- src/main.rs
+pub fn call(storage: Storage, args: CheckoutArgs) {
+    let resolves = |name: &str| revision::resolve(&storage, name).is_some();
+
+    let (target, paths): (Option<String>, Vec<String>) =
+        if !args.paths.is_empty() {
+            // checkout [<tree-ish>] -- <paths>
+            (args.targets.first().cloned(), args.targets.iter().skip(1).chain(&args.paths).cloned().collect())
+        } else {
+            // Without "--", the first argument is a revision only if it resolves as one
+            match args.targets.split_first() {
+                Some((first, rest)) if resolves(first) || args.new_branch.is_some() => (Some(first.clone()), rest.to_vec()),
+                _ => (None, args.targets.clone()),
+            }
+        };
+
+    if args.new_branch.is_none() && (target.is_none() || !paths.is_empty()) {
+        if paths.is_empty() { die("you must specify path(s) to restore"); }
+        checkout_paths(&storage, target.as_deref(), &paths);
+        return;
+    }
+
+    let destination =
+        match (args.new_branch, target) {
+            (Some(name), start) => Destination::NewBranch(name, start),
+            (None, Some(name)) if !args.detach && storage.read_ref(&format!("refs/heads/{}", name)).is_some() => {
+                Destination::Branch(name)
+            }
+            (None, Some(rev)) => {
+                let commit = revision::resolve(&storage, &rev).unwrap_or_else(|| die(format!("invalid reference: {}", rev)));
+                Destination::Detached(rev, commit)
+            }
+            (None, None) => unreachable!(),
+        };
+
+    switch_to(&storage, destination, args.force);
+}
+
+// Paths are restored from the index, or from a tree-ish into both the index and the work tree
+fn checkout_paths(storage: &Storage, source: Option<&str>, paths: &[String]) {
+    let mut index = storage.read_index();
+    let (files, target) =
+        match source {
+            Some(rev) => {
+                let tree = revision::resolve_tree(storage, rev).unwrap_or_else(|| die(format!("invalid reference: {}", rev)));
+                (checkout::tree_files(storage, Some(&tree)), RestoreTarget { worktree: true, staged: true, overlay: true })
+            }
+            None => (index_files(storage), RestoreTarget { worktree: true, staged: false, overlay: true }),
+        };
+
+    let unmatched = checkout::restore(storage, &mut index, &files, paths, target);
+    report_unmatched(&unmatched);
+    storage.save_index(&mut index);
+}
+
+pub fn index_files(storage: &Storage) -> Files {
+    storage
+        .read_index()
+        .entries
+        .iter()
+        .filter(|e| e.stage() == 0)
+        .map(|e| (e.path.clone(), (e.mode, e.object_hash())))
+        .collect()
+}
+
+pub fn report_unmatched(unmatched: &[String]) {
+    if unmatched.is_empty() { return; }
+
+    for spec in unmatched {
+        eprintln!("error: pathspec '{}' did not match any file(s) known to rinit", spec);
+    }
+    process::exit(1);
+}
+
+// Moves HEAD, the index and the work tree to another branch or commit
+pub fn switch_to(storage: &Storage, destination: Destination, force: bool) {
+    let previous = storage.read_ref("HEAD");
+    let previous_branch = storage.head_target();
+
+    let commit =
+        match &destination {
+            Destination::Branch(name) => storage.read_ref(&format!("refs/heads/{}", name)),
+            Destination::NewBranch(name, start) => {
+                if storage.read_ref(&format!("refs/heads/{}", name)).is_some() {
+                    die(format!("a branch named '{}' already exists", name));
+                }
+                match start {
+                    Some(rev) => Some(revision::resolve(storage, rev).unwrap_or_else(|| die(format!("invalid reference: {}", rev)))),
+                    None => previous.clone(),
+                }
+            }
+            Destination::Detached(_, commit) => Some(commit.clone()),
+        };
+
+    let mut index = storage.read_index();
+    if index.entries.iter().any(|e| e.stage() != 0) {
+        die("you need to resolve your current index first");
+    }
+
+    if let Some(commit) = &commit {
+        let current = checkout::tree_files(storage, previous.as_ref().map(|c| storage.read_commit(c).tree).as_deref());
+        let target = checkout::tree_files(storage, Some(&storage.read_commit(commit).tree));
+
+        if let Err(blocked) = checkout::switch(storage, &mut index, &current, &target, force, true) {
+            report_blocked(&blocked.modified, "Your local changes to the following files would be overwritten by checkout:",
+                           "Please commit your changes or stash them before you switch branches.");
+            report_blocked(&blocked.untracked, "The following untracked working tree files would be overwritten by checkout:",
+                           "Please move or remove them before you switch branches.");
+            eprintln!("Aborting");
+            process::exit(1);
+        }
+        storage.save_index(&mut index);
+    }
+
+    match destination {
+        Destination::Branch(name) => {
+            let reference = format!("refs/heads/{}", name);
+            if previous_branch.as_deref() == Some(reference.as_str()) {
+                eprintln!("Already on '{}'", name);
+            } else {
+                report_previous(storage, previous_branch.is_none(), previous.as_deref(), commit.as_deref());
+                storage.set_head(&reference);
+                eprintln!("Switched to branch '{}'", name);
+            }
+        }
+        Destination::NewBranch(name, _) => {
+            let reference = format!("refs/heads/{}", name);
+            if let Some(commit) = &commit { storage.update_ref(&reference, commit); }
+            report_previous(storage, previous_branch.is_none(), previous.as_deref(), commit.as_deref());
+            storage.set_head(&reference);
+            eprintln!("Switched to a new branch '{}'", name);
+        }
+        Destination::Detached(rev, commit) => {
+            if previous_branch.is_some() {
+                eprintln!("Note: switching to '{}'.\n", rev);
+                eprintln!("You are in 'detached HEAD' state. You can look around and make experimental");
+                eprintln!("commits, and switch back to a branch with `rinit switch <branch>`.\n");
+            } else {
+                report_previous(storage, true, previous.as_deref(), Some(&commit));
+            }
+            storage.set_head(&commit);
+            eprintln!("HEAD is now at {}", describe(storage, &commit));
+        }
+    }
+}
+
+pub fn report_blocked(paths: &[String], header: &str, advice: &str) {
+    if paths.is_empty() { return; }
+
+    eprintln!("error: {}", header);
+    for path in paths { eprintln!("\t{}", path); }
+    eprintln!("{}", advice);
+}
+
+// Leaving a detached HEAD mentions where it was
+fn report_previous(storage: &Storage, detached: bool, previous: Option<&str>, next: Option<&str>) {
+    if let Some(previous) = previous.filter(|p| detached && Some(*p) != next) {
+        eprintln!("Previous HEAD position was {}", describe(storage, previous));
+    }
+}
+
+// "a1b2c3d Commit subject"
+fn describe(storage: &Storage, commit: &str) -> String {
+    format!("{} {}", hash::short(commit), storage.read_commit(commit).subject())
+}
@@ -0,0 +1,28 @@
+use std::fs;
+use std::process;
+use crate::commands::CommitGraphArgs;
+use crate::commit_graph;
+use crate::maintenance;
+use crate::storage::Storage;
+use crate::utils::process::die;
+
+// rinit commit-graph write --changed-paths
+// rinit commit-graph verify
+pub fn call(storage: Storage, args: CommitGraphArgs) {
+    match args.action.as_str() {
+        "write" => maintenance::write_commit_graph(&storage, args.changed_paths),
+        "verify" => verify(&storage),
+        _ => unreachable!(),
+    }
+}
+
+fn verify(storage: &Storage) {
+    let path = storage.commit_graph_path();
+    if !path.is_file() { return; }
+
+    let data = fs::read(&path).unwrap_or_else(|e| die(format!("could not read {}: {}", path.display(), e)));
+    let problems = commit_graph::verify(storage, &data);
+    for problem in &problems { eprintln!("error: commit-graph: {}", problem); }
+
+    if !problems.is_empty() { process::exit(1); }
+}
@@ -0,0 +1,50 @@
+use std::env;
+use crate::commands::CommitTreeArgs;
+use crate::revision;
+use crate::storage::Storage;
+use crate::structs::git_commit::{GitCommit, GitSignature};
+use crate::structs::git_object::GitObject;
+use crate::utils::process::die;
+use crate::utils::time;
+
+// rinit commit-tree <tree> -p <parent> -m "message"
+pub fn call(storage: Storage, args: CommitTreeArgs) {
+    let tree = storage.resolve_name(&args.tree)
+        .unwrap_or_else(|| die(format!("not a valid object name {}", args.tree)));
+    storage.read_tree(&tree);
+
+    let parents =
+        args.parents
+            .iter()
+            .map(|p| revision::resolve(&storage, p).unwrap_or_else(|| die(format!("not a valid object name {}", p))))
+            .collect();
+
+    let mut message = args.message.join("\n\n");
+    if !message.ends_with('\n') { message.push('\n'); }
+
+    let commit = GitCommit {
+        tree,
+        parents,
+        author: signature("AUTHOR"),
+        committer: signature("COMMITTER"),
+        message,
+    };
+    storage.persist_object(&commit);
+
+    println!("{}", commit.hash());
+}
+
+// Identity comes from RINIT_AUTHOR_NAME, RINIT_COMMITTER_EMAIL, ... falling back to $USER
+pub fn signature(role: &str) -> GitSignature {
+    let user = env::var("USER").unwrap_or_else(|_| "rinit".to_string());
+    let name = env::var(format!("RINIT_{}_NAME", role)).unwrap_or_else(|_| user.clone());
+    let email = env::var(format!("RINIT_{}_EMAIL", role)).unwrap_or_else(|_| format!("{}@localhost", user));
+    let date = env::var(format!("RINIT_{}_DATE", role)).ok();
+
+    let (timestamp, tz) = match date.as_deref().and_then(|d| d.split_once(' ')) {
+        Some((timestamp, tz)) => (timestamp.parse().unwrap_or_else(|_| time::now()), tz.to_string()),
+        None => (time::now(), "+0000".to_string())
+    };
+
+    GitSignature::new(&name, &email, timestamp, &tz)
+}
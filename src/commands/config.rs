@@ -0,0 +1,148 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use crate::commands::ConfigArgs;
+use crate::config::{self, Config, ConfigEntry, ConfigFile, Scope};
+use crate::storage::Storage;
+use crate::utils::process::die;
+
+// Reads from every config file unless one is named, writes to the repository's by default. Exit
+// codes follow Git: 1 for a missing or invalid key, 2 for a key without a section, 3 for an
+// unreadable file, 5 for a variable that cannot be unset or overwritten
+// rinit config user.name "A U Thor"
+// rinit config --get --type=bool core.trustctime
+// rinit config --global --add include.path ~/extra.gitconfig
+// rinit config --list --show-origin
+// rinit config --unset-all remote.origin.fetch
+pub fn call(storage: Storage, args: ConfigArgs) {
+    let actions = [args.get, args.get_all, args.set, args.add, args.unset, args.unset_all, args.list];
+    if actions.iter().filter(|action| **action).count() > 1 { usage("only one action at a time"); }
+
+    let scope =
+        match (args.system, args.global, args.local, &args.file) {
+            (true, _, _, _) => Some(Scope::System),
+            (_, true, _, _) => Some(Scope::Global),
+            (_, _, true, _) => Some(Scope::Local),
+            (_, _, _, Some(_)) => Some(Scope::Command),
+            _ => None,
+        };
+    let path = args.file.as_ref().map(PathBuf::from).or_else(|| scope.map(|scope| config::scope_path(&storage, scope)));
+
+    if args.list {
+        let config = read(&storage, &path, scope);
+        for entry in config.entries() {
+            match &entry.value {
+                Some(value) => println!("{}{}={}", prefix(&args, entry), entry.key, value),
+                None => println!("{}{}", prefix(&args, entry), entry.key),
+            }
+        }
+        return;
+    }
+
+    let Some(key) = args.key.as_deref() else { usage("no key given") };
+    if !key.contains('.') {
+        eprintln!("error: key does not contain a section: {}", key);
+        process::exit(2);
+    }
+    let Some(canonical) = config::canonical_key(key) else {
+        eprintln!("error: invalid key: {}", key);
+        process::exit(1);
+    };
+
+    let writing = args.set || args.add || args.unset || args.unset_all || (args.value.is_some() && !args.get && !args.get_all);
+    if !writing {
+        let config = read(&storage, &path, scope);
+        let entries = if args.get_all { config.get_all(&canonical) } else { config.get_entry(&canonical).into_iter().collect() };
+        if entries.is_empty() { process::exit(1); }
+
+        for entry in entries {
+            println!("{}{}", prefix(&args, entry), typed(&args, key, entry.value.as_deref()));
+        }
+        return;
+    }
+
+    let path = path.unwrap_or_else(|| config::scope_path(&storage, Scope::Local));
+    let content = fs::read_to_string(&path).unwrap_or_default();
+    let mut file = ConfigFile::parse(&content).unwrap_or_else(|line| {
+        eprintln!("error: bad config line {} in file {}", line, path.display());
+        process::exit(3);
+    });
+    let existing = file.entries.iter().filter(|entry| entry.key == canonical).count();
+
+    if args.unset || args.unset_all {
+        if existing == 0 { process::exit(5); }
+        if existing > 1 && args.unset {
+            eprintln!("warning: {} has multiple values", key);
+            process::exit(5);
+        }
+        file.unset(key);
+    } else {
+        let Some(value) = args.value.as_deref() else { usage("missing value") };
+        let value = normalized(&args, key, value);
+
+        match args.add {
+            true => file.add(key, &value),
+            false if existing > 1 => {
+                eprintln!("warning: {} has multiple values", key);
+                eprintln!("error: cannot overwrite multiple values with a single value");
+                process::exit(5);
+            }
+            false => file.set(key, &value),
+        }
+    }
+
+    if let Some(parent) = path.parent() { let _ = fs::create_dir_all(parent); }
+    fs::write(&path, file.to_string()).unwrap_or_else(|error| die(format!("could not write config file {}: {}", path.display(), error)));
+}
+
+fn read(storage: &Storage, path: &Option<PathBuf>, scope: Option<Scope>) -> Config {
+    match (path, scope) {
+        (Some(path), Some(scope)) => Config::from_file(storage, path, scope),
+        _ => Config::load(storage),
+    }
+}
+
+// "file:<path>\t" and "<scope>\t" in front of a value
+fn prefix(args: &ConfigArgs, entry: &ConfigEntry) -> String {
+    let mut prefix = String::new();
+    if args.show_scope { prefix.push_str(&format!("{}\t", entry.scope)); }
+    if args.show_origin { prefix.push_str(&format!("file:{}\t", entry.origin.display())); }
+
+    prefix
+}
+
+// A value as shown with --type
+fn typed(args: &ConfigArgs, key: &str, value: Option<&str>) -> String {
+    let raw = value.unwrap_or("");
+
+    match args.value_type.as_deref() {
+        Some("bool") => {
+            let parsed = config::parse_bool(value).unwrap_or_else(|| die(format!("bad boolean config value '{}' for '{}'", raw, key)));
+            parsed.to_string()
+        }
+        Some("int") => {
+            let parsed = config::parse_int(raw).unwrap_or_else(|| die(format!("bad numeric config value '{}' for '{}'", raw, key)));
+            parsed.to_string()
+        }
+        Some("path") => config::expand_path(raw).to_string_lossy().to_string(),
+        Some("color") => config::parse_color(raw).unwrap_or_else(|| die(format!("invalid color value: {}", raw))),
+        _ => raw.to_string(),
+    }
+}
+
+// A value as written with --type: booleans and integers in canonical form, colors checked
+fn normalized(args: &ConfigArgs, key: &str, value: &str) -> String {
+    match args.value_type.as_deref() {
+        Some("bool" | "int") => typed(args, key, Some(value)),
+        Some("color") => {
+            typed(args, key, Some(value));
+            value.to_string()
+        }
+        _ => value.to_string(),
+    }
+}
+
+fn usage(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(129)
+}
@@ -0,0 +1,51 @@
+use std::fs;
+use crate::commands::CountObjectsArgs;
+use crate::storage::Storage;
+
+// rinit count-objects
+// rinit count-objects -v -H
+pub fn call(storage: Storage, args: CountObjectsArgs) {
+    let loose = storage.loose_objects();
+    let size: u64 = loose.iter().map(|hash| file_size(&storage.object_path(hash))).sum();
+    let format = |bytes: u64| if args.human_readable { human(bytes) } else { (bytes / 1024).to_string() };
+
+    if !args.verbose {
+        let unit = if args.human_readable { "" } else { " kilobytes" };
+        println!("{} objects, {}{}", loose.len(), format(size), unit);
+        return;
+    }
+
+    let packs = storage.packs();
+    let in_pack: usize = packs.iter().map(|pack| pack.index.len()).sum();
+    let size_pack: u64 =
+        packs.iter().map(|pack| file_size(&pack.path) + file_size(&pack.path.with_extension("idx"))).sum();
+    let prune_packable = loose.iter().filter(|hash| packs.iter().any(|pack| pack.contains(hash))).count();
+
+    println!("count: {}", loose.len());
+    println!("size: {}", format(size));
+    println!("in-pack: {}", in_pack);
+    println!("packs: {}", packs.len());
+    println!("size-pack: {}", format(size_pack));
+    println!("prune-packable: {}", prune_packable);
+    println!("garbage: 0");
+    println!("size-garbage: {}", format(0));
+}
+
+fn file_size(path: &std::path::Path) -> u64 {
+    fs::metadata(path).map(|meta| meta.len()).unwrap_or(0)
+}
+
+// Sizes the way `-H` prints them: "512 bytes", "1.50 KiB", "3.20 MiB"
+fn human(bytes: u64) -> String {
+    let units = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 { return format!("{} bytes", bytes); }
+
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.2} {}", value, units[unit])
+}
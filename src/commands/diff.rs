@@ -0,0 +1,388 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use crate::commands::DiffArgs;
+use crate::diff::{self, Algorithm};
+use crate::pathspec;
+use crate::revision;
+use crate::storage::Storage;
+use crate::structs::flags::{ExtendedFlags, Flags};
+use crate::tree_diff::{self, ChangeKind, DiffEntry, RenameOptions, TreeChange};
+use crate::utils::hash;
+use crate::utils::process::die;
+use crate::worktree::{self, FileState, StatChecker};
+
+const STAT_WIDTH: usize = 80;
+
+// Reads blob contents as raw bytes, work tree files are kept in memory since they have no object
+struct Blobs<'a> {
+    storage: &'a Storage,
+    worktree: HashMap<String, Vec<u8>>,
+}
+
+enum Stat {
+    Lines(usize, usize),
+    Binary(usize, usize),
+    Unmerged,
+}
+
+// rinit diff
+// rinit diff --cached --stat
+// rinit diff -U1 -M HEAD~2 HEAD -- src/
+pub fn call(storage: Storage, args: DiffArgs) {
+    let algorithm =
+        match args.diff_algorithm.as_deref() {
+            Some("patience") => Algorithm::Patience,
+            Some(_) => Algorithm::Myers,
+            None if args.patience => Algorithm::Patience,
+            None => Algorithm::Myers,
+        };
+    // Copy detection implies rename detection
+    let renames = RenameOptions {
+        renames: args.find_renames.or(args.find_copies),
+        copies: args.find_copies,
+        limit: tree_diff::rename_limit(&storage, "diff.renameLimit"),
+    };
+    let (revisions, paths) = split_arguments(&storage, &args);
+    let mut blobs = Blobs { storage: &storage, worktree: HashMap::new() };
+
+    let changes =
+        match (args.cached, revisions.as_slice()) {
+            (true, []) => tree_to_index(&storage, head_tree(&storage).as_deref(), &paths),
+            (true, [rev]) => tree_to_index(&storage, Some(&resolve_tree(&storage, rev)), &paths),
+            (false, []) => index_to_worktree(&mut blobs, &paths),
+            (false, [rev]) => tree_to_worktree(&mut blobs, &resolve_tree(&storage, rev), &paths),
+            (false, [from, to]) => {
+                let (from, to) = (resolve_tree(&storage, from), resolve_tree(&storage, to));
+                tree_diff::diff_trees(&storage, Some(&from), Some(&to))
+                    .into_iter()
+                    .filter(|change| pathspec::matches_any(&paths, change.path()))
+                    .collect()
+            }
+            _ => die("usage: rinit diff [--cached] [<commit> [<commit>]] [-- <path>...]"),
+        };
+    let changes = tree_diff::detect_renames(changes, &renames, &|entry| String::from_utf8_lossy(&blobs.load(Some(entry))).to_string());
+
+    if args.name_only {
+        for change in &changes { println!("{}", change.path()); }
+    } else if args.name_status {
+        for change in &changes { println!("{}", name_status(change)); }
+    } else if args.stat {
+        print_stat(&blobs, &changes, algorithm);
+    } else {
+        for change in &changes { print_patch(&blobs, change, args.unified, algorithm); }
+    }
+}
+
+// Leading arguments that resolve as revisions are revisions, the rest are paths.
+// "a..b" is the same as "a b"
+fn split_arguments(storage: &Storage, args: &DiffArgs) -> (Vec<String>, Vec<String>) {
+    let mut revisions = vec![];
+    let mut paths = args.paths.clone();
+
+    for (position, argument) in args.revisions.iter().enumerate() {
+        if let Some((from, to)) = argument.split_once("..") {
+            revisions.push(if from.is_empty() { "HEAD".to_string() } else { from.to_string() });
+            revisions.push(if to.is_empty() { "HEAD".to_string() } else { to.to_string() });
+        } else if revision::resolve(storage, argument).is_some() {
+            revisions.push(argument.to_string());
+        } else {
+            paths.splice(0..0, args.revisions[position..].iter().cloned());
+            break;
+        }
+    }
+
+    (revisions, paths)
+}
+
+fn resolve_tree(storage: &Storage, rev: &str) -> String {
+    revision::resolve_tree(storage, rev)
+        .unwrap_or_else(|| die(format!("ambiguous argument '{}': unknown revision or path not in the working tree.", rev)))
+}
+
+fn head_tree(storage: &Storage) -> Option<String> {
+    storage.read_ref("HEAD").map(|commit| storage.read_commit(&commit).tree)
+}
+
+fn read_tree_files(storage: &Storage, tree: Option<&str>) -> BTreeMap<String, DiffEntry> {
+    match tree {
+        Some(tree) => storage
+            .read_tree_files(tree)
+            .into_iter()
+            .map(|(path, object_ref)| {
+                let entry = DiffEntry { path: path.clone(), mode: object_ref.permissions, hash: object_ref.hash };
+                (path, entry)
+            })
+            .collect(),
+        None => BTreeMap::new(),
+    }
+}
+
+fn tree_to_index(storage: &Storage, tree: Option<&str>, paths: &[String]) -> Vec<TreeChange> {
+    let index = storage.read_index();
+    let old = read_tree_files(storage, tree);
+    let new: BTreeMap<String, DiffEntry> =
+        index.entries
+            .iter()
+            .filter(|e| e.stage() == 0)
+            .map(|e| (e.path.clone(), DiffEntry { path: e.path.clone(), mode: e.mode, hash: e.object_hash() }))
+            .collect();
+    let all: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    let changes =
+        all.into_iter()
+            .filter(|path| pathspec::matches_any(paths, path))
+            .filter_map(|path| TreeChange::between(old.get(path).cloned(), new.get(path).cloned()))
+            .collect();
+    let unmerged: BTreeSet<String> =
+        index.entries.iter().filter(|e| e.stage() != 0).map(|e| e.path.clone()).collect();
+
+    with_unmerged(changes, unmerged, paths)
+}
+
+fn index_to_worktree(blobs: &mut Blobs, paths: &[String]) -> Vec<TreeChange> {
+    let storage = blobs.storage;
+    let index = storage.read_index();
+    let checker = StatChecker::new(storage, &index);
+    let mut changes = vec![];
+    let mut unmerged = BTreeSet::new();
+
+    for entry in index.entries.iter().filter(|e| pathspec::matches_any(paths, &e.path)) {
+        if entry.stage() != 0 {
+            unmerged.insert(entry.path.clone());
+            continue;
+        }
+        if entry.flags.intersects(Flags::ASSUME_VALID) { continue; }
+        if entry.extended_flags.intersects(ExtendedFlags::SKIP_WORKTREE) { continue; }
+
+        let old = DiffEntry { path: entry.path.clone(), mode: entry.mode, hash: entry.object_hash() };
+        let new =
+            match checker.check(entry) {
+                FileState::Unchanged => continue,
+                FileState::Deleted => None,
+                FileState::Modified => blobs.read_worktree(&entry.path),
+            };
+
+        changes.extend(TreeChange::between(Some(old), new));
+    }
+
+    with_unmerged(changes, unmerged, paths)
+}
+
+// Compares a tree with the tracked files of the work tree, which are the files of the tree and the index
+fn tree_to_worktree(blobs: &mut Blobs, tree: &str, paths: &[String]) -> Vec<TreeChange> {
+    let storage = blobs.storage;
+    let index = storage.read_index();
+    let checker = StatChecker::new(storage, &index);
+    let old = read_tree_files(storage, Some(tree));
+    let tracked: BTreeSet<&String> =
+        old.keys().chain(index.entries.iter().filter(|e| e.stage() == 0).map(|e| &e.path)).collect();
+    let mut changes = vec![];
+
+    for path in tracked.into_iter().filter(|path| pathspec::matches_any(paths, path)) {
+        let old = old.get(path);
+
+        // Files matching both the tree and a stat-clean index entry need no reading
+        if let (Some(old), Some(entry)) = (old, index.entry(path)) {
+            let same = old.mode == entry.mode && old.hash == entry.object_hash();
+            if same && checker.check(entry) == FileState::Unchanged { continue; }
+        }
+
+        changes.extend(TreeChange::between(old.cloned(), blobs.read_worktree(path)));
+    }
+
+    changes
+}
+
+// Conflicted paths replace any other change of the same path
+fn with_unmerged(mut changes: Vec<TreeChange>, unmerged: BTreeSet<String>, paths: &[String]) -> Vec<TreeChange> {
+    changes.retain(|change| !unmerged.contains(change.path()));
+    changes.extend(
+        unmerged
+            .iter()
+            .filter(|path| pathspec::matches_any(paths, path))
+            .map(|path| TreeChange::unmerged(path))
+    );
+    changes.sort_by(|a, b| a.path().cmp(b.path()));
+
+    changes
+}
+
+impl Blobs<'_> {
+    fn read_worktree(&mut self, path: &str) -> Option<DiffEntry> {
+        let full_path = self.storage.working_root.join(path);
+        let meta = fs::symlink_metadata(&full_path).ok().filter(|meta| !meta.is_dir())?;
+        let content = worktree::read_content(&full_path);
+        let hash = hash::from_bytes(&content);
+        self.worktree.insert(hash.clone(), content);
+
+        Some(DiffEntry { path: path.to_string(), mode: worktree::file_mode(&meta), hash })
+    }
+
+    fn load(&self, entry: Option<&DiffEntry>) -> Vec<u8> {
+        match entry {
+            Some(entry) => match self.worktree.get(&entry.hash) {
+                Some(content) => content.clone(),
+                None => {
+                    let (_, content) = self.storage.read_raw(&entry.hash).unwrap_or_else(|| panic!("object {} not found", entry.hash));
+                    content
+                }
+            },
+            None => vec![],
+        }
+    }
+}
+
+// "M\tpath" or "R087\told\tnew"
+fn name_status(change: &TreeChange) -> String {
+    match change.kind {
+        ChangeKind::Renamed | ChangeKind::Copied => {
+            format!("{}{:03}\t{}\t{}", change.kind.code(), change.similarity, change.old_path(), change.path())
+        }
+        _ => format!("{}\t{}", change.kind.code(), change.path()),
+    }
+}
+
+fn print_patch(blobs: &Blobs, change: &TreeChange, context: usize, algorithm: Algorithm) {
+    match change.kind {
+        ChangeKind::Unmerged => {
+            println!("* Unmerged path {}", change.path());
+            return;
+        }
+        // A type change is shown as the removal of the old file and the addition of the new one
+        ChangeKind::TypeChanged => {
+            print_patch(blobs, &TreeChange::between(change.old.clone(), None).unwrap(), context, algorithm);
+            print_patch(blobs, &TreeChange::between(None, change.new.clone()).unwrap(), context, algorithm);
+            return;
+        }
+        _ => {}
+    }
+
+    let (old_path, new_path) = (change.old_path(), change.path());
+    println!("diff --git a/{} b/{}", old_path, new_path);
+
+    match (&change.old, &change.new) {
+        (None, Some(new)) => println!("new file mode {:o}", new.mode),
+        (Some(old), None) => println!("deleted file mode {:o}", old.mode),
+        (Some(old), Some(new)) if old.mode != new.mode => {
+            println!("old mode {:o}", old.mode);
+            println!("new mode {:o}", new.mode);
+        }
+        _ => {}
+    }
+
+    let verb = if change.kind == ChangeKind::Renamed { "rename" } else { "copy" };
+    if matches!(change.kind, ChangeKind::Renamed | ChangeKind::Copied) {
+        println!("similarity index {}%", change.similarity);
+        println!("{} from {}", verb, old_path);
+        println!("{} to {}", verb, new_path);
+    }
+
+    let old_hash = change.old.as_ref().map_or("0000000", |e| hash::short(&e.hash));
+    let new_hash = change.new.as_ref().map_or("0000000", |e| hash::short(&e.hash));
+    if old_hash == new_hash { return; }
+
+    match (&change.old, &change.new) {
+        (Some(old), Some(new)) if old.mode == new.mode => println!("index {}..{} {:o}", old_hash, new_hash, old.mode),
+        _ => println!("index {}..{}", old_hash, new_hash),
+    }
+
+    let old_content = blobs.load(change.old.as_ref());
+    let new_content = blobs.load(change.new.as_ref());
+    let old_name = if change.old.is_some() { format!("a/{}", old_path) } else { "/dev/null".to_string() };
+    let new_name = if change.new.is_some() { format!("b/{}", new_path) } else { "/dev/null".to_string() };
+
+    if diff::is_binary(&old_content) || diff::is_binary(&new_content) {
+        println!("Binary files {} and {} differ", old_name, new_name);
+        return;
+    }
+
+    let (old_content, new_content) = (String::from_utf8_lossy(&old_content), String::from_utf8_lossy(&new_content));
+    let old_lines = diff::split_lines(&old_content);
+    let new_lines = diff::split_lines(&new_content);
+    let edits = diff::diff_lines(&old_lines, &new_lines, algorithm);
+    if edits.is_empty() { return; }
+
+    println!("--- {}", old_name);
+    println!("+++ {}", new_name);
+    print!("{}", diff::unified(&old_lines, &new_lines, &edits, context));
+}
+
+fn stat(blobs: &Blobs, change: &TreeChange, algorithm: Algorithm) -> Stat {
+    if change.kind == ChangeKind::Unmerged { return Stat::Unmerged; }
+
+    let old_content = blobs.load(change.old.as_ref());
+    let new_content = blobs.load(change.new.as_ref());
+
+    if diff::is_binary(&old_content) || diff::is_binary(&new_content) {
+        return Stat::Binary(old_content.len(), new_content.len());
+    }
+
+    let (old_content, new_content) = (String::from_utf8_lossy(&old_content), String::from_utf8_lossy(&new_content));
+
+    let old_lines = diff::split_lines(&old_content);
+    let new_lines = diff::split_lines(&new_content);
+    let (added, removed) = diff::count_changes(&diff::diff_lines(&old_lines, &new_lines, algorithm));
+
+    Stat::Lines(added, removed)
+}
+
+// "old => new" for renames and copies
+fn stat_name(change: &TreeChange) -> String {
+    match change.kind {
+        ChangeKind::Renamed | ChangeKind::Copied => format!("{} => {}", change.old_path(), change.path()),
+        _ => change.path().to_string(),
+    }
+}
+
+//  src/main.rs | 12 ++++++++----
+//  1 file changed, 8 insertions(+), 4 deletions(-)
+fn print_stat(blobs: &Blobs, changes: &[TreeChange], algorithm: Algorithm) {
+    if changes.is_empty() { return; }
+
+    let stats: Vec<Stat> = changes.iter().map(|change| stat(blobs, change, algorithm)).collect();
+    let names: Vec<String> = changes.iter().map(stat_name).collect();
+    let name_width = names.iter().map(|name| name.chars().count()).max().unwrap_or(0);
+    let max_changes =
+        stats.iter().map(|s| match s { Stat::Lines(a, r) => a + r, _ => 0 }).max().unwrap_or(0);
+    // Binary files print "Bin" in the count column
+    let has_binary = stats.iter().any(|s| matches!(s, Stat::Binary(..)));
+    let count_width = max_changes.to_string().len().max(if has_binary { 3 } else { 0 });
+    let graph_width = STAT_WIDTH.saturating_sub(name_width + count_width + 6).max(10);
+
+    let (mut insertions, mut deletions) = (0, 0);
+    for (name, stat) in names.iter().zip(&stats) {
+        let summary =
+            match *stat {
+                Stat::Unmerged => "Unmerged".to_string(),
+                Stat::Binary(old, new) => format!("Bin {} -> {} bytes", old, new),
+                Stat::Lines(added, removed) => {
+                    insertions += added;
+                    deletions += removed;
+
+                    let (plus, minus) = scale(added, removed, max_changes, graph_width);
+                    format!("{:>width$} {}{}", added + removed, "+".repeat(plus), "-".repeat(minus), width = count_width)
+                        .trim_end()
+                        .to_string()
+                }
+            };
+
+        println!(" {:<width$} | {}", name, summary, width = name_width);
+    }
+
+    let files = if changes.len() == 1 { "file" } else { "files" };
+    let mut line = format!(" {} {} changed", changes.len(), files);
+    if insertions > 0 || deletions == 0 {
+        line.push_str(&format!(", {} insertion{}(+)", insertions, if insertions == 1 { "" } else { "s" }));
+    }
+    if deletions > 0 || insertions == 0 {
+        line.push_str(&format!(", {} deletion{}(-)", deletions, if deletions == 1 { "" } else { "s" }));
+    }
+    println!("{}", line);
+}
+
+// Shrinks the +/- graph to fit, keeping at least one mark for any non-zero count
+fn scale(added: usize, removed: usize, max_changes: usize, width: usize) -> (usize, usize) {
+    if max_changes <= width { return (added, removed); }
+
+    let shrink = |count: usize| if count == 0 { 0 } else { (count * width / max_changes).max(1) };
+    (shrink(added), shrink(removed))
+}
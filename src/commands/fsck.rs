@@ -0,0 +1,23 @@
+use std::process;
+use crate::commands::FsckArgs;
+use crate::fsck::{self, FsckOptions};
+use crate::storage::Storage;
+
+// Exits with 1 when anything is corrupt or missing, unreachable objects alone are not errors
+// rinit fsck
+// rinit fsck --unreachable --connectivity-only
+pub fn call(storage: Storage, args: FsckArgs) {
+    let report = fsck::check(&storage, FsckOptions { connectivity_only: args.connectivity_only });
+
+    for error in &report.errors { eprintln!("{}", error); }
+
+    if args.unreachable {
+        for (hash, object_type) in &report.unreachable { println!("unreachable {} {}", object_type, hash); }
+    }
+    if !args.no_dangling && !args.unreachable {
+        for (hash, object_type) in &report.dangling { println!("dangling {} {}", object_type, hash); }
+    }
+    for notice in &report.notices { eprintln!("{}", notice); }
+
+    if !report.errors.is_empty() { process::exit(1); }
+}
@@ -0,0 +1,48 @@
+use crate::commands::GcArgs;
+use crate::maintenance::{self, RepackOptions};
+use crate::pack::write::PackOptions;
+use crate::storage::Storage;
+use crate::utils::process::die;
+use crate::utils::time;
+
+const DEFAULT_PRUNE_EXPIRE: &str = "2.weeks.ago";
+
+// Packs refs and every reachable object into a single pack with its bitmap unless
+// repack.writeBitmaps is false, writes the commit-graph, then prunes unreachable loose objects older
+// than the grace period, gc.pruneExpire or two weeks by default
+// rinit gc
+// rinit gc --aggressive --prune=now
+pub fn call(storage: Storage, args: GcArgs) {
+    storage.pack_refs();
+
+    let config = storage.config();
+    let pack =
+        if args.aggressive {
+            let window = config.get_int("gc.aggressiveWindow").unwrap_or(250) as usize;
+            let depth = config.get_int("gc.aggressiveDepth").unwrap_or(50) as usize;
+            PackOptions { window, depth, reuse_deltas: false }
+        } else {
+            PackOptions::default()
+        };
+    let write_bitmap = config.get_bool("repack.writeBitmaps").unwrap_or(true);
+    let options = RepackOptions { all: true, delete_redundant: true, loosen_unreachable: true, write_bitmap, pack };
+    maintenance::repack(&storage, options);
+
+    // Keeps the changed-path filters of an existing graph
+    if config.get_bool("gc.writeCommitGraph").unwrap_or(true) {
+        let changed_paths = storage.commit_graph().is_some_and(|graph| graph.has_bloom_filters());
+        maintenance::write_commit_graph(&storage, changed_paths);
+    }
+
+    if args.no_prune { return; }
+
+    let expire =
+        args.prune
+            .clone()
+            .or_else(|| config.get("gc.pruneExpire").map(str::to_string))
+            .unwrap_or_else(|| DEFAULT_PRUNE_EXPIRE.to_string());
+    if expire == "never" { return; }
+
+    let date = time::parse_date(&expire).unwrap_or_else(|| die(format!("malformed expiration date '{}'", expire)));
+    maintenance::prune(&storage, date, false);
+}
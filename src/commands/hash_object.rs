@@ -5,7 +5,7 @@ use crate::utils::files::{read_stdin};
 
 pub fn call(storage: Storage, args: HashObjectArgs) {
     let content =
-        if args.stdin == true {
+        if args.stdin {
             read_stdin()
         } else {
             storage.read_file(args.filepath.unwrap())
@@ -13,7 +13,7 @@ pub fn call(storage: Storage, args: HashObjectArgs) {
 
     let object = GitBlob::new(content.as_str());
 
-    if args.write == true { storage.persist_object(&object); }
+    if args.write { storage.persist_object(&object); }
 
     println!("{:?}", object);
 }
@@ -0,0 +1,47 @@
+use std::fs;
+use std::path::PathBuf;
+use crate::commands::IndexPackArgs;
+use crate::pack::index;
+use crate::pack::ingest::{self, ReceivedPack};
+use crate::storage::Storage;
+use crate::utils::files::read_stdin_bytes;
+use crate::utils::hash;
+use crate::utils::process::die;
+
+// Builds the index of a pack after checking every object in it. Deltas against objects that are
+// not in the pack are resolved from the repository
+// rinit index-pack objects.pack
+// rinit index-pack --stdin --fix-thin < incoming.pack
+pub fn call(storage: Storage, args: IndexPackArgs) {
+    let data =
+        match &args.pack {
+            Some(path) => fs::read(path).unwrap_or_else(|e| die(format!("cannot open packfile '{}': {}", path, e))),
+            None => read_stdin_bytes(),
+        };
+
+    let mut pack = ingest::parse(&data, &|name| storage.read_raw(name)).unwrap_or_else(|e| die(e));
+
+    match args.pack {
+        Some(path) => {
+            let output = args.output.map(PathBuf::from).unwrap_or_else(|| PathBuf::from(&path).with_extension("idx"));
+            fs::write(output, index::write(&ingest::index_entries(&pack), &pack.checksum)).expect("Can't write pack index");
+        }
+        None => {
+            let data = if args.fix_thin && !pack.external.is_empty() { ingest::complete(&data, &mut pack) } else { data };
+            store(&storage, &data, &pack);
+            storage.reload_packs();
+        }
+    }
+
+    println!("{}", hash::to_hex(&pack.checksum));
+}
+
+// Into the repository as "pack-<checksum>.pack", the index last so readers never see a pack
+// without its data
+fn store(storage: &Storage, data: &[u8], pack: &ReceivedPack) {
+    let base = storage.pack_path().join(format!("pack-{}", hash::to_hex(&pack.checksum)));
+
+    fs::create_dir_all(storage.pack_path()).expect("Can't create pack directory");
+    fs::write(base.with_extension("pack"), data).expect("Can't write pack");
+    fs::write(base.with_extension("idx"), index::write(&ingest::index_entries(pack), &pack.checksum)).expect("Can't write pack index");
+}
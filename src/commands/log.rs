@@ -0,0 +1,164 @@
+use crate::commands::LogArgs;
+use crate::pathspec;
+use crate::revision;
+use crate::revwalk::{RevWalk, RevWalkOptions, WalkEntry};
+use crate::storage::Storage;
+use crate::structs::git_commit::GitSignature;
+use crate::tree_diff::{self, ChangeKind, RenameOptions};
+use crate::utils::hash;
+use crate::utils::process::die;
+use crate::utils::time;
+
+// rinit log --oneline -n 10 master ^feature -- src/
+// rinit log --name-status -M
+pub fn call(storage: Storage, args: LogArgs) {
+    let options = RevWalkOptions {
+        first_parent: args.first_parent,
+        topo_order: args.topo_order,
+        reverse: args.reverse,
+        max_count: args.max_count,
+        since: None,
+        until: None,
+        paths: args.paths.clone(),
+    };
+    let mut walk = RevWalk::new(&storage, options);
+
+    let revisions = if args.revisions.is_empty() { vec!["HEAD".to_string()] } else { args.revisions.clone() };
+    for rev in &revisions {
+        match rev.split_once("..") {
+            Some((from, to)) => {
+                walk.hide(&resolve(&storage, if from.is_empty() { "HEAD" } else { from }));
+                walk.push(&resolve(&storage, if to.is_empty() { "HEAD" } else { to }));
+            }
+            None => match rev.strip_prefix('^') {
+                Some(hidden) => walk.hide(&resolve(&storage, hidden)),
+                None => walk.push(&resolve(&storage, rev)),
+            }
+        }
+    }
+
+    let format = match (args.oneline, args.format.as_deref()) {
+        (true, _) | (_, Some("oneline")) => Some("%h %s".to_string()),
+        (_, None) | (_, Some("medium")) => None,
+        (_, Some(custom)) => Some(custom.trim_start_matches("format:").trim_start_matches("tformat:").to_string()),
+    };
+
+    for (index, entry) in walk.run().iter().enumerate() {
+        match &format {
+            Some(format) => println!("{}", expand_format(format, entry)),
+            None => {
+                if index > 0 { println!(); }
+                print!("{}", medium(entry));
+            }
+        }
+
+        if args.name_only || args.name_status {
+            if format.is_none() { println!(); }
+            print!("{}", changed_files(&storage, &args, entry));
+        }
+    }
+}
+
+// Files changed by a commit relative to its first parent
+fn changed_files(storage: &Storage, args: &LogArgs, entry: &WalkEntry) -> String {
+    let parent_tree = entry.commit.parents.first().map(|parent| storage.read_commit(parent).tree);
+    let changes = tree_diff::diff_trees(storage, parent_tree.as_deref(), Some(&entry.commit.tree));
+    let renames = RenameOptions { renames: args.find_renames, copies: None, limit: tree_diff::rename_limit(storage, "diff.renameLimit") };
+    let content = |file: &tree_diff::DiffEntry| storage.read_object(&file.hash).content().to_string();
+    let mut out = String::new();
+
+    for change in tree_diff::detect_renames(changes, &renames, &content) {
+        let path = change.path();
+        let old_path = change.old_path();
+        if !pathspec::matches_any(&args.paths, path) && !pathspec::matches_any(&args.paths, old_path) { continue; }
+
+        let line =
+            match change.kind {
+                _ if args.name_only => path.to_string(),
+                ChangeKind::Renamed | ChangeKind::Copied => {
+                    format!("{}{:03}\t{}\t{}", change.kind.code(), change.similarity, old_path, path)
+                }
+                kind => format!("{}\t{}", kind.code(), path),
+            };
+        out.push_str(&line);
+        out.push('\n');
+    }
+
+    out
+}
+
+fn resolve(storage: &Storage, rev: &str) -> String {
+    revision::resolve(storage, rev).unwrap_or_else(|| {
+        if rev == "HEAD" { die("your current branch does not have any commits yet") }
+        die(format!("bad revision '{}'", rev))
+    })
+}
+
+fn medium(entry: &WalkEntry) -> String {
+    let commit = &entry.commit;
+    let mut out = format!("commit {}\n", entry.hash);
+
+    if commit.parents.len() > 1 {
+        let parents: Vec<&str> = commit.parents.iter().map(|p| hash::short(p)).collect();
+        out.push_str(&format!("Merge: {}\n", parents.join(" ")));
+    }
+    out.push_str(&format!("Author: {} <{}>\n", commit.author.name, commit.author.email));
+    out.push_str(&format!("Date:   {}\n\n", commit.author.date()));
+    for line in commit.message.trim_end().lines() {
+        out.push_str(&format!("    {}\n", line));
+    }
+
+    out
+}
+
+// Supports the commonly used subset of `git log --format` placeholders
+pub fn expand_format(format: &str, entry: &WalkEntry) -> String {
+    let commit = &entry.commit;
+    let mut out = String::new();
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+
+        let placeholder = match chars.next() {
+            Some('H') => entry.hash.clone(),
+            Some('h') => hash::short(&entry.hash).to_string(),
+            Some('T') => commit.tree.clone(),
+            Some('t') => hash::short(&commit.tree).to_string(),
+            Some('P') => commit.parents.join(" "),
+            Some('p') => commit.parents.iter().map(|p| hash::short(p)).collect::<Vec<_>>().join(" "),
+            Some('s') => commit.subject().to_string(),
+            Some('b') => commit.body().to_string(),
+            Some('B') => commit.message.clone(),
+            Some('n') => "\n".to_string(),
+            Some('%') => "%".to_string(),
+            Some(role @ ('a' | 'c')) => {
+                let signature = if role == 'a' { &commit.author } else { &commit.committer };
+                match chars.next() {
+                    Some(field) => signature_field(signature, field).unwrap_or_else(|| format!("%{}{}", role, field)),
+                    None => format!("%{}", role)
+                }
+            }
+            Some(other) => format!("%{}", other),
+            None => "%".to_string(),
+        };
+
+        out.push_str(&placeholder);
+    }
+
+    out
+}
+
+fn signature_field(signature: &GitSignature, field: char) -> Option<String> {
+    match field {
+        'n' => Some(signature.name.clone()),
+        'e' => Some(signature.email.clone()),
+        'd' => Some(signature.date()),
+        'i' => Some(time::format_iso(signature.time, &signature.tz)),
+        't' => Some(signature.time.to_string()),
+        _ => None
+    }
+}
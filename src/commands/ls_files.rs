@@ -0,0 +1,67 @@
+use crate::commands::LsFilesArgs;
+use crate::ignore::IgnoreRules;
+use crate::pathspec;
+use crate::storage::Storage;
+use crate::structs::git_index::GitIndexEntry;
+use crate::utils::process::die;
+use crate::worktree::{self, FileState, StatChecker};
+
+// rinit ls-files --stage
+// rinit ls-files --others --ignored -x 'target'
+// rinit ls-files --others --exclude-standard
+pub fn call(storage: Storage, args: LsFilesArgs) {
+    let index = storage.read_index();
+    let show_stage = args.stage || args.unmerged;
+    let show_cached = args.cached || !(show_stage || args.modified || args.deleted || args.others);
+    let terminator = if args.z { '\0' } else { '\n' };
+
+    let mut rules = if args.exclude_standard { IgnoreRules::load(&storage) } else { IgnoreRules::default() };
+    rules.add_patterns(&args.exclude);
+    if args.ignored && rules.is_empty() {
+        die("ls-files --ignored needs some exclude pattern");
+    }
+
+    let selected = |path: &str| pathspec::matches_any(&args.paths, path);
+    let wanted = |path: &str| selected(path) && rules.is_ignored(path) == args.ignored;
+
+    if args.others {
+        for path in worktree::untracked(&storage, &index) {
+            if wanted(&path) { print!("{}{}", path, terminator); }
+        }
+    }
+
+    if show_cached || show_stage {
+        for entry in &index.entries {
+            if args.unmerged && entry.stage() == 0 { continue; }
+            if !selected(&entry.path) || (args.ignored && !rules.is_ignored(&entry.path)) { continue; }
+
+            if show_stage {
+                print!("{}{}", stage_line(entry), terminator);
+            } else {
+                print!("{}{}", entry.path, terminator);
+            }
+        }
+    }
+
+    if args.modified || args.deleted {
+        let checker = StatChecker::new(&storage, &index);
+        for entry in &index.entries {
+            if !selected(&entry.path) { continue; }
+
+            // Deleted files are reported as modified too, just like Git does
+            match checker.check(entry) {
+                FileState::Deleted => {
+                    if args.deleted { print!("{}{}", entry.path, terminator); }
+                    if args.modified { print!("{}{}", entry.path, terminator); }
+                }
+                FileState::Modified if args.modified => print!("{}{}", entry.path, terminator),
+                _ => {}
+            }
+        }
+    }
+}
+
+// 100644 2f781156939ad540b2434d012446154321e41e03 0	example_file.txt
+fn stage_line(entry: &GitIndexEntry) -> String {
+    format!("{:06o} {} {}\t{}", entry.mode, entry.object_hash(), entry.stage(), entry.path)
+}
@@ -0,0 +1,59 @@
+use crate::commands::LsTreeArgs;
+use crate::pathspec;
+use crate::revision;
+use crate::storage::Storage;
+use crate::structs::git_tree::GitObjectRef;
+use crate::structs::GitObjectType;
+use crate::utils::process::die;
+
+// rinit ls-tree -r -l HEAD src/
+pub fn call(storage: Storage, args: LsTreeArgs) {
+    let tree = revision::resolve_tree(&storage, &args.tree_ish)
+        .unwrap_or_else(|| die(format!("not a tree object: {}", args.tree_ish)));
+
+    list_tree(&storage, &args, &tree, "");
+}
+
+fn list_tree(storage: &Storage, args: &LsTreeArgs, tree: &str, prefix: &str) {
+    for object_ref in storage.read_tree(tree).refs {
+        let path = format!("{}{}", prefix, object_ref.content);
+        let is_tree = object_ref.ref_type == GitObjectType::Tree;
+        let matched = pathspec::matches_any(&args.paths, &path);
+        let leads_inside = args.paths.iter().any(|spec| spec.starts_with(&format!("{}/", path)));
+
+        if !matched && !leads_inside { continue; }
+
+        // "dir/" asks for the contents of dir rather than the entry itself
+        let contents_only = args.paths.iter().any(|spec| spec.trim_end_matches('/') == path && spec.ends_with('/'));
+        let descend = is_tree && (leads_inside || (matched && args.r));
+
+        let show =
+            matched && !(is_tree && contents_only) && (
+                if is_tree { args.d || args.t || !descend } else { !args.d }
+            );
+
+        if show { print_entry(storage, args, &object_ref, &path); }
+        if descend {
+            list_tree(storage, args, &object_ref.hash, &format!("{}/", path));
+        }
+    }
+}
+
+fn print_entry(storage: &Storage, args: &LsTreeArgs, object_ref: &GitObjectRef, path: &str) {
+    if args.name_only {
+        println!("{}", path);
+        return;
+    }
+
+    let mode = format!("{:06o}", object_ref.permissions);
+
+    if args.l {
+        let size = match object_ref.ref_type {
+            GitObjectType::Blob => storage.read_object(&object_ref.hash).content().len().to_string(),
+            _ => "-".to_string()
+        };
+        println!("{} {} {} {:>7}\t{}", mode, object_ref.ref_type, object_ref.hash, size, path);
+    } else {
+        println!("{} {} {}\t{}", mode, object_ref.ref_type, object_ref.hash, path);
+    }
+}
@@ -0,0 +1,212 @@
+use std::fs;
+use std::process;
+use crate::checkout::{self, Files};
+use crate::commands::checkout::report_blocked;
+use crate::commands::commit_tree::signature;
+use crate::commands::MergeArgs;
+use crate::merge::{self, ConflictStyle, Labels, Resolution};
+use crate::revision;
+use crate::reachability::CommitGraph;
+use crate::storage::Storage;
+use crate::structs::git_commit::GitCommit;
+use crate::structs::git_index::GitIndex;
+use crate::structs::git_object::GitObject;
+use crate::utils::files::{read_file, write_file};
+use crate::utils::hash;
+use crate::utils::process::die;
+
+// rinit merge feature
+// rinit merge --no-ff --conflict=diff3 topic
+// rinit merge --abort
+// rinit merge --continue
+pub fn call(storage: Storage, args: MergeArgs) {
+    if args.abort { return abort(&storage); }
+    if args.continue_merge { return continue_merge(&storage); }
+
+    if storage.read_ref("MERGE_HEAD").is_some() {
+        die("You have not concluded your merge (MERGE_HEAD exists).");
+    }
+
+    let name = args.commit.clone().unwrap();
+    let theirs = revision::resolve(&storage, &name).unwrap_or_else(|| die(format!("{} - not something we can merge", name)));
+
+    let mut index = storage.read_index();
+    if index.entries.iter().any(|e| e.stage() != 0) {
+        die("Merging is not possible because you have unmerged files.");
+    }
+
+    // An unborn branch simply takes their history
+    let Some(head) = storage.read_ref("HEAD") else {
+        fast_forward(&storage, &mut index, None, &theirs);
+        return;
+    };
+
+    let mut graph = CommitGraph::new(&storage);
+    let bases = graph.merge_bases(&head, std::slice::from_ref(&theirs));
+    if bases.contains(&theirs) {
+        println!("Already up to date.");
+        return;
+    }
+    if bases.contains(&head) && !args.no_ff {
+        fast_forward(&storage, &mut index, Some(&head), &theirs);
+        return;
+    }
+    if args.ff_only { die("Not possible to fast-forward, aborting."); }
+
+    // The merge commit is written from the index, so it has to match HEAD
+    let ours = checkout::tree_files(&storage, Some(&storage.read_commit(&head).tree));
+    let staged: Vec<String> =
+        index.entries.iter()
+            .filter(|e| ours.get(&e.path) != Some(&(e.mode, e.object_hash())))
+            .map(|e| e.path.clone())
+            .chain(ours.keys().filter(|path| !index.contains(path)).cloned())
+            .collect();
+    if !staged.is_empty() { blocked(&staged); }
+
+    let style =
+        match args.conflict.as_deref().or(storage.config().get("merge.conflictStyle")) {
+            Some("diff3") => ConflictStyle::Diff3,
+            _ => ConflictStyle::Merge,
+        };
+    let labels = Labels {
+        base: match bases.as_slice() {
+            [] => "empty tree".to_string(),
+            [base] => hash::short(base).to_string(),
+            _ => "merged common ancestors".to_string(),
+        },
+        ours: "HEAD".to_string(),
+        theirs: name.clone(),
+    };
+
+    let base = merge::base_tree(&storage, &mut graph, &bases);
+    let results = merge::merge_trees(&storage, base.as_deref(), &storage.read_commit(&head).tree, &storage.read_commit(&theirs).tree, &labels, style);
+    if let Err(paths) = merge::apply(&storage, &mut index, &ours, &results) { blocked(&paths); }
+    storage.save_index(&mut index);
+    storage.update_ref("ORIG_HEAD", &head);
+
+    let conflicts: Vec<&String> =
+        results.values()
+            .filter_map(|resolution| match resolution {
+                Resolution::Conflict { message, .. } => Some(message),
+                Resolution::Clean(_) => None,
+            })
+            .collect();
+    for message in &conflicts { println!("{}", message); }
+
+    let message = args.message.clone().unwrap_or_else(|| merge_message(&storage, &name));
+    if conflicts.is_empty() && !args.no_commit {
+        commit(&storage, &index, vec![head, theirs], &message);
+        println!("Merge made by the 'ort' strategy.");
+        return;
+    }
+
+    // The merge is concluded later with `merge --continue`
+    storage.update_ref("MERGE_HEAD", &theirs);
+    write_file(&storage.root().join("MERGE_MSG"), &format!("{}\n", message));
+
+    if conflicts.is_empty() {
+        println!("Automatic merge went well; stopped before committing as requested");
+    } else {
+        println!("Automatic merge failed; fix conflicts and then commit the result.");
+        process::exit(1);
+    }
+}
+
+fn fast_forward(storage: &Storage, index: &mut GitIndex, head: Option<&str>, theirs: &str) {
+    let current = checkout::tree_files(storage, head.map(|commit| storage.read_commit(commit).tree).as_deref());
+    let target = checkout::tree_files(storage, Some(&storage.read_commit(theirs).tree));
+
+    if let Err(paths) = checkout::switch(storage, index, &current, &target, false, true) {
+        report_blocked(&paths.modified, "Your local changes to the following files would be overwritten by merge:",
+                       "Please commit your changes or stash them before you merge.");
+        report_blocked(&paths.untracked, "The following untracked working tree files would be overwritten by merge:",
+                       "Please move or remove them before you merge.");
+        eprintln!("Aborting");
+        process::exit(1);
+    }
+    storage.save_index(index);
+
+    if let Some(head) = head {
+        storage.update_ref("ORIG_HEAD", head);
+        println!("Updating {}..{}", hash::short(head), hash::short(theirs));
+    }
+    storage.update_head(theirs);
+    println!("Fast-forward");
+}
+
+fn blocked(paths: &[String]) -> ! {
+    report_blocked(paths, "Your local changes to the following files would be overwritten by merge:",
+                   "Please commit your changes or stash them before you merge.");
+    eprintln!("Aborting");
+    process::exit(1);
+}
+
+// "Merge branch 'feature'", naming the current branch unless it is the main one
+fn merge_message(storage: &Storage, name: &str) -> String {
+    let mut message =
+        if storage.ref_exists(&format!("refs/heads/{}", name)) {
+            format!("Merge branch '{}'", name)
+        } else {
+            format!("Merge commit '{}'", name)
+        };
+
+    let current = storage.head_target().and_then(|target| target.strip_prefix("refs/heads/").map(str::to_string));
+    if let Some(branch) = current.filter(|branch| branch != "master" && branch != "main") {
+        message.push_str(&format!(" into {}", branch));
+    }
+
+    message
+}
+
+fn commit(storage: &Storage, index: &GitIndex, parents: Vec<String>, message: &str) -> String {
+    let mut message = message.to_string();
+    if !message.ends_with('\n') { message.push('\n'); }
+
+    let commit = GitCommit {
+        tree: storage.write_tree(index),
+        parents,
+        author: signature("AUTHOR"),
+        committer: signature("COMMITTER"),
+        message,
+    };
+    storage.persist_object(&commit);
+    storage.update_head(&commit.hash());
+
+    commit.hash().to_string()
+}
+
+// Concludes a merge that stopped for conflicts or --no-commit
+fn continue_merge(storage: &Storage) {
+    let merge_head = storage.read_ref("MERGE_HEAD").unwrap_or_else(|| die("There is no merge in progress (MERGE_HEAD missing)."));
+    let index = storage.read_index();
+    if index.entries.iter().any(|e| e.stage() != 0) {
+        eprintln!("error: Committing is not possible because you have unmerged files.");
+        process::exit(1);
+    }
+
+    let head = storage.read_ref("HEAD").unwrap_or_else(|| die("no commit to merge into"));
+    let message = read_file(storage.root().join("MERGE_MSG"));
+    let commit = commit(storage, &index, vec![head, merge_head], &message);
+    clear_state(storage);
+
+    let branch = storage.head_target().and_then(|target| target.strip_prefix("refs/heads/").map(str::to_string));
+    println!("[{} {}] {}", branch.as_deref().unwrap_or("detached HEAD"), hash::short(&commit), message.lines().next().unwrap_or(""));
+}
+
+// Puts the paths the merge touched back to HEAD, local changes to other files survive
+fn abort(storage: &Storage) {
+    if storage.read_ref("MERGE_HEAD").is_none() { die("There is no merge to abort (MERGE_HEAD missing)."); }
+
+    let head: Files =
+        checkout::tree_files(storage, storage.read_ref("HEAD").map(|commit| storage.read_commit(&commit).tree).as_deref());
+    let mut index = storage.read_index();
+    checkout::reset_merge(storage, &mut index, &head);
+    storage.save_index(&mut index);
+
+    clear_state(storage);
+}
+
+fn clear_state(storage: &Storage) {
+    storage.delete_ref("MERGE_HEAD");
+    let _ = fs::remove_file(storage.root().join("MERGE_MSG"));
+}
@@ -0,0 +1,54 @@
+use std::process;
+use crate::commands::MergeBaseArgs;
+use crate::reachability::CommitGraph;
+use crate::revision;
+use crate::storage::Storage;
+use crate::utils::process::die;
+
+// rinit merge-base master feature
+// rinit merge-base --all --octopus master feature topic
+// rinit merge-base --is-ancestor HEAD~2 HEAD
+// rinit merge-base --fork-point master
+pub fn call(storage: Storage, args: MergeBaseArgs) {
+    let commits: Vec<String> =
+        args.commits
+            .iter()
+            .map(|rev| revision::resolve(&storage, rev).unwrap_or_else(|| die(format!("Not a valid object name {}", rev))))
+            .collect();
+    let mut graph = CommitGraph::new(&storage);
+
+    if args.is_ancestor {
+        let [ancestor, descendant] = commits.as_slice() else { die("--is-ancestor takes exactly two commits") };
+        process::exit(if graph.is_ancestor(ancestor, descendant) { 0 } else { 1 });
+    }
+
+    let bases =
+        if args.fork_point {
+            fork_point(&storage, &mut graph, &commits)
+        } else if args.octopus {
+            graph.octopus_bases(&commits)
+        } else {
+            if commits.len() < 2 { die("merge-base needs at least two commits"); }
+            graph.merge_bases(&commits[0], &commits[1..])
+        };
+
+    if bases.is_empty() { process::exit(1); }
+
+    let shown = if args.all { bases.len() } else { 1 };
+    for base in bases.iter().take(shown) { println!("{}", base); }
+}
+
+// The point where `commit` forked from the history of a ref: the most recent position of the ref
+// that `commit` contains. Without reflogs the only position known is the current tip
+fn fork_point(storage: &Storage, graph: &mut CommitGraph, commits: &[String]) -> Vec<String> {
+    let (tip, commit) =
+        match commits {
+            [tip] => (tip.clone(), revision::resolve(storage, "HEAD").unwrap_or_else(|| die("Not a valid object name HEAD"))),
+            [tip, commit] => (tip.clone(), commit.clone()),
+            _ => die("--fork-point takes a ref and at most one commit"),
+        };
+    let positions = [tip];
+    let bases = graph.merge_bases(&commit, &positions);
+
+    positions.into_iter().filter(|position| bases.contains(position)).take(1).collect()
+}
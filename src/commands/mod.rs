@@ -2,6 +2,33 @@ pub mod hash_object;
 pub mod update_index;
 pub mod write_tree;
 pub mod cat_file;
+pub mod commit_tree;
+pub mod log;
+pub mod ls_tree;
+pub mod ls_files;
+pub mod add;
+pub mod check_ignore;
+pub mod status;
+pub mod diff;
+pub mod checkout;
+pub mod switch;
+pub mod restore;
+pub mod read_tree;
+pub mod branch;
+pub mod merge;
+pub mod merge_base;
+pub mod pack_objects;
+pub mod gc;
+pub mod repack;
+pub mod prune;
+pub mod count_objects;
+pub mod fsck;
+pub mod index_pack;
+pub mod unpack_objects;
+pub mod multi_pack_index;
+pub mod commit_graph;
+pub mod rev_list;
+pub mod config;
 
 use clap::{Args, Parser, Subcommand};
 
@@ -20,6 +47,52 @@ pub enum Commands {
     WriteTree(WriteTreeArgs),
     CatFile(CatFileArgs),
     UpdateIndex(UpdateIndexArgs),
+    CommitTree(CommitTreeArgs),
+    Log(LogArgs),
+    LsTree(LsTreeArgs),
+    LsFiles(LsFilesArgs),
+    Add(AddArgs),
+    CheckIgnore(CheckIgnoreArgs),
+    Status(StatusArgs),
+    Diff(DiffArgs),
+    Checkout(CheckoutArgs),
+    Switch(SwitchArgs),
+    Restore(RestoreArgs),
+    ReadTree(ReadTreeArgs),
+    Branch(BranchArgs),
+    Merge(MergeArgs),
+    MergeBase(MergeBaseArgs),
+    PackObjects(PackObjectsArgs),
+    Gc(GcArgs),
+    Repack(RepackArgs),
+    Prune(PruneArgs),
+    CountObjects(CountObjectsArgs),
+    Fsck(FsckArgs),
+    IndexPack(IndexPackArgs),
+    UnpackObjects(UnpackObjectsArgs),
+    MultiPackIndex(MultiPackIndexArgs),
+    CommitGraph(CommitGraphArgs),
+    RevList(RevListArgs),
+    Config(ConfigArgs),
+}
+
+// File modes are written in octal, like 100644
+pub fn parse_mode(value: &str) -> Result<u32, String> {
+    u32::from_str_radix(value, 8).map_err(|_| format!("invalid mode '{}'", value))
+}
+
+// Similarity thresholds as Git reads them: "90%" is 90%, bare digits are a fraction, so "5" is 50%
+pub fn parse_similarity(value: &str) -> Result<u32, String> {
+    let invalid = || format!("invalid similarity '{}'", value);
+
+    match value.strip_suffix('%') {
+        Some(percent) => percent.parse::<u32>().ok().filter(|p| *p <= 100).ok_or_else(invalid),
+        None if !value.is_empty() && value.chars().all(|c| c.is_ascii_digit()) => {
+            let digits = &value[..value.len().min(6)];
+            Ok((digits.parse::<u64>().unwrap() * 100 / 10u64.pow(digits.len() as u32)) as u32)
+        }
+        None => Err(invalid()),
+    }
 }
 
 #[derive(Debug, Args)]
@@ -27,11 +100,28 @@ pub struct UpdateIndexArgs {
     #[arg(long, default_value_t = false)]
     pub add: bool,
     #[arg(long, default_value_t = false)]
-    pub cacheinfo: bool,
+    pub remove: bool,
+    #[arg(long, default_value_t = false)]
+    pub force_remove: bool,
+    #[arg(long, default_value_t = false)]
+    pub refresh: bool,
+    #[arg(long, value_parser = ["+x", "-x"], allow_hyphen_values = true)]
+    pub chmod: Option<String>,
+    #[arg(long, default_value_t = false)]
+    pub assume_unchanged: bool,
+    #[arg(long, default_value_t = false)]
+    pub no_assume_unchanged: bool,
+    #[arg(long, default_value_t = false)]
+    pub skip_worktree: bool,
+    #[arg(long, default_value_t = false)]
+    pub no_skip_worktree: bool,
+    #[arg(long, default_value_t = false)]
+    pub index_info: bool,
+    /// <mode> <hash> <path> or <mode>,<hash>,<path>
+    #[arg(long, num_args = 1..=3, value_delimiter = ',')]
+    pub cacheinfo: Vec<String>,
 
-    pub mode: Option<u32>,
-    pub sha1: Option<String>,
-    pub path: Option<String>,
+    pub paths: Vec<String>,
 }
 
 #[derive(Debug, Args)]
@@ -55,4 +145,468 @@ pub struct CatFileArgs {
     pub p: bool,
 
     pub hash: Option<String>
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Args)]
+pub struct CommitTreeArgs {
+    pub tree: String,
+
+    #[arg(short, action = clap::ArgAction::Append)]
+    pub parents: Vec<String>,
+    #[arg(short, required = true)]
+    pub message: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct LogArgs {
+    #[arg(long, default_value_t = false)]
+    pub oneline: bool,
+    #[arg(short = 'n', long = "max-count")]
+    pub max_count: Option<usize>,
+    #[arg(long, default_value_t = false)]
+    pub first_parent: bool,
+    #[arg(long, default_value_t = false)]
+    pub reverse: bool,
+    #[arg(long, default_value_t = false)]
+    pub topo_order: bool,
+    #[arg(long, visible_alias = "pretty")]
+    pub format: Option<String>,
+    #[arg(long, default_value_t = false)]
+    pub name_only: bool,
+    #[arg(long, default_value_t = false)]
+    pub name_status: bool,
+    #[arg(short = 'M', long, num_args = 0..=1, require_equals = true, default_missing_value = "50",
+          value_parser = parse_similarity)]
+    pub find_renames: Option<u32>,
+
+    pub revisions: Vec<String>,
+    #[arg(last = true)]
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct LsTreeArgs {
+    #[arg(short, default_value_t = false)]
+    pub r: bool,
+    #[arg(short, default_value_t = false)]
+    pub t: bool,
+    #[arg(short, default_value_t = false)]
+    pub d: bool,
+    #[arg(long, default_value_t = false)]
+    pub name_only: bool,
+    #[arg(short, long = "long", default_value_t = false)]
+    pub l: bool,
+
+    pub tree_ish: String,
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct LsFilesArgs {
+    #[arg(short, long, default_value_t = false)]
+    pub cached: bool,
+    #[arg(short, long, default_value_t = false)]
+    pub stage: bool,
+    #[arg(short, long, default_value_t = false)]
+    pub modified: bool,
+    #[arg(short, long, default_value_t = false)]
+    pub deleted: bool,
+    #[arg(short, long, default_value_t = false)]
+    pub others: bool,
+    #[arg(short, long, default_value_t = false)]
+    pub ignored: bool,
+    #[arg(short, long, default_value_t = false)]
+    pub unmerged: bool,
+    #[arg(short, default_value_t = false)]
+    pub z: bool,
+    #[arg(short = 'x', long)]
+    pub exclude: Vec<String>,
+    #[arg(long, default_value_t = false)]
+    pub exclude_standard: bool,
+
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct AddArgs {
+    #[arg(short = 'A', long, default_value_t = false)]
+    pub all: bool,
+    #[arg(short, long, default_value_t = false)]
+    pub update: bool,
+    #[arg(short = 'n', long, default_value_t = false)]
+    pub dry_run: bool,
+    #[arg(short, long, default_value_t = false)]
+    pub verbose: bool,
+    #[arg(short, long, default_value_t = false)]
+    pub force: bool,
+
+    pub pathspecs: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct CheckIgnoreArgs {
+    #[arg(short, long, default_value_t = false)]
+    pub verbose: bool,
+    #[arg(short, long, default_value_t = false)]
+    pub non_matching: bool,
+    #[arg(long, default_value_t = false)]
+    pub no_index: bool,
+    #[arg(long, default_value_t = false)]
+    pub stdin: bool,
+
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct StatusArgs {
+    #[arg(short, long, default_value_t = false)]
+    pub short: bool,
+    #[arg(short, long, default_value_t = false)]
+    pub branch: bool,
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "v1",
+          value_parser = ["v1", "v2", "1", "2"])]
+    pub porcelain: Option<String>,
+    #[arg(short = 'u', long = "untracked-files", num_args = 0..=1,
+          default_value = "normal", default_missing_value = "all", value_parser = ["no", "normal", "all"])]
+    pub untracked_files: String,
+    #[arg(long, default_value_t = false)]
+    pub ignored: bool,
+    #[arg(short, default_value_t = false)]
+    pub z: bool,
+
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct DiffArgs {
+    #[arg(long, visible_alias = "staged", default_value_t = false)]
+    pub cached: bool,
+    #[arg(short = 'U', long = "unified", default_value_t = 3)]
+    pub unified: usize,
+    #[arg(long, default_value_t = false)]
+    pub stat: bool,
+    #[arg(long, default_value_t = false)]
+    pub name_only: bool,
+    #[arg(long, default_value_t = false)]
+    pub name_status: bool,
+    #[arg(long, default_value_t = false)]
+    pub patience: bool,
+    #[arg(long, value_parser = ["default", "myers", "patience"])]
+    pub diff_algorithm: Option<String>,
+    #[arg(short = 'M', long, num_args = 0..=1, require_equals = true, default_missing_value = "50",
+          value_parser = parse_similarity)]
+    pub find_renames: Option<u32>,
+    #[arg(short = 'C', long, num_args = 0..=1, require_equals = true, default_missing_value = "50",
+          value_parser = parse_similarity)]
+    pub find_copies: Option<u32>,
+
+    pub revisions: Vec<String>,
+    #[arg(last = true)]
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct CheckoutArgs {
+    #[arg(short = 'b')]
+    pub new_branch: Option<String>,
+    #[arg(short, long, default_value_t = false)]
+    pub force: bool,
+    #[arg(long, default_value_t = false)]
+    pub detach: bool,
+
+    pub targets: Vec<String>,
+    #[arg(last = true)]
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct SwitchArgs {
+    #[arg(short, long)]
+    pub create: Option<String>,
+    #[arg(short, long, visible_alias = "discard-changes", default_value_t = false)]
+    pub force: bool,
+    #[arg(short, long, default_value_t = false)]
+    pub detach: bool,
+
+    pub target: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct RestoreArgs {
+    #[arg(short, long)]
+    pub source: Option<String>,
+    #[arg(short = 'S', long, default_value_t = false)]
+    pub staged: bool,
+    #[arg(short = 'W', long, default_value_t = false)]
+    pub worktree: bool,
+
+    #[arg(required = true)]
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ReadTreeArgs {
+    #[arg(short, default_value_t = false)]
+    pub m: bool,
+    #[arg(short, default_value_t = false)]
+    pub u: bool,
+    #[arg(long, default_value_t = false)]
+    pub aggressive: bool,
+    #[arg(long)]
+    pub prefix: Option<String>,
+    #[arg(long, default_value_t = false)]
+    pub empty: bool,
+
+    #[arg(num_args = 0..=3)]
+    pub trees: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct BranchArgs {
+    #[arg(short, long, default_value_t = false)]
+    pub delete: bool,
+    #[arg(short = 'D', default_value_t = false)]
+    pub force_delete: bool,
+    #[arg(short = 'm', long = "move", default_value_t = false)]
+    pub rename: bool,
+    #[arg(short = 'M', default_value_t = false)]
+    pub force_rename: bool,
+    #[arg(short, long, default_value_t = false)]
+    pub force: bool,
+    #[arg(short, long, default_value_t = false)]
+    pub verbose: bool,
+    #[arg(short, long, default_value_t = false)]
+    pub list: bool,
+    #[arg(long, num_args = 0..=1, default_missing_value = "HEAD")]
+    pub contains: Option<String>,
+    #[arg(long, num_args = 0..=1, default_missing_value = "HEAD")]
+    pub merged: Option<String>,
+    #[arg(long, num_args = 0..=1, default_missing_value = "HEAD", conflicts_with = "merged")]
+    pub no_merged: Option<String>,
+
+    pub names: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct MergeArgs {
+    #[arg(short, long)]
+    pub message: Option<String>,
+    #[arg(long, default_value_t = false)]
+    pub no_ff: bool,
+    #[arg(long, default_value_t = false, conflicts_with = "no_ff")]
+    pub ff_only: bool,
+    #[arg(long, default_value_t = false)]
+    pub no_commit: bool,
+    #[arg(long, value_parser = ["merge", "diff3"])]
+    pub conflict: Option<String>,
+    #[arg(long, default_value_t = false, conflicts_with_all = ["continue_merge", "commit"])]
+    pub abort: bool,
+    #[arg(long = "continue", default_value_t = false, conflicts_with = "commit")]
+    pub continue_merge: bool,
+
+    #[arg(required_unless_present_any = ["abort", "continue_merge"])]
+    pub commit: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct MergeBaseArgs {
+    #[arg(short, long, default_value_t = false)]
+    pub all: bool,
+    #[arg(long, default_value_t = false, conflicts_with_all = ["octopus", "fork_point"])]
+    pub is_ancestor: bool,
+    #[arg(long, default_value_t = false, conflicts_with = "fork_point")]
+    pub octopus: bool,
+    #[arg(long, default_value_t = false)]
+    pub fork_point: bool,
+
+    #[arg(required = true)]
+    pub commits: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct PackObjectsArgs {
+    #[arg(long, default_value_t = 10)]
+    pub window: usize,
+    #[arg(long, default_value_t = 50)]
+    pub depth: usize,
+    #[arg(long, default_value_t = false)]
+    pub stdout: bool,
+    #[arg(long, default_value_t = false)]
+    pub no_reuse_delta: bool,
+    // Stdin has revisions instead of objects, "^rev" excludes what is reachable from rev
+    #[arg(long, default_value_t = false)]
+    pub revs: bool,
+    // Everything reachable from refs and HEAD, like `--revs` with all of them
+    #[arg(long, default_value_t = false)]
+    pub all: bool,
+    #[arg(long, default_value_t = false)]
+    pub no_use_bitmap_index: bool,
+
+    #[arg(conflicts_with = "stdout")]
+    pub base_name: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct GcArgs {
+    // Unreachable loose objects older than this date are pruned, "now" prunes all of them
+    #[arg(long, conflicts_with = "no_prune")]
+    pub prune: Option<String>,
+    #[arg(long, default_value_t = false)]
+    pub no_prune: bool,
+    #[arg(long, default_value_t = false)]
+    pub aggressive: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RepackArgs {
+    #[arg(short, long, default_value_t = false)]
+    pub all: bool,
+    #[arg(short, long, default_value_t = false)]
+    pub delete: bool,
+    // Computes deltas again instead of reusing the ones of existing packs
+    #[arg(short = 'f', long = "no-reuse-delta", default_value_t = false)]
+    pub no_reuse_delta: bool,
+    // Bitmaps need every reachable object in the pack
+    #[arg(short = 'b', long, requires = "all", default_value_t = false)]
+    pub write_bitmap_index: bool,
+    #[arg(long, default_value_t = 10)]
+    pub window: usize,
+    #[arg(long, default_value_t = 50)]
+    pub depth: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct PruneArgs {
+    #[arg(short = 'n', long, default_value_t = false)]
+    pub dry_run: bool,
+    #[arg(short, long, default_value_t = false)]
+    pub verbose: bool,
+    #[arg(long)]
+    pub expire: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct CountObjectsArgs {
+    #[arg(short, long, default_value_t = false)]
+    pub verbose: bool,
+    #[arg(short = 'H', long, default_value_t = false)]
+    pub human_readable: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct FsckArgs {
+    // Also lists unreachable objects that other unreachable objects point to
+    #[arg(long, default_value_t = false)]
+    pub unreachable: bool,
+    #[arg(long, default_value_t = false)]
+    pub no_dangling: bool,
+    #[arg(long, default_value_t = false)]
+    pub connectivity_only: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct IndexPackArgs {
+    // Reads the pack from stdin and stores it in the repository
+    #[arg(long, default_value_t = false)]
+    pub stdin: bool,
+    // Appends the local bases of a thin pack to it, only with --stdin
+    #[arg(long, default_value_t = false, requires = "stdin")]
+    pub fix_thin: bool,
+    #[arg(short, conflicts_with = "stdin")]
+    pub output: Option<String>,
+
+    #[arg(required_unless_present = "stdin", conflicts_with = "stdin")]
+    pub pack: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct UnpackObjectsArgs {
+    #[arg(short = 'n', default_value_t = false)]
+    pub dry_run: bool,
+    #[arg(short, default_value_t = false)]
+    pub quiet: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct MultiPackIndexArgs {
+    #[arg(value_parser = ["write", "verify"])]
+    pub action: String,
+}
+
+#[derive(Debug, Args)]
+pub struct CommitGraphArgs {
+    #[arg(value_parser = ["write", "verify"])]
+    pub action: String,
+    // Stores Bloom filters of the paths each commit changed
+    #[arg(long, default_value_t = false)]
+    pub changed_paths: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RevListArgs {
+    // Trees and blobs too, with the path they were found at
+    #[arg(long, default_value_t = false)]
+    pub objects: bool,
+    #[arg(long, default_value_t = false)]
+    pub use_bitmap_index: bool,
+    #[arg(long, default_value_t = false)]
+    pub all: bool,
+    #[arg(long, default_value_t = false)]
+    pub count: bool,
+    #[arg(short = 'n', long = "max-count")]
+    pub max_count: Option<usize>,
+    #[arg(long, visible_alias = "after")]
+    pub since: Option<String>,
+    #[arg(long, visible_alias = "before")]
+    pub until: Option<String>,
+    // What to do with trees and blobs missing from the repository: fail, skip them, or print them
+    // prefixed with "?"
+    #[arg(long, value_parser = ["error", "allow-any", "print"], default_value = "error")]
+    pub missing: String,
+    // The revisions that follow are excluded, and ^rev ones included. Empty, it applies to --all
+    #[arg(long, num_args = 0..)]
+    pub not: Option<Vec<String>>,
+
+    pub revisions: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigArgs {
+    // Which file to read or write, all of them are read by default and the repository's is written
+    #[arg(long, default_value_t = false, conflicts_with_all = ["system", "local", "file"])]
+    pub global: bool,
+    #[arg(long, default_value_t = false, conflicts_with_all = ["local", "file"])]
+    pub system: bool,
+    #[arg(long, default_value_t = false, conflicts_with = "file")]
+    pub local: bool,
+    #[arg(short, long)]
+    pub file: Option<String>,
+
+    // Without an action, a key alone is read and a key with a value is set
+    #[arg(long, default_value_t = false)]
+    pub get: bool,
+    #[arg(long, default_value_t = false)]
+    pub get_all: bool,
+    #[arg(long, default_value_t = false)]
+    pub set: bool,
+    #[arg(long, default_value_t = false)]
+    pub add: bool,
+    #[arg(long, default_value_t = false)]
+    pub unset: bool,
+    #[arg(long, default_value_t = false)]
+    pub unset_all: bool,
+    #[arg(short, long, default_value_t = false)]
+    pub list: bool,
+
+    // Values are checked and shown as this type, colors as their escape sequence
+    #[arg(long = "type", value_parser = ["bool", "int", "path", "color"])]
+    pub value_type: Option<String>,
+    #[arg(long, default_value_t = false)]
+    pub show_origin: bool,
+    #[arg(long, default_value_t = false)]
+    pub show_scope: bool,
+
+    pub key: Option<String>,
+    #[arg(conflicts_with_all = ["get", "get_all", "unset", "unset_all", "list"])]
+    pub value: Option<String>,
+}
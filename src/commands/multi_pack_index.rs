@@ -0,0 +1,28 @@
+use std::fs;
+use std::process;
+use crate::commands::MultiPackIndexArgs;
+use crate::maintenance;
+use crate::pack::midx::{self, MIDX_FILE};
+use crate::storage::Storage;
+use crate::utils::process::die;
+
+// rinit multi-pack-index write
+// rinit multi-pack-index verify
+pub fn call(storage: Storage, args: MultiPackIndexArgs) {
+    match args.action.as_str() {
+        "write" => maintenance::write_multi_pack_index(&storage),
+        "verify" => verify(&storage),
+        _ => unreachable!(),
+    }
+}
+
+fn verify(storage: &Storage) {
+    let path = storage.pack_path().join(MIDX_FILE);
+    if !path.is_file() { return; }
+
+    let data = fs::read(&path).unwrap_or_else(|e| die(format!("could not read {}: {}", path.display(), e)));
+    let problems = midx::verify(&data, &storage.packs());
+    for problem in &problems { eprintln!("error: multi-pack-index: {}", problem); }
+
+    if !problems.is_empty() { process::exit(1); }
+}
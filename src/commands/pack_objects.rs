@@ -0,0 +1,65 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use crate::commands::PackObjectsArgs;
+use crate::pack::write::{self, PackOptions};
+use crate::reachability;
+use crate::revision;
+use crate::storage::Storage;
+use crate::utils::files::read_stdin;
+use crate::utils::process::die;
+
+// Object names are read from stdin, one per line, optionally followed by the path they were found at
+// rinit pack-objects --window=20 --depth=30 .rinit/objects/pack/pack < objects
+// rinit pack-objects --stdout < objects > out.pack
+// With --revs, stdin has revisions and the objects they reach are packed, bitmaps help finding them
+// echo -e "master\n^v1" | rinit pack-objects --revs --stdout > out.pack
+// rinit pack-objects --all .rinit/objects/pack/pack
+pub fn call(storage: Storage, args: PackObjectsArgs) {
+    let list = if args.revs || args.all { revision_objects(&storage, &args) } else { named_objects(&storage) };
+
+    let options = PackOptions { window: args.window, depth: args.depth, reuse_deltas: !args.no_reuse_delta };
+    let objects = write::collect(&storage, &list);
+    let pack = write::build(&storage, &objects, options);
+
+    if args.stdout {
+        io::stdout().write_all(&pack.data).expect("Can't write pack");
+    } else {
+        let prefix = args.base_name.map(PathBuf::from).unwrap_or_else(|| storage.pack_path().join("pack"));
+        println!("{}", write::write_files(&prefix, &pack));
+        storage.reload_packs();
+    }
+
+    eprintln!("Total {} (delta {}), reused {} (delta {})", objects.len(), pack.deltas, pack.reused, pack.reused);
+}
+
+fn named_objects(storage: &Storage) -> Vec<(String, String)> {
+    read_stdin()
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (name, path) = line.split_once(' ').unwrap_or((line, ""));
+            let hash = storage.find_object(name).unwrap_or_else(|| die(format!("unable to find object {}", name)));
+            (hash, path.to_string())
+        })
+        .collect()
+}
+
+fn revision_objects(storage: &Storage, args: &PackObjectsArgs) -> Vec<(String, String)> {
+    let mut include = vec![];
+    let mut exclude = vec![];
+
+    if args.all {
+        include.extend(storage.list_refs("refs/").into_iter().map(|(_, hash)| hash));
+        include.extend(storage.read_ref("HEAD"));
+    }
+    if args.revs {
+        for line in read_stdin().lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (rev, list) = match line.strip_prefix('^') { Some(rev) => (rev, &mut exclude), None => (line, &mut include) };
+            list.push(revision::resolve(storage, rev).unwrap_or_else(|| die(format!("bad revision '{}'", rev))));
+        }
+    }
+
+    let use_bitmaps = !args.no_use_bitmap_index && storage.config().get_bool("pack.useBitmaps").unwrap_or(true);
+    reachability::objects_between(storage, &include, &exclude, use_bitmaps)
+}
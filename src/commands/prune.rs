@@ -0,0 +1,22 @@
+use crate::commands::PruneArgs;
+use crate::maintenance;
+use crate::storage::Storage;
+use crate::utils::process::die;
+use crate::utils::time;
+
+// Deletes unreachable loose objects, by default whatever their age
+// rinit prune -n
+// rinit prune -v --expire=2.weeks.ago
+pub fn call(storage: Storage, args: PruneArgs) {
+    let expire =
+        match &args.expire {
+            Some(date) => time::parse_date(date).unwrap_or_else(|| die(format!("malformed expiration date '{}'", date))),
+            None => time::now(),
+        };
+
+    for (hash, object_type) in maintenance::prune(&storage, expire, args.dry_run) {
+        if args.dry_run || args.verbose { println!("{} {}", hash, object_type); }
+    }
+
+    if !args.dry_run { maintenance::prune_packed(&storage); }
+}
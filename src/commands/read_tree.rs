@@ -0,0 +1,56 @@
+use std::process;
+use crate::checkout::{self, Files};
+use crate::commands::ReadTreeArgs;
+use crate::revision;
+use crate::storage::Storage;
+use crate::structs::git_index::GitIndex;
+use crate::utils::process::die;
+
+// rinit read-tree HEAD
+// rinit read-tree --prefix=vendor/lib <tree>
+// rinit read-tree -m -u HEAD feature
+// rinit read-tree -m -u <base> HEAD feature
+pub fn call(storage: Storage, args: ReadTreeArgs) {
+    if args.u && !args.m { die("-u is meaningless without -m"); }
+    if args.prefix.is_some() && args.m { die("--prefix cannot be used with -m"); }
+
+    let trees: Vec<Files> =
+        args.trees
+            .iter()
+            .map(|rev| {
+                let tree = revision::resolve_tree(&storage, rev).unwrap_or_else(|| die(format!("failed to unpack tree object {}", rev)));
+                checkout::tree_files(&storage, Some(&tree))
+            })
+            .collect();
+
+    let mut index = if args.m || args.prefix.is_some() { storage.read_index() } else { GitIndex::empty() };
+    if args.m && trees.len() > 1 && index.entries.iter().any(|e| e.stage() != 0) {
+        die("you need to resolve your current index first");
+    }
+
+    match (trees.as_slice(), args.m) {
+        ([], _) if args.empty => index.entries.clear(),
+        ([], _) => die("usage: rinit read-tree [-m [-u] [--aggressive]] [--prefix=<prefix>] [<tree-ish1> [<tree-ish2> [<tree-ish3>]]]"),
+        ([tree], _) => {
+            checkout::read_tree(&mut index, tree, args.prefix.as_deref().unwrap_or("")).unwrap_or_else(|e| die(e));
+        }
+        ([current, target], true) => {
+            if let Err(blocked) = checkout::switch(&storage, &mut index, current, target, false, args.u) {
+                for path in blocked.modified { eprintln!("error: Entry '{}' not uptodate. Cannot merge.", path); }
+                for path in blocked.untracked {
+                    eprintln!("error: Untracked working tree file '{}' would be overwritten by merge.", path);
+                }
+                process::exit(128);
+            }
+        }
+        ([base, ours, theirs], true) => {
+            if let Err(blocked) = checkout::three_way(&storage, &mut index, [base, ours, theirs], args.aggressive, args.u) {
+                for path in blocked { eprintln!("error: Entry '{}' would be overwritten by merge. Cannot merge.", path); }
+                process::exit(128);
+            }
+        }
+        (_, _) => die("multiple trees can only be read with -m"),
+    }
+
+    storage.save_index(&mut index);
+}
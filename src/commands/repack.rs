@@ -0,0 +1,24 @@
+use crate::commands::RepackArgs;
+use crate::maintenance::{self, RepackOptions};
+use crate::pack::write::PackOptions;
+use crate::storage::Storage;
+
+// Without -a only the loose objects are packed, -d then deletes them and packs made redundant. With
+// -a, -b also writes a reachability bitmap for the new pack
+// rinit repack -a -d
+// rinit repack -a -d -b
+// rinit repack -a -d -f --window=250 --depth=50
+pub fn call(storage: Storage, args: RepackArgs) {
+    let options = RepackOptions {
+        all: args.all,
+        delete_redundant: args.delete,
+        loosen_unreachable: args.all,
+        write_bitmap: args.write_bitmap_index || storage.config().get_bool("repack.writeBitmaps").unwrap_or(false),
+        pack: PackOptions { window: args.window, depth: args.depth, reuse_deltas: !args.no_reuse_delta },
+    };
+
+    match maintenance::repack(&storage, options) {
+        Some(checksum) => println!("pack-{}", checksum),
+        None => println!("Nothing new to pack."),
+    }
+}
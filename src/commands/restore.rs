@@ -0,0 +1,35 @@
+use crate::checkout::{self, RestoreTarget};
+use crate::commands::checkout::{index_files, report_unmatched};
+use crate::commands::RestoreArgs;
+use crate::revision;
+use crate::storage::Storage;
+use crate::utils::process::die;
+
+// rinit restore src/main.rs
+// rinit restore --staged src/
+// rinit restore --source=HEAD~2 --staged --worktree Cargo.toml
+pub fn call(storage: Storage, args: RestoreArgs) {
+    let target = RestoreTarget { worktree: args.worktree || !args.staged, staged: args.staged, overlay: false };
+
+    // The work tree alone is restored from the index, anything involving the index from HEAD
+    let source =
+        match args.source.as_deref() {
+            Some(rev) => Some(rev),
+            None if target.staged => Some("HEAD"),
+            None => None,
+        };
+    let files =
+        match source {
+            Some("HEAD") if storage.read_ref("HEAD").is_none() => checkout::Files::new(),
+            Some(rev) => {
+                let tree = revision::resolve_tree(&storage, rev).unwrap_or_else(|| die(format!("could not resolve {}", rev)));
+                checkout::tree_files(&storage, Some(&tree))
+            }
+            None => index_files(&storage),
+        };
+
+    let mut index = storage.read_index();
+    let unmatched = checkout::restore(&storage, &mut index, &files, &args.paths, target);
+    report_unmatched(&unmatched);
+    storage.save_index(&mut index);
+}
@@ -0,0 +1,107 @@
+use std::collections::HashSet;
+use crate::commands::RevListArgs;
+use crate::reachability::{self, CommitGraph};
+use crate::revision;
+use crate::revwalk::{RevWalk, RevWalkOptions};
+use crate::storage::Storage;
+use crate::utils::process::die;
+use crate::utils::time;
+
+// Commits reachable from the revisions but not from the excluded ones, newest first. --objects
+// adds the trees and blobs of the listed commits that excluded commits do not have, with their
+// paths; --use-bitmap-index reads them from pack bitmaps when the walk is not limited
+// rinit rev-list master ^feature
+// rinit rev-list --count --since=2.weeks.ago v1..master
+// rinit rev-list --objects new-tip --not --all
+// rinit rev-list --objects --missing=print --all
+pub fn call(storage: Storage, args: RevListArgs) {
+    let mut include = vec![];
+    let mut exclude = vec![];
+
+    if args.all {
+        let mut tips: Vec<String> = storage.list_refs("refs/").into_iter().map(|(_, hash)| hash).collect();
+        tips.extend(storage.read_ref("HEAD"));
+
+        match args.not.as_ref().is_some_and(Vec::is_empty) {
+            true => exclude.extend(tips),
+            false => include.extend(tips),
+        }
+    }
+    let mut graph = CommitGraph::new(&storage);
+    for rev in &args.revisions { add_revision(&storage, &mut graph, rev, &mut include, &mut exclude); }
+    for rev in args.not.iter().flatten() { add_revision(&storage, &mut graph, rev, &mut exclude, &mut include); }
+
+    let since = args.since.as_deref().map(parse_date);
+    let until = args.until.as_deref().map(parse_date);
+    let limited = args.max_count.is_some() || since.is_some() || until.is_some();
+
+    if args.objects && args.use_bitmap_index && !limited && !args.count && args.missing == "error" {
+        for (hash, path) in reachability::objects_between(&storage, &include, &exclude, true) {
+            print_object(&hash, &path);
+        }
+        return;
+    }
+
+    let options = RevWalkOptions { max_count: args.max_count, since, until, ..RevWalkOptions::default() };
+    let mut walk = RevWalk::new(&storage, options);
+    for hash in &include { walk.push(hash); }
+    for hash in &exclude { walk.hide(hash); }
+    let commits = walk.run_commits();
+
+    if args.count { return println!("{}", commits.len()); }
+    for entry in &commits { println!("{}", entry.hash); }
+    if !args.objects { return; }
+
+    // What excluded commits have is left out: the trees of the excluded revisions and of the
+    // excluded parents of listed commits
+    let listed: HashSet<&str> = commits.iter().map(|entry| entry.hash.as_str()).collect();
+    let mut hidden: Vec<String> = exclude.iter().map(|hash| storage.read_commit(hash).tree).collect();
+    for parent in commits.iter().flat_map(|entry| &entry.parents) {
+        if !listed.contains(parent.as_str()) && exclude.iter().any(|hash| graph.is_ancestor(parent, hash)) {
+            hidden.push(storage.read_commit(parent).tree);
+        }
+    }
+
+    let trees: Vec<String> = commits.iter().map(|entry| entry.tree.clone()).collect();
+    let mut missing = vec![];
+    let check = (args.missing != "error").then_some(&mut missing);
+    for (hash, path) in reachability::commit_objects(&storage, &trees, &hidden, check) {
+        print_object(&hash, &path);
+    }
+
+    if args.missing == "print" {
+        for hash in missing { println!("?{}", hash); }
+    }
+}
+
+// Adds a revision to the `positive` side, its ^excluded form or the left of a range to the other.
+// The symmetric range `a...b` excludes the merge bases of both sides
+fn add_revision(storage: &Storage, graph: &mut CommitGraph, rev: &str, positive: &mut Vec<String>, negative: &mut Vec<String>) {
+    let side = |name: &str| resolve(storage, if name.is_empty() { "HEAD" } else { name });
+
+    if let Some((left, right)) = rev.split_once("...") {
+        let (left, right) = (side(left), side(right));
+        negative.extend(graph.merge_bases(&left, std::slice::from_ref(&right)));
+        positive.extend([left, right]);
+    } else if let Some((from, to)) = rev.split_once("..") {
+        negative.push(side(from));
+        positive.push(side(to));
+    } else {
+        match rev.strip_prefix('^') {
+            Some(hidden) => negative.push(resolve(storage, hidden)),
+            None => positive.push(resolve(storage, rev)),
+        }
+    }
+}
+
+fn print_object(hash: &str, path: &str) {
+    if path.is_empty() { println!("{}", hash) } else { println!("{} {}", hash, path) }
+}
+
+fn parse_date(value: &str) -> i64 {
+    time::parse_date(value).unwrap_or_else(|| die(format!("malformed date '{}'", value)))
+}
+
+fn resolve(storage: &Storage, rev: &str) -> String {
+    revision::resolve(storage, rev).unwrap_or_else(|| die(format!("bad revision '{}'", rev)))
+}
@@ -0,0 +1,155 @@
+use crate::commands::StatusArgs;
+use crate::status::{self, Side, Status, UntrackedMode};
+use crate::storage::Storage;
+use crate::utils::hash;
+use crate::worktree;
+
+const NULL_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// rinit status
+// rinit status -s -b
+// rinit status --porcelain=v2 --branch
+pub fn call(storage: Storage, args: StatusArgs) {
+    let untracked_mode = match args.untracked_files.as_str() {
+        "no" => UntrackedMode::No,
+        "all" => UntrackedMode::All,
+        _ => UntrackedMode::Normal,
+    };
+    // Like Git, status opportunistically refreshes the index so later runs can trust stat data
+    let mut index = storage.read_index();
+    if worktree::refresh_stats(&storage, &mut index).0 { storage.save_index(&mut index); }
+
+    let status = status::compute(&storage, &args.paths, untracked_mode, args.ignored);
+    let terminator = if args.z { "\0" } else { "\n" };
+
+    match args.porcelain.as_deref() {
+        Some("v2" | "2") => print_porcelain_v2(&status, args.branch, terminator),
+        Some(_) => print_short(&status, args.branch, terminator),
+        None if args.short => print_short(&status, args.branch, terminator),
+        None => print_long(&status),
+    }
+}
+
+fn print_short(status: &Status, branch: bool, terminator: &str) {
+    if branch {
+        match (&status.branch, &status.head) {
+            (Some(name), Some(_)) => print!("## {}{}", name, terminator),
+            (Some(name), None) => print!("## No commits yet on {}{}", name, terminator),
+            (None, _) => print!("## HEAD (no branch){}", terminator),
+        }
+    }
+
+    let mut lines: Vec<(String, String)> =
+        status.changes
+            .iter()
+            .map(|e| {
+                let x = e.staged.map_or(' ', |c| c.code());
+                let y = e.unstaged.map_or(' ', |c| c.code());
+                (e.path.clone(), format!("{}{} {}", x, y, e.path))
+            })
+            .collect();
+    lines.extend(status.conflicts.iter().map(|c| (c.path.clone(), format!("{} {}", c.code(), c.path))));
+    lines.sort();
+
+    for (_, line) in lines { print!("{}{}", line, terminator); }
+    for path in &status.untracked { print!("?? {}{}", path, terminator); }
+    for path in &status.ignored { print!("!! {}{}", path, terminator); }
+}
+
+fn print_porcelain_v2(status: &Status, branch: bool, terminator: &str) {
+    if branch {
+        print!("# branch.oid {}{}", status.head.as_deref().unwrap_or("(initial)"), terminator);
+        print!("# branch.head {}{}", status.branch.as_deref().unwrap_or("(detached)"), terminator);
+    }
+
+    let mode = |side: &Side| side.as_ref().map_or(0, |(mode, _)| *mode);
+    let hash = |side: &Side| side.as_ref().map_or(NULL_HASH.to_string(), |(_, hash)| hash.clone());
+
+    let mut lines: Vec<(String, String)> =
+        status.changes
+            .iter()
+            .map(|e| {
+                let x = e.staged.map_or('.', |c| c.code());
+                let y = e.unstaged.map_or('.', |c| c.code());
+                let line = format!(
+                    "1 {}{} N... {:06o} {:06o} {:06o} {} {} {}",
+                    x, y, mode(&e.head), mode(&e.index), e.worktree_mode.unwrap_or(0),
+                    hash(&e.head), hash(&e.index), e.path
+                );
+                (e.path.clone(), line)
+            })
+            .collect();
+
+    lines.extend(status.conflicts.iter().map(|c| {
+        let line = format!(
+            "u {} N... {:06o} {:06o} {:06o} {:06o} {} {} {} {}",
+            c.code(), mode(&c.stages[0]), mode(&c.stages[1]), mode(&c.stages[2]), c.worktree_mode.unwrap_or(0),
+            hash(&c.stages[0]), hash(&c.stages[1]), hash(&c.stages[2]), c.path
+        );
+        (c.path.clone(), line)
+    }));
+    lines.sort();
+
+    for (_, line) in lines { print!("{}{}", line, terminator); }
+    for path in &status.untracked { print!("? {}{}", path, terminator); }
+    for path in &status.ignored { print!("! {}{}", path, terminator); }
+}
+
+fn print_long(status: &Status) {
+    match (&status.branch, &status.head) {
+        (Some(name), _) => println!("On branch {}", name),
+        (None, Some(head)) => println!("HEAD detached at {}", hash::short(head)),
+        (None, None) => println!("Not currently on any branch."),
+    }
+    if status.head.is_none() { println!("\nNo commits yet"); }
+
+    let staged: Vec<_> = status.changes.iter().filter_map(|e| e.staged.map(|c| (c, &e.path))).collect();
+    if !staged.is_empty() {
+        println!("\nChanges to be committed:");
+        println!("  (use \"rinit restore --staged <file>...\" to unstage)");
+        for (change, path) in staged {
+            println!("\t{:<12}{}", format!("{}:", change.describe()), path);
+        }
+    }
+
+    if !status.conflicts.is_empty() {
+        println!("\nUnmerged paths:");
+        println!("  (use \"rinit add <file>...\" to mark resolution)");
+        for conflict in &status.conflicts {
+            println!("\t{:<17}{}", format!("{}:", conflict.describe()), conflict.path);
+        }
+    }
+
+    let unstaged: Vec<_> = status.changes.iter().filter_map(|e| e.unstaged.map(|c| (c, &e.path))).collect();
+    if !unstaged.is_empty() {
+        println!("\nChanges not staged for commit:");
+        println!("  (use \"rinit add <file>...\" to update what will be committed)");
+        println!("  (use \"rinit restore <file>...\" to discard changes in working directory)");
+        for (change, path) in &unstaged {
+            println!("\t{:<12}{}", format!("{}:", change.describe()), path);
+        }
+    }
+
+    if !status.untracked.is_empty() {
+        println!("\nUntracked files:");
+        println!("  (use \"rinit add <file>...\" to include in what will be committed)");
+        for path in &status.untracked { println!("\t{}", path); }
+    }
+
+    if !status.ignored.is_empty() {
+        println!("\nIgnored files:");
+        println!("  (use \"rinit add -f <file>...\" to include in what will be committed)");
+        for path in &status.ignored { println!("\t{}", path); }
+    }
+
+    println!();
+    if status.has_staged() || !status.conflicts.is_empty() { return; }
+
+    if !unstaged.is_empty() {
+        println!("no changes added to commit (use \"rinit add\")");
+    } else if !status.untracked.is_empty() {
+        println!("nothing added to commit but untracked files present (use \"rinit add\" to track)");
+    } else if status.is_clean() {
+        println!("nothing to commit, working tree clean");
+    }
+}
@@ -0,0 +1,31 @@
+use crate::commands::checkout::{switch_to, Destination};
+use crate::commands::SwitchArgs;
+use crate::revision;
+use crate::storage::Storage;
+use crate::utils::process::die;
+
+// rinit switch feature
+// rinit switch -c topic HEAD~2
+// rinit switch --detach v1.0
+pub fn call(storage: Storage, args: SwitchArgs) {
+    let destination =
+        match (args.create, args.target) {
+            (Some(name), start) => Destination::NewBranch(name, start),
+            (None, None) => die("missing branch or commit argument"),
+            (None, Some(rev)) if args.detach => {
+                let commit = revision::resolve(&storage, &rev).unwrap_or_else(|| die(format!("invalid reference: {}", rev)));
+                Destination::Detached(rev, commit)
+            }
+            (None, Some(name)) => {
+                if storage.read_ref(&format!("refs/heads/{}", name)).is_some() {
+                    Destination::Branch(name)
+                } else if revision::resolve(&storage, &name).is_some() {
+                    die(format!("a branch is expected, got '{}'", name))
+                } else {
+                    die(format!("invalid reference: {}", name))
+                }
+            }
+        };
+
+    switch_to(&storage, destination, args.force);
+}
@@ -0,0 +1,22 @@
+use crate::commands::UnpackObjectsArgs;
+use crate::pack::ingest;
+use crate::storage::Storage;
+use crate::utils::files::read_stdin_bytes;
+use crate::utils::process::die;
+
+// Writes every object of a pack read from stdin as a loose object, objects already present are
+// left alone
+// rinit unpack-objects < incoming.pack
+// rinit unpack-objects -n < incoming.pack
+pub fn call(storage: Storage, args: UnpackObjectsArgs) {
+    let data = read_stdin_bytes();
+    let pack = ingest::parse(&data, &|name| storage.read_raw(name)).unwrap_or_else(|e| die(e));
+
+    let total = pack.objects.len();
+    for object in pack.objects {
+        if args.dry_run || storage.object_exists(&object.name) { continue; }
+        storage.persist_raw(&object.name, object.object_type, &object.content).unwrap_or_else(|e| die(e));
+    }
+
+    if !args.quiet { eprintln!("Unpacking objects: 100% ({}/{}), done.", total, total); }
+}
@@ -1,19 +1,127 @@
-use crate::commands::UpdateIndexArgs;
+use std::fs;
+use crate::commands::{parse_mode, UpdateIndexArgs};
 use crate::storage::Storage;
-use crate::structs::git_index::GitIndexEntry;
+use crate::structs::flags::{ExtendedFlags, Flags};
+use crate::structs::git_index::{GitIndex, GitIndexEntry};
+use crate::utils::files::read_stdin;
+use crate::utils::hash;
+use crate::utils::process::die;
+use crate::worktree;
 
 // git update-index --add --cacheinfo 100644 83baae61804e65cc73a7201a7252750c76066a30 Cargo.lock
+// git update-index --add --chmod=+x script.sh
+// git ls-tree -r HEAD | git update-index --index-info
 pub fn call(storage: Storage, args: UpdateIndexArgs) {
-    if args.add == true { add_entry(storage, args) }
+    let mut index = storage.read_index();
+
+    if args.index_info { index_info(&storage, &mut index); }
+    if !args.cacheinfo.is_empty() { cacheinfo(&storage, &mut index, &args); }
+    if args.refresh { refresh(&storage, &mut index); }
+
+    for path in &args.paths {
+        if marks_only(&args) {
+            mark(&mut index, &args, path);
+        } else {
+            update_path(&storage, &mut index, &args, path);
+        }
+
+        if let Some(chmod) = &args.chmod {
+            let entry = index.entry_mut(path).unwrap_or_else(|| die(format!("Unable to mark file {}", path)));
+            entry.mode = if chmod == "+x" { 0o100755 } else { 0o100644 };
+        }
+    }
+
+    storage.save_index(&mut index);
 }
 
-fn add_entry(storage: Storage, args: UpdateIndexArgs) {
-    let mut index = storage.read_index();
-    let UpdateIndexArgs { mode, sha1, path, .. } = args;
-    let entry = GitIndexEntry::from_path(path.unwrap(), mode, sha1);
+fn marks_only(args: &UpdateIndexArgs) -> bool {
+    args.assume_unchanged || args.no_assume_unchanged || args.skip_worktree || args.no_skip_worktree
+}
+
+fn update_path(storage: &Storage, index: &mut GitIndex, args: &UpdateIndexArgs, path: &str) {
+    if args.force_remove {
+        index.remove_entry(path);
+        return;
+    }
+
+    let exists = fs::symlink_metadata(storage.working_root.join(path)).is_ok_and(|m| !m.is_dir());
+
+    if !exists {
+        if args.remove {
+            index.remove_entry(path);
+            return;
+        }
+        die(format!("{}: does not exist and --remove not passed", path));
+    }
+
+    if !args.add && index.entry(path).is_none() {
+        die(format!("{}: cannot add to the index - missing --add option?", path));
+    }
+
+    index.add_entry(worktree::stage_file(storage, path).unwrap_or_else(|error| die(error)));
+}
+
+fn mark(index: &mut GitIndex, args: &UpdateIndexArgs, path: &str) {
+    let entry = index.entry_mut(path).unwrap_or_else(|| die(format!("Unable to mark file {}", path)));
+
+    if args.assume_unchanged { entry.flags.insert(Flags::ASSUME_VALID); }
+    if args.no_assume_unchanged { entry.flags.remove(Flags::ASSUME_VALID); }
+    if args.skip_worktree { entry.extended_flags.insert(ExtendedFlags::SKIP_WORKTREE); }
+    if args.no_skip_worktree { entry.extended_flags.remove(ExtendedFlags::SKIP_WORKTREE); }
+}
+
+fn cacheinfo(storage: &Storage, index: &mut GitIndex, args: &UpdateIndexArgs) {
+    let usage = || die("option 'cacheinfo' expects <mode>,<sha1>,<path>");
+    let [mode, hash, path] = args.cacheinfo.as_slice() else { usage() };
+    let mode = parse_mode(mode).unwrap_or_else(|e| die(e));
+    if hash::to_bytes(hash).is_err() { usage() }
+    check_object(storage, mode, hash, path);
+
+    if !args.add && !index.contains(path) {
+        die(format!("{}: cannot add to the index - missing --add option?", path));
+    }
+
+    index.add_entry(GitIndexEntry::new(path, mode, hash, Default::default()));
+}
+
+// Accepts the output formats of `ls-tree` and `ls-files --stage`, a zero mode removes the path:
+//   <mode> SP <sha1> TAB <path>
+//   <mode> SP <type> SP <sha1> TAB <path>
+//   <mode> SP <sha1> SP <stage> TAB <path>
+fn index_info(storage: &Storage, index: &mut GitIndex) {
+    for line in read_stdin().lines().filter(|l| !l.is_empty()) {
+        let (info, path) = line.split_once('\t').unwrap_or_else(|| die(format!("malformed index info {}", line)));
+        let fields: Vec<&str> = info.split(' ').collect();
+
+        let (mode, hash, stage) = match fields.as_slice() {
+            [mode, hash] => (*mode, *hash, 0),
+            [mode, hash, stage] if stage.len() == 1 => (*mode, *hash, stage.parse().unwrap_or(0)),
+            [mode, _type, hash] => (*mode, *hash, 0),
+            _ => die(format!("malformed index info {}", line))
+        };
+        let mode = parse_mode(mode).unwrap_or_else(|e| die(e));
+
+        if mode == 0 {
+            index.remove_entry(path);
+            continue;
+        }
+        if hash::to_bytes(hash).is_err() { die(format!("malformed index info {}", line)) }
+        check_object(storage, mode, hash, path);
+
+        let mut entry = GitIndexEntry::new(path, mode, hash, Default::default());
+        entry.set_stage(stage);
+        index.add_entry(entry);
+    }
+}
+
+fn check_object(storage: &Storage, mode: u32, hash: &str, path: &str) {
+    if !storage.object_exists(hash) { die(format!("invalid object {:o} {} for '{}'", mode, hash, path)) }
+}
 
-    index.add_entry(entry);
-    storage.save_index(&index);
+fn refresh(storage: &Storage, index: &mut GitIndex) {
+    let (_changed, needs_update) = worktree::refresh_stats(storage, index);
 
-    println!("{:?}", index);
+    for path in needs_update {
+        println!("{}: needs update", path);
+    }
 }
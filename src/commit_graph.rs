@@ -0,0 +1,419 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use sha2::{Digest, Sha256};
+
+use crate::pack::index::{invalid, HASH_SIZE};
+use crate::storage::Storage;
+use crate::tree_diff;
+use crate::utils::hash;
+
+pub const GRAPH_SIGNATURE: &[u8; 4] = b"CGPH";
+pub const GRAPH_VERSION: u8 = 1;
+// Object names are SHA-256
+const HASH_VERSION: u8 = 2;
+
+const CHUNK_FANOUT: u32 = 0x4f49_4446;
+const CHUNK_NAMES: u32 = 0x4f49_444c;
+const CHUNK_DATA: u32 = 0x4344_4154;
+const CHUNK_EXTRA_EDGES: u32 = 0x4544_4745;
+const CHUNK_BLOOM_INDEXES: u32 = 0x4249_4458;
+const CHUNK_BLOOM_DATA: u32 = 0x4244_4154;
+
+// Parent slots of the commit data: no parent, or the start of a list of parents in the extra edges
+const NO_PARENT: u32 = 0x7000_0000;
+const EXTRA_EDGES: u32 = 0x8000_0000;
+const LAST_EDGE: u32 = 0x8000_0000;
+
+// Changed-path filters as Git writes them: 10 bits per path, 7 hash functions, version 2 of the
+// murmur3 hashing, and no filter for commits changing more than 512 paths
+const BLOOM_VERSION: u32 = 2;
+const BLOOM_HASHES: u32 = 7;
+const BLOOM_BITS_PER_ENTRY: u32 = 10;
+const BLOOM_MAX_CHANGES: usize = 512;
+const BLOOM_SEEDS: (u32, u32) = (0x293a_e76f, 0x7e64_6e2c);
+
+// A commit as the graph stores it
+#[derive(Debug, Clone)]
+pub struct GraphCommit {
+    pub tree: String,
+    pub parents: Vec<String>,
+    // Topological level: 1 for root commits, one more than the highest parent otherwise
+    pub generation: u32,
+    pub time: i64,
+}
+
+// "objects/info/commit-graph": the commits of the repository sorted by name, each with its tree,
+// parents, generation number and commit date, and optionally a Bloom filter of the paths it
+// changed against its first parent. Walks read it instead of parsing commit objects
+#[derive(Debug)]
+pub struct CommitGraphFile {
+    pub fanout: [u32; 256],
+    pub names: Vec<[u8; HASH_SIZE]>,
+    data: Vec<u8>,
+    extra_edges: Vec<u32>,
+    bloom: Option<(Vec<u32>, Vec<u8>)>,
+}
+
+impl CommitGraphFile {
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read(path)?)
+    }
+
+    // Everything later lookups index with is checked here, a corrupt file is rejected as a whole
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        if data.len() < 8 + HASH_SIZE { return Err(invalid("Commit-graph is too short")); }
+        let (body, trailer) = data.split_at(data.len() - HASH_SIZE);
+        if Sha256::digest(body)[..] != *trailer { return Err(invalid("Commit-graph checksum does not match")); }
+        let mut reader = Cursor::new(body);
+
+        let mut signature = [0u8; 4];
+        reader.read_exact(&mut signature)?;
+        if &signature != GRAPH_SIGNATURE || reader.read_u8()? != GRAPH_VERSION {
+            return Err(invalid("Not a version 1 commit-graph"));
+        }
+        if reader.read_u8()? != HASH_VERSION { return Err(invalid("Commit-graph uses another hash")); }
+        let chunk_count = reader.read_u8()? as usize;
+        if reader.read_u8()? != 0 { return Err(invalid("Split commit-graphs are not supported")); }
+
+        let mut table = Vec::with_capacity(chunk_count + 1);
+        for _ in 0..=chunk_count {
+            table.push((reader.read_u32::<BigEndian>()?, reader.read_u64::<BigEndian>()? as usize));
+        }
+        let chunk = |id: u32| -> io::Result<Option<&[u8]>> {
+            let Some(i) = table[..chunk_count].iter().position(|(found, _)| *found == id) else { return Ok(None) };
+            body.get(table[i].1..table[i + 1].1).map(Some).ok_or_else(|| invalid("Commit-graph chunk out of bounds"))
+        };
+        let required = |id: u32| chunk(id)?.ok_or_else(|| invalid("Missing commit-graph chunk"));
+
+        let mut fanout = [0u32; 256];
+        let mut reader = Cursor::new(required(CHUNK_FANOUT)?);
+        for count in fanout.iter_mut() { *count = reader.read_u32::<BigEndian>()?; }
+        if fanout.windows(2).any(|pair| pair[0] > pair[1]) { return Err(invalid("Commit-graph fanout is not sorted")); }
+        let count = fanout[255] as usize;
+
+        let names_chunk = required(CHUNK_NAMES)?;
+        let names: Vec<[u8; HASH_SIZE]> = names_chunk.chunks_exact(HASH_SIZE).map(|name| name.try_into().unwrap()).collect();
+        let data = required(CHUNK_DATA)?.to_vec();
+        if names_chunk.len() != count * HASH_SIZE || data.len() != count * (HASH_SIZE + 16) {
+            return Err(invalid("Commit-graph commit count does not match"));
+        }
+        // Lookups binary search the fanout bucket of a name's first byte
+        let bucket = |first: usize| (if first == 0 { 0 } else { fanout[first - 1] as usize })..fanout[first] as usize;
+        if names.windows(2).any(|pair| pair[0] >= pair[1]) || names.iter().enumerate().any(|(i, name)| !bucket(name[0] as usize).contains(&i)) {
+            return Err(invalid("Commit-graph names do not match the fanout"));
+        }
+
+        let words = |chunk: &[u8]| chunk.chunks_exact(4).map(|word| u32::from_be_bytes(word.try_into().unwrap())).collect::<Vec<u32>>();
+        let extra_edges = chunk(CHUNK_EXTRA_EDGES)?.map(words).unwrap_or_default();
+        if data.chunks_exact(HASH_SIZE + 16).any(|record| !valid_parents(&record[HASH_SIZE..], count, &extra_edges)) {
+            return Err(invalid("Commit-graph parent out of bounds"));
+        }
+
+        let bloom =
+            match (chunk(CHUNK_BLOOM_INDEXES)?, chunk(CHUNK_BLOOM_DATA)?) {
+                (Some(indexes), Some(filters)) if filters.len() >= 12 => {
+                    let settings = words(&filters[..12]);
+                    // Filters made with other settings can't be queried with ours
+                    if settings != [BLOOM_VERSION, BLOOM_HASHES, BLOOM_BITS_PER_ENTRY] {
+                        None
+                    } else if indexes.len() != count * 4 || words(indexes).windows(2).any(|pair| pair[0] > pair[1]) {
+                        return Err(invalid("Commit-graph Bloom filter indexes are corrupt"));
+                    } else {
+                        Some((words(indexes), filters[12..].to_vec()))
+                    }
+                }
+                _ => None,
+            };
+
+        Ok(Self { fanout, names, data, extra_edges, bloom })
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn has_bloom_filters(&self) -> bool {
+        self.bloom.is_some()
+    }
+
+    pub fn position(&self, commit: &str) -> Option<usize> {
+        let name = hash::to_bytes(commit).ok()?;
+        let first = name[0] as usize;
+        let start = if first == 0 { 0 } else { self.fanout[first - 1] as usize };
+
+        self.names[start..self.fanout[first] as usize].binary_search(&name).ok().map(|i| start + i)
+    }
+
+    pub fn get(&self, commit: &str) -> Option<GraphCommit> {
+        self.position(commit).map(|position| self.at(position))
+    }
+
+    // Tree, two parent slots, then the generation in the top 30 bits of a 64-bit word whose low
+    // 34 bits are the commit date
+    fn at(&self, position: usize) -> GraphCommit {
+        let record = &self.data[position * (HASH_SIZE + 16)..(position + 1) * (HASH_SIZE + 16)];
+        let word = |i: usize| u32::from_be_bytes(record[HASH_SIZE + i * 4..HASH_SIZE + i * 4 + 4].try_into().unwrap());
+
+        let mut parents = vec![];
+        if word(0) != NO_PARENT { parents.push(self.name(word(0))); }
+        match word(1) {
+            NO_PARENT => {}
+            slot if slot & EXTRA_EDGES != 0 => {
+                for edge in &self.extra_edges[(slot & !EXTRA_EDGES) as usize..] {
+                    parents.push(self.name(edge & !LAST_EDGE));
+                    if edge & LAST_EDGE != 0 { break; }
+                }
+            }
+            slot => parents.push(self.name(slot)),
+        }
+
+        GraphCommit {
+            tree: hash::to_hex(&record[..HASH_SIZE]),
+            parents,
+            generation: word(2) >> 2,
+            time: (((word(2) & 0x3) as i64) << 32) | word(3) as i64,
+        }
+    }
+
+    fn name(&self, position: u32) -> String {
+        hash::to_hex(&self.names[position as usize])
+    }
+
+    // Whether a commit may have changed `path` against its first parent. None when the commit has
+    // no filter, or one too large to tell
+    pub fn maybe_changed(&self, commit: &str, path: &str) -> Option<bool> {
+        let (indexes, filters) = self.bloom.as_ref()?;
+        let position = self.position(commit)?;
+        let start = if position == 0 { 0 } else { indexes[position - 1] as usize };
+        let filter = filters.get(start..indexes[position] as usize)?;
+        if filter.is_empty() || filter == [0xff] { return None; }
+
+        Some(bloom_keys(path, filter.len()).all(|bit| filter[bit / 8] & (1 << (bit % 8)) != 0))
+    }
+}
+
+// Both parent slots of a commit record point at commits of the graph, the second one possibly at a
+// list of them in the extra edges that ends within the chunk
+fn valid_parents(slots: &[u8], count: usize, extra_edges: &[u32]) -> bool {
+    let slot = |i: usize| u32::from_be_bytes(slots[i * 4..i * 4 + 4].try_into().unwrap());
+    let position = |slot: u32| (slot as usize) < count;
+
+    let first = slot(0) == NO_PARENT || position(slot(0));
+    let second =
+        match slot(1) {
+            NO_PARENT => true,
+            slot if slot & EXTRA_EDGES != 0 => {
+                let edges = extra_edges.get((slot & !EXTRA_EDGES) as usize..).unwrap_or_default();
+                match edges.iter().position(|edge| edge & LAST_EDGE != 0) {
+                    Some(last) => edges[..=last].iter().all(|edge| position(edge & !LAST_EDGE)),
+                    None => false,
+                }
+            }
+            slot => position(slot),
+        };
+
+    first && second
+}
+
+// Every commit reachable from `tips`, parents before children so generations can be computed
+// in one pass
+pub fn write(storage: &Storage, tips: &[String], changed_paths: bool) -> Vec<u8> {
+    let mut commits: HashMap<String, GraphCommit> = HashMap::new();
+    let mut order = vec![];
+    let mut stack: Vec<(String, bool)> = tips.iter().map(|tip| (tip.clone(), false)).collect();
+    // The SHA-1 names of objects from Git's packs don't fit, nor do commits on top of them
+    let mut excluded: HashSet<String> = HashSet::new();
+    let fits = |hash: &str| hash.len() == HASH_SIZE * 2;
+
+    while let Some((hash, expanded)) = stack.pop() {
+        if expanded {
+            let parents = commits[&hash].parents.clone();
+            if !fits(&commits[&hash].tree) || parents.iter().any(|parent| excluded.contains(parent)) {
+                excluded.insert(hash);
+                continue;
+            }
+            let generation = parents.iter().map(|parent| commits[parent].generation).max().unwrap_or(0) + 1;
+            commits.get_mut(&hash).unwrap().generation = generation;
+            order.push(hash);
+            continue;
+        }
+        if commits.contains_key(&hash) || excluded.contains(&hash) { continue; }
+        if !fits(&hash) {
+            excluded.insert(hash);
+            continue;
+        }
+
+        let commit = storage.read_commit(&hash);
+        let graph_commit = GraphCommit { tree: commit.tree, parents: commit.parents, generation: 0, time: commit.committer.time };
+        stack.push((hash.clone(), true));
+        stack.extend(graph_commit.parents.iter().filter(|parent| !commits.contains_key(*parent)).map(|parent| (parent.clone(), false)));
+        commits.insert(hash, graph_commit);
+    }
+
+    let mut names: Vec<[u8; HASH_SIZE]> = order.iter().map(|hash| hash::to_bytes(hash).unwrap()).collect();
+    names.sort();
+    let positions: HashMap<String, u32> = names.iter().enumerate().map(|(i, name)| (hash::to_hex(name), i as u32)).collect();
+
+    let mut fanout_chunk = vec![];
+    let mut count = 0;
+    for first in 0..=255u8 {
+        count += names.iter().filter(|name| name[0] == first).count();
+        fanout_chunk.write_u32::<BigEndian>(count as u32).unwrap();
+    }
+
+    let mut data_chunk = vec![];
+    let mut edges_chunk = vec![];
+    let mut bloom_indexes = vec![];
+    let mut bloom_data = vec![];
+    for name in &names {
+        let hash = hash::to_hex(name);
+        let commit = &commits[&hash];
+        data_chunk.extend_from_slice(&hash::to_bytes(&commit.tree).unwrap());
+
+        let parents: Vec<u32> = commit.parents.iter().map(|parent| positions[parent]).collect();
+        data_chunk.write_u32::<BigEndian>(parents.first().copied().unwrap_or(NO_PARENT)).unwrap();
+        match parents.len() {
+            0 | 1 => data_chunk.write_u32::<BigEndian>(NO_PARENT).unwrap(),
+            2 => data_chunk.write_u32::<BigEndian>(parents[1]).unwrap(),
+            _ => {
+                data_chunk.write_u32::<BigEndian>(EXTRA_EDGES | (edges_chunk.len() / 4) as u32).unwrap();
+                for (i, parent) in parents[1..].iter().enumerate() {
+                    let last = if i == parents.len() - 2 { LAST_EDGE } else { 0 };
+                    edges_chunk.write_u32::<BigEndian>(parent | last).unwrap();
+                }
+            }
+        }
+        data_chunk.write_u32::<BigEndian>((commit.generation << 2) | ((commit.time >> 32) as u32 & 0x3)).unwrap();
+        data_chunk.write_u32::<BigEndian>(commit.time as u32).unwrap();
+
+        if changed_paths {
+            bloom_data.extend(bloom_filter(storage, commit));
+            bloom_indexes.write_u32::<BigEndian>(bloom_data.len() as u32).unwrap();
+        }
+    }
+
+    let oid_chunk: Vec<u8> = names.iter().flat_map(|name| name.to_vec()).collect();
+    let mut chunks = vec![(CHUNK_FANOUT, fanout_chunk), (CHUNK_NAMES, oid_chunk), (CHUNK_DATA, data_chunk)];
+    if !edges_chunk.is_empty() { chunks.push((CHUNK_EXTRA_EDGES, edges_chunk)); }
+    if changed_paths {
+        let mut header = vec![];
+        for value in [BLOOM_VERSION, BLOOM_HASHES, BLOOM_BITS_PER_ENTRY] { header.write_u32::<BigEndian>(value).unwrap(); }
+        header.extend(bloom_data);
+
+        chunks.push((CHUNK_BLOOM_INDEXES, bloom_indexes));
+        chunks.push((CHUNK_BLOOM_DATA, header));
+    }
+
+    let mut file = vec![];
+    file.extend_from_slice(GRAPH_SIGNATURE);
+    file.extend_from_slice(&[GRAPH_VERSION, HASH_VERSION, chunks.len() as u8, 0]);
+
+    let mut offset = file.len() + (chunks.len() + 1) * 12;
+    for (id, chunk) in &chunks {
+        file.write_u32::<BigEndian>(*id).unwrap();
+        file.write_u64::<BigEndian>(offset as u64).unwrap();
+        offset += chunk.len();
+    }
+    file.write_u32::<BigEndian>(0).unwrap();
+    file.write_u64::<BigEndian>(offset as u64).unwrap();
+
+    for (_, chunk) in chunks { file.extend(chunk); }
+    let checksum = Sha256::digest(&file);
+    file.extend_from_slice(&checksum);
+
+    file
+}
+
+// Problems found comparing the file with the commit objects it describes
+pub fn verify(storage: &Storage, data: &[u8]) -> Vec<String> {
+    let graph =
+        match CommitGraphFile::parse(data) {
+            Ok(graph) => graph,
+            Err(error) => return vec![error.to_string()],
+        };
+
+    let mut problems = vec![];
+
+    for position in 0..graph.len() {
+        let hash = hash::to_hex(&graph.names[position]);
+        if !storage.object_exists(&hash) {
+            problems.push(format!("commit {} is missing from the object store", hash));
+            continue;
+        }
+
+        let stored = graph.at(position);
+        let commit = storage.read_commit(&hash);
+        if stored.tree != commit.tree { problems.push(format!("root tree of commit {} does not match", hash)); }
+        if stored.parents != commit.parents { problems.push(format!("parents of commit {} do not match", hash)); }
+        if stored.time != commit.committer.time { problems.push(format!("commit date of {} does not match", hash)); }
+
+        let expected = stored.parents.iter().filter_map(|parent| graph.get(parent)).map(|parent| parent.generation).max().unwrap_or(0) + 1;
+        if stored.generation != expected { problems.push(format!("generation of commit {} is {}, expected {}", hash, stored.generation, expected)); }
+    }
+
+    problems
+}
+
+// The changed paths against the first parent and the directories containing them
+fn bloom_filter(storage: &Storage, commit: &GraphCommit) -> Vec<u8> {
+    let parent_tree = commit.parents.first().map(|parent| storage.read_commit(parent).tree);
+    let mut paths = BTreeSet::new();
+    for change in tree_diff::diff_trees(storage, parent_tree.as_deref(), Some(&commit.tree)) {
+        let path = change.path();
+        paths.extend(path.match_indices('/').map(|(i, _)| path[..i].to_string()));
+        paths.insert(path.to_string());
+    }
+
+    if paths.is_empty() { return vec![0]; }
+    if paths.len() > BLOOM_MAX_CHANGES { return vec![0xff]; }
+
+    let size = (paths.len() * BLOOM_BITS_PER_ENTRY as usize).div_ceil(8);
+    let mut filter = vec![0u8; size];
+    for path in &paths {
+        for bit in bloom_keys(path, size) { filter[bit / 8] |= 1 << (bit % 8); }
+    }
+
+    filter
+}
+
+// Double hashing: the bits of a path are h0 + i * h1 for each of the hash functions
+fn bloom_keys(path: &str, size: usize) -> impl Iterator<Item = usize> {
+    let h0 = murmur3(BLOOM_SEEDS.0, path.as_bytes());
+    let h1 = murmur3(BLOOM_SEEDS.1, path.as_bytes());
+    let bits = (size * 8) as u64;
+
+    (0..BLOOM_HASHES).map(move |i| (h0.wrapping_add(i.wrapping_mul(h1)) as u64 % bits) as usize)
+}
+
+// 32-bit MurmurHash3 over unsigned bytes
+fn murmur3(seed: u32, data: &[u8]) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+    let mix = |mut k: u32| {
+        k = k.wrapping_mul(C1);
+        k = k.rotate_left(15);
+        k.wrapping_mul(C2)
+    };
+
+    let mut hash = seed;
+    let blocks = data.chunks_exact(4);
+    let tail = blocks.remainder();
+    for block in blocks {
+        hash ^= mix(u32::from_le_bytes(block.try_into().unwrap()));
+        hash = hash.rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64);
+    }
+
+    let mut k = 0u32;
+    for (i, byte) in tail.iter().enumerate() { k ^= (*byte as u32) << (8 * i); }
+    if !tail.is_empty() { hash ^= mix(k); }
+
+    hash ^= data.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^ (hash >> 16)
+}
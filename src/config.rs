@@ -0,0 +1,683 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::storage::Storage;
+use crate::utils::process::die;
+use crate::utils::wildmatch;
+
+// Files included by included files count, a loop stops here
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+// Where a variable comes from, from the lowest precedence to the highest. `Command` is a file given
+// on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    System,
+    Global,
+    Local,
+    Command,
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self { Scope::System => "system", Scope::Global => "global", Scope::Local => "local", Scope::Command => "command" };
+        write!(f, "{}", name)
+    }
+}
+
+// A variable as read. `key` is "section.subsection.name" with the section and the name lowercased,
+// a name without "=" has no value, which counts as true
+#[derive(Debug, Clone)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: Option<String>,
+    pub scope: Scope,
+    pub origin: PathBuf,
+}
+
+// The variables of the system, global and repository config files in the order they were read,
+// included files in place of their include. Later values take precedence
+#[derive(Debug, Default)]
+pub struct Config {
+    entries: Vec<ConfigEntry>,
+}
+
+impl Config {
+    // Files outside of the repository are not for a command to fix: a broken system or global
+    // file is skipped with a warning, a broken repository one is still fatal
+    pub fn load(storage: &Storage) -> Self {
+        let mut config = Config::default();
+
+        for (scope, path) in config_files(storage) {
+            let lenient = matches!(scope, Scope::System | Scope::Global);
+            config.read(&path, scope, &storage.root(), 0, lenient);
+        }
+
+        config
+    }
+
+    // A single file and what it includes
+    pub fn from_file(storage: &Storage, path: &Path, scope: Scope) -> Self {
+        let mut config = Config::default();
+        config.read(path, scope, &storage.root(), 0, false);
+
+        config
+    }
+
+    pub fn entries(&self) -> &[ConfigEntry] {
+        &self.entries
+    }
+
+    // The value that wins, an empty one for a name without "="
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.get_entry(key).map(|entry| entry.value.as_deref().unwrap_or(""))
+    }
+
+    pub fn get_entry(&self, key: &str) -> Option<&ConfigEntry> {
+        let key = canonical_key(key).unwrap_or_else(|| key.to_lowercase());
+
+        self.entries.iter().rev().find(|entry| entry.key == key)
+    }
+
+    // Every value of a multi-valued variable, in order
+    pub fn get_all(&self, key: &str) -> Vec<&ConfigEntry> {
+        let key = canonical_key(key).unwrap_or_else(|| key.to_lowercase());
+
+        self.entries.iter().filter(|entry| entry.key == key).collect()
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        parse_bool(self.get_entry(key)?.value.as_deref())
+    }
+
+    pub fn get_int(&self, key: &str) -> Option<i64> {
+        parse_int(self.get(key)?)
+    }
+
+    pub fn get_path(&self, key: &str) -> Option<PathBuf> {
+        self.get(key).map(expand_path)
+    }
+
+    fn read(&mut self, path: &Path, scope: Scope, git_dir: &Path, depth: usize, lenient: bool) {
+        let content =
+            match fs::read_to_string(path) {
+                Ok(content) => content,
+                Err(error) if lenient && path.exists() => return broken(format!("unable to read {}: {}", path.display(), error), lenient),
+                Err(_) => return,
+            };
+
+        if depth > MAX_INCLUDE_DEPTH {
+            return broken(format!("exceeded maximum include depth ({}) including {}", MAX_INCLUDE_DEPTH, path.display()), lenient);
+        }
+
+        let file =
+            match ConfigFile::parse(&content) {
+                Ok(file) => file,
+                Err(line) => return broken(format!("bad config line {} in file {}", line, path.display()), lenient),
+            };
+        for entry in file.entries {
+            let include = included_path(&entry.key, entry.value.as_deref(), path, git_dir);
+            self.entries.push(ConfigEntry { key: entry.key, value: entry.value, scope, origin: path.to_path_buf() });
+
+            if let Some(included) = include { self.read(&included, scope, git_dir, depth + 1, lenient); }
+        }
+    }
+}
+
+fn broken(error: String, lenient: bool) {
+    if !lenient { die(error); }
+    eprintln!("warning: {}, ignoring the file", error);
+}
+
+// The files read by default, lowest precedence first: /etc/gitconfig, then $XDG_CONFIG_HOME/git/config
+// and ~/.gitconfig, then the repository's. $GIT_CONFIG_SYSTEM and $GIT_CONFIG_GLOBAL replace the
+// first ones, $GIT_CONFIG_NOSYSTEM skips the system file
+fn config_files(storage: &Storage) -> Vec<(Scope, PathBuf)> {
+    let mut files = vec![];
+
+    if env::var_os("GIT_CONFIG_NOSYSTEM").is_none() {
+        files.push((Scope::System, scope_path(storage, Scope::System)));
+    }
+    match env::var_os("GIT_CONFIG_GLOBAL") {
+        Some(global) => files.push((Scope::Global, PathBuf::from(global))),
+        None => {
+            files.extend(xdg_config().map(|path| (Scope::Global, path)));
+            files.extend(home_dir().map(|home| (Scope::Global, home.join(".gitconfig"))));
+        }
+    }
+    files.push((Scope::Local, scope_path(storage, Scope::Local)));
+
+    files
+}
+
+// The file `config --system`, `--global` or `--local` writes to
+pub fn scope_path(storage: &Storage, scope: Scope) -> PathBuf {
+    match scope {
+        Scope::System => env::var_os("GIT_CONFIG_SYSTEM").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("/etc/gitconfig")),
+        Scope::Global => {
+            if let Some(global) = env::var_os("GIT_CONFIG_GLOBAL") { return PathBuf::from(global); }
+            let home = home_dir().map(|home| home.join(".gitconfig"));
+
+            // The XDG file is only written to when it is the one in use
+            match (xdg_config(), &home) {
+                (Some(xdg), Some(home)) if xdg.is_file() && !home.is_file() => xdg,
+                _ => home.unwrap_or_else(|| die("$HOME not set")),
+            }
+        }
+        Scope::Local | Scope::Command => storage.root().join("config"),
+    }
+}
+
+fn xdg_config() -> Option<PathBuf> {
+    match env::var("XDG_CONFIG_HOME") {
+        Ok(xdg) if !xdg.is_empty() => Some(PathBuf::from(xdg).join("git").join("config")),
+        _ => home_dir().map(|home| home.join(".config").join("git").join("config")),
+    }
+}
+
+// The file an "include.path" or a matching "includeIf.<condition>.path" points to. Relative paths
+// are relative to the including file
+fn included_path(key: &str, value: Option<&str>, origin: &Path, git_dir: &Path) -> Option<PathBuf> {
+    let condition = key.strip_prefix("includeif.").and_then(|rest| rest.strip_suffix(".path"));
+    if key != "include.path" && !condition.is_some_and(|condition| condition_holds(condition, origin, git_dir)) {
+        return None;
+    }
+
+    let path = expand_path(value?);
+    match path.is_relative() {
+        true => Some(origin.parent().unwrap_or(Path::new("")).join(path)),
+        false => Some(path),
+    }
+}
+
+// "gitdir:<pattern>" matches the repository's directory, "gitdir/i:" ignoring case. Like in Git a
+// pattern ending in "/" matches everything below, one that is not absolute matches at any depth
+// and "./" is relative to the including file
+fn condition_holds(condition: &str, origin: &Path, git_dir: &Path) -> bool {
+    let (pattern, ignore_case) =
+        match (condition.strip_prefix("gitdir:"), condition.strip_prefix("gitdir/i:")) {
+            (Some(pattern), _) => (pattern, false),
+            (_, Some(pattern)) => (pattern, true),
+            _ => return false,
+        };
+
+    let mut pattern =
+        match pattern.strip_prefix("./") {
+            Some(relative) => origin.parent().unwrap_or(Path::new("")).join(relative).to_string_lossy().to_string(),
+            None => expand_path(pattern).to_string_lossy().to_string(),
+        };
+    if !pattern.starts_with('/') { pattern = format!("**/{}", pattern); }
+    if pattern.ends_with('/') { pattern.push_str("**"); }
+
+    let git_dir = fs::canonicalize(git_dir).unwrap_or_else(|_| git_dir.to_path_buf()).to_string_lossy().to_string();
+    match ignore_case {
+        true => wildmatch::matches(&pattern.to_lowercase(), &git_dir.to_lowercase()),
+        false => wildmatch::matches(&pattern, &git_dir),
+    }
+}
+
+// Splits "Section.Sub.Section.Name" into its section, subsection and name, None when the key has no
+// section or an invalid name
+pub fn split_key(key: &str) -> Option<(&str, Option<&str>, &str)> {
+    let (section, rest) = key.split_once('.')?;
+    let (subsection, name) = match rest.rsplit_once('.') { Some((subsection, name)) => (Some(subsection), name), None => (None, rest) };
+
+    let valid_section = !section.is_empty() && section.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if !valid_section || !valid_name || subsection.is_some_and(|sub| sub.contains('\n')) { return None; }
+
+    Some((section, subsection, name))
+}
+
+// The section and the name are case-insensitive, the subsection is not
+pub fn canonical_key(key: &str) -> Option<String> {
+    let (section, subsection, name) = split_key(key)?;
+
+    Some(join_key(&section.to_lowercase(), subsection, &name.to_lowercase()))
+}
+
+fn join_key(section: &str, subsection: Option<&str>, name: &str) -> String {
+    match subsection {
+        Some(subsection) => format!("{}.{}.{}", section, subsection, name),
+        None => format!("{}.{}", section, name),
+    }
+}
+
+// No value is true, "yes", "on" and non-zero numbers too. An empty value is false
+pub fn parse_bool(value: Option<&str>) -> Option<bool> {
+    let Some(value) = value else { return Some(true) };
+
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" => Some(true),
+        "false" | "no" | "off" | "" => Some(false),
+        number => parse_int(number).map(|n| n != 0),
+    }
+}
+
+// Integers may end with k, m or g for KiB, MiB and GiB
+pub fn parse_int(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (number, factor) =
+        match value.chars().last().map(|c| c.to_ascii_lowercase()) {
+            Some('k') => (&value[..value.len() - 1], 1 << 10),
+            Some('m') => (&value[..value.len() - 1], 1 << 20),
+            Some('g') => (&value[..value.len() - 1], 1 << 30),
+            _ => (value, 1),
+        };
+
+    number.parse::<i64>().ok()?.checked_mul(factor)
+}
+
+// "bold red", "ul #ff0000 blue", "brightgreen 236" into the ANSI escape sequence: attributes,
+// then the foreground color, then the background one
+pub fn parse_color(value: &str) -> Option<String> {
+    let mut attributes = vec![];
+    let mut colors: Vec<String> = vec![];
+
+    for word in value.split_whitespace().map(str::to_lowercase) {
+        if word == "reset" { return Some("\x1b[m".to_string()); }
+
+        let (negated, attribute) = match word.strip_prefix("no-").or_else(|| word.strip_prefix("no")) { Some(rest) => (true, rest), None => (false, word.as_str()) };
+        let code = match attribute { "bold" => Some(1), "dim" => Some(2), "italic" => Some(3), "ul" => Some(4), "blink" => Some(5), "reverse" => Some(7), "strike" => Some(9), _ => None };
+        if let Some(code) = code {
+            // Turning bold or dim off is the same code
+            attributes.push(if !negated { code } else if code <= 2 { 22 } else { 20 + code });
+            continue;
+        }
+
+        if colors.len() == 2 { return None; }
+        let background = !colors.is_empty();
+        colors.push(color_code(&word, background)?);
+    }
+
+    // Git writes attributes in the order of their codes
+    attributes.sort_unstable();
+    attributes.dedup();
+    let codes: Vec<String> = attributes.iter().map(u32::to_string).chain(colors.into_iter().filter(|code| !code.is_empty())).collect();
+    match codes.is_empty() {
+        true => Some(String::new()),
+        false => Some(format!("\x1b[{}m", codes.join(";"))),
+    }
+}
+
+fn color_code(word: &str, background: bool) -> Option<String> {
+    const NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+    let base = if background { 40 } else { 30 };
+
+    if word == "normal" { return Some(String::new()); }
+    if word == "default" { return Some((base + 9).to_string()); }
+    if let Some(position) = NAMES.iter().position(|name| *name == word) { return Some((base + position).to_string()); }
+    if let Some(position) = word.strip_prefix("bright").and_then(|name| NAMES.iter().position(|found| *found == name)) {
+        return Some((base + 60 + position).to_string());
+    }
+    if let Ok(number) = word.parse::<u8>() { return Some(format!("{};5;{}", base + 8, number)); }
+
+    let hex = word.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(format!("{};2;{};{};{}", base + 8, channel(0)?, channel(2)?, channel(4)?))
+}
+
+// A variable as found in a file, with the lines it spans
+#[derive(Debug)]
+pub struct FileEntry {
+    pub key: String,
+    pub value: Option<String>,
+    first_line: usize,
+    last_line: usize,
+}
+
+// A section header and the last line of what belongs to it
+#[derive(Debug)]
+struct SectionSpan {
+    section: String,
+    subsection: Option<String>,
+    header_line: usize,
+    last_line: usize,
+}
+
+// A config file as written, kept line by line so that edits leave the rest of it untouched
+#[derive(Debug, Default)]
+pub struct ConfigFile {
+    lines: Vec<String>,
+    sections: Vec<SectionSpan>,
+    pub entries: Vec<FileEntry>,
+}
+
+impl ConfigFile {
+    // INI-style: "[section]" or "[section \"subsection\"]" headers, then "name = value" lines where
+    // values may be quoted, use \n \t \b \\ \" escapes, go on after a trailing backslash and end at
+    // a "#" or ";" comment. Errors are the number of the offending line
+    pub fn parse(content: &str) -> Result<Self, usize> {
+        let mut file = ConfigFile { lines: content.lines().map(str::to_string).collect(), ..Self::default() };
+        let mut section: Option<(String, Option<String>)> = None;
+        let mut line = 0;
+
+        while line < file.lines.len() {
+            let mut cursor = Cursor { lines: &file.lines, line, column: 0 };
+            cursor.skip_whitespace();
+
+            if cursor.peek() == Some('[') {
+                let (name, subsection) = cursor.header().ok_or(line + 1)?;
+                file.sections.push(SectionSpan { section: name.clone(), subsection: subsection.clone(), header_line: line, last_line: line });
+                section = Some((name, subsection));
+                cursor.skip_whitespace();
+            }
+
+            match cursor.peek() {
+                None | Some('#' | ';') => {}
+                Some(c) if c.is_ascii_alphabetic() => {
+                    let Some((name, subsection)) = &section else { return Err(line + 1) };
+                    let (variable, value) = cursor.variable().ok_or(cursor.line + 1)?;
+
+                    file.entries.push(FileEntry {
+                        key: join_key(name, subsection.as_deref(), &variable.to_lowercase()),
+                        value,
+                        first_line: line,
+                        last_line: cursor.line,
+                    });
+                    if let Some(span) = file.sections.last_mut() { span.last_line = cursor.line; }
+                }
+                Some(_) => return Err(line + 1),
+            }
+
+            line = cursor.line + 1;
+        }
+
+        Ok(file)
+    }
+
+    // Adds a variable at the end of its section, or of the file in a new section
+    pub fn add(&mut self, key: &str, value: &str) {
+        let (section, subsection, name) = split_key(key).expect("invalid key");
+        let section = section.to_lowercase();
+        let line = format!("\t{} = {}", name, quote(value));
+
+        match self.sections.iter().rposition(|span| span.section == section && span.subsection.as_deref() == subsection) {
+            Some(i) => self.insert_line(i, line),
+            None => {
+                let header =
+                    match subsection {
+                        Some(subsection) => format!("[{} \"{}\"]", section, subsection.replace('\\', "\\\\").replace('"', "\\\"")),
+                        None => format!("[{}]", section),
+                    };
+                let header_line = self.lines.len();
+                self.lines.push(header);
+                self.sections.push(SectionSpan { section, subsection: subsection.map(str::to_string), header_line, last_line: header_line });
+                self.insert_line(self.sections.len() - 1, line);
+            }
+        }
+    }
+
+    // Gives the only value of a variable a new value, in place, or adds it
+    pub fn set(&mut self, key: &str, value: &str) {
+        let canonical = canonical_key(key).expect("invalid key");
+        let (_, _, name) = split_key(key).unwrap();
+
+        match self.entries.iter().position(|entry| entry.key == canonical) {
+            Some(i) => {
+                let (first, last) = (self.entries[i].first_line, self.entries[i].last_line);
+                let line = format!("\t{} = {}", name, quote(value));
+
+                // A variable written after its header moves to a line of its own
+                match self.header_part(first) {
+                    Some(header) => {
+                        self.entries.remove(i);
+                        self.replace_lines(first, last, Some(header));
+                        self.insert_line(self.section_at(first).unwrap(), line);
+                    }
+                    None => {
+                        let indent: String = self.lines[first].chars().take_while(|c| c.is_whitespace()).collect();
+                        self.replace_lines(first, last, Some(format!("{}{}", indent, line.trim_start())));
+                    }
+                }
+            }
+            None => self.add(key, value),
+        }
+    }
+
+    // Removes every value of a variable, returns how many there were
+    pub fn unset(&mut self, key: &str) -> usize {
+        let canonical = canonical_key(key).expect("invalid key");
+        let spans: Vec<(usize, usize)> =
+            self.entries.iter().filter(|entry| entry.key == canonical).map(|entry| (entry.first_line, entry.last_line)).collect();
+        // Sections where a variable goes with its whole line
+        let mut affected: Vec<usize> =
+            spans.iter().filter(|(first, _)| self.header_part(*first).is_none()).filter_map(|(first, _)| self.section_at(*first)).collect();
+        affected.dedup();
+
+        for &(first, last) in spans.iter().rev() {
+            match self.header_part(first) {
+                Some(header) => self.replace_lines(first, last, Some(header)),
+                None => self.replace_lines(first, last, None),
+            }
+        }
+        self.entries.retain(|entry| entry.key != canonical);
+
+        // Like Git, a section left with nothing in it, not even comments, goes away together with the
+        // blank lines around it
+        for i in affected.into_iter().rev() {
+            let header = self.sections[i].header_line;
+            let end = self.sections.get(i + 1).map(|span| span.header_line).unwrap_or(self.lines.len());
+            let used = self.entries.iter().any(|entry| (header..end).contains(&entry.first_line));
+            if used || !self.lines[header + 1..end].iter().all(|line| line.trim().is_empty()) { continue; }
+
+            let start = self.lines[..header].iter().rposition(|line| !line.trim().is_empty()).map_or(0, |line| line + 1);
+            for line in (start..end).rev() { self.replace_lines(line, line, None); }
+            self.sections.remove(i);
+        }
+
+        spans.len()
+    }
+
+    // The section a line belongs to
+    fn section_at(&self, line: usize) -> Option<usize> {
+        self.sections.iter().rposition(|span| span.header_line <= line)
+    }
+
+    // "[section]" out of a line that also has a variable after it
+    fn header_part(&self, line: usize) -> Option<String> {
+        if !self.sections.iter().any(|span| span.header_line == line) { return None; }
+        let text = &self.lines[line];
+
+        Some(text[..text.find(']').unwrap() + 1].to_string())
+    }
+
+    // Inserts a line at the end of a section
+    fn insert_line(&mut self, section: usize, line: String) {
+        let at = self.sections[section].last_line + 1;
+        self.lines.insert(at, line);
+
+        let moved = |line: &mut usize| if *line >= at { *line += 1 };
+        for entry in &mut self.entries {
+            moved(&mut entry.first_line);
+            moved(&mut entry.last_line);
+        }
+        for span in &mut self.sections {
+            moved(&mut span.header_line);
+            moved(&mut span.last_line);
+        }
+        self.sections[section].last_line = at;
+    }
+
+    // Replaces lines `first` to `last` with one line or none, what came after moves up
+    fn replace_lines(&mut self, first: usize, last: usize, replacement: Option<String>) {
+        let kept = replacement.is_some() as usize;
+        self.lines.splice(first..=last, replacement);
+
+        let moved = |line: &mut usize| {
+            if *line > last {
+                *line = *line + kept - (last - first + 1);
+            } else if *line >= first {
+                *line = if kept == 1 { first } else { first.saturating_sub(1) };
+            }
+        };
+        for entry in &mut self.entries {
+            moved(&mut entry.first_line);
+            moved(&mut entry.last_line);
+        }
+        for span in &mut self.sections {
+            moved(&mut span.header_line);
+            moved(&mut span.last_line);
+        }
+    }
+}
+
+impl fmt::Display for ConfigFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.lines.iter().try_for_each(|line| writeln!(f, "{}", line))
+    }
+}
+
+// Quotes what would not read back the same: surrounding spaces and comment characters
+fn quote(value: &str) -> String {
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t");
+    let needs_quotes = value.starts_with(' ') || value.ends_with(' ') || value.contains(['#', ';']);
+
+    if needs_quotes { format!("\"{}\"", escaped) } else { escaped }
+}
+
+// Reads a file character by character, values can go on over several lines
+struct Cursor<'a> {
+    lines: &'a [String],
+    line: usize,
+    column: usize,
+}
+
+impl Cursor<'_> {
+    // None at the end of a line
+    fn peek(&self) -> Option<char> {
+        self.lines[self.line][self.column..].chars().next()
+    }
+
+    fn bump(&mut self) {
+        if let Some(c) = self.peek() { self.column += c.len_utf8(); }
+    }
+
+    fn next_line(&mut self) -> bool {
+        if self.line + 1 >= self.lines.len() { return false; }
+        self.line += 1;
+        self.column = 0;
+        true
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) { self.bump(); }
+    }
+
+    fn take_while(&mut self, keep: impl Fn(char) -> bool) -> String {
+        let mut taken = String::new();
+        while let Some(c) = self.peek().filter(|c| keep(*c)) {
+            taken.push(c);
+            self.bump();
+        }
+        taken
+    }
+
+    // "[section]", "[section \"subsection\"]", or the old "[section.subsection]" lowercased
+    fn header(&mut self) -> Option<(String, Option<String>)> {
+        self.bump();
+        let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.').to_lowercase();
+        self.skip_whitespace();
+
+        let subsection =
+            match self.peek() {
+                Some('"') => {
+                    self.bump();
+                    let mut subsection = String::new();
+                    loop {
+                        match self.peek()? {
+                            '"' => break,
+                            '\\' => {
+                                self.bump();
+                                subsection.push(self.peek()?);
+                            }
+                            c => subsection.push(c),
+                        }
+                        self.bump();
+                    }
+                    self.bump();
+                    Some(subsection)
+                }
+                _ => None,
+            };
+        if self.peek() != Some(']') { return None; }
+        self.bump();
+
+        match (name.split_once('.'), subsection) {
+            (Some((section, old_style)), None) if !section.is_empty() && !old_style.is_empty() => Some((section.to_string(), Some(old_style.to_string()))),
+            (None, subsection) if !name.is_empty() => Some((name, subsection)),
+            _ => None,
+        }
+    }
+
+    // "name", "name = value"
+    fn variable(&mut self) -> Option<(String, Option<String>)> {
+        let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '-');
+        self.skip_whitespace();
+
+        match self.peek() {
+            None | Some('#' | ';') => Some((name, None)),
+            Some('=') => {
+                self.bump();
+                self.value().map(|value| (name, Some(value)))
+            }
+            Some(_) => None,
+        }
+    }
+
+    // Whitespace around the value is dropped unless quoted, inside it is kept
+    fn value(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        let mut value = String::new();
+        let mut kept = 0;
+        let mut quoted = false;
+
+        loop {
+            match self.peek() {
+                None => break,
+                Some('\\') => {
+                    self.bump();
+                    match self.peek() {
+                        None if self.next_line() => continue,
+                        None => break,
+                        Some('n') => value.push('\n'),
+                        Some('t') => value.push('\t'),
+                        Some('b') => { value.pop(); }
+                        Some(c @ ('\\' | '"')) => value.push(c),
+                        Some(_) => return None,
+                    }
+                    self.bump();
+                    kept = value.len();
+                }
+                Some('"') => {
+                    quoted = !quoted;
+                    self.bump();
+                    kept = value.len();
+                }
+                Some('#' | ';') if !quoted => break,
+                Some(c) => {
+                    value.push(c);
+                    self.bump();
+                    if quoted || !c.is_whitespace() { kept = value.len(); }
+                }
+            }
+        }
+        if quoted { return None; }
+
+        value.truncate(kept);
+        Some(value)
+    }
+}
+
+pub fn home_dir() -> Option<PathBuf> {
+    env::var("HOME").ok().map(PathBuf::from)
+}
+
+// "~/.gitignore" -> "/home/user/.gitignore"
+pub fn expand_path(value: &str) -> PathBuf {
+    match (value.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(value)
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm { Myers, Patience }
+
+// Indexes into the old and new lists of lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+#[derive(Debug)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub edits: Vec<Edit>,
+}
+
+// Lines keep their trailing newline, so a missing newline at the end of file is a change too
+pub fn split_lines(content: &str) -> Vec<&str> {
+    content.split_inclusive('\n').collect()
+}
+
+// Git treats content with a NUL byte in its first 8000 bytes as binary
+pub fn is_binary(content: &[u8]) -> bool {
+    content.iter().take(8000).any(|&b| b == 0)
+}
+
+pub fn diff_lines(old: &[&str], new: &[&str], algorithm: Algorithm) -> Vec<Edit> {
+    // Lines are compared as numbers, equal lines share an id
+    let mut ids: HashMap<&str, usize> = HashMap::new();
+    let a = intern(&mut ids, old);
+    let b = intern(&mut ids, new);
+
+    let mut edits = vec![];
+    match algorithm {
+        Algorithm::Myers => myers(&a, &b, 0, 0, &mut edits),
+        Algorithm::Patience => patience(&a, &b, 0, 0, &mut edits),
+    }
+
+    edits
+}
+
+fn intern<'a>(ids: &mut HashMap<&'a str, usize>, lines: &[&'a str]) -> Vec<usize> {
+    lines
+        .iter()
+        .map(|line| {
+            let next = ids.len();
+            *ids.entry(line).or_insert(next)
+        })
+        .collect()
+}
+
+// Lines added and removed by the edit script
+pub fn count_changes(edits: &[Edit]) -> (usize, usize) {
+    edits.iter().fold((0, 0), |(added, removed), edit| match edit {
+        Edit::Insert(_) => (added + 1, removed),
+        Edit::Delete(_) => (added, removed + 1),
+        Edit::Equal(..) => (added, removed),
+    })
+}
+
+// Groups changes into hunks with `context` lines of unchanged text around them
+pub fn hunks(edits: &[Edit], context: usize) -> Vec<Hunk> {
+    let changes: Vec<usize> =
+        edits
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| !matches!(e, Edit::Equal(..)))
+            .map(|(i, _)| i)
+            .collect();
+
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for &i in &changes {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(edits.len());
+
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let (old_start, new_start) = position(edits, start);
+            let slice = edits[start..end].to_vec();
+            let old_len = slice.iter().filter(|e| !matches!(e, Edit::Insert(_))).count();
+            let new_len = slice.iter().filter(|e| !matches!(e, Edit::Delete(_))).count();
+
+            Hunk { old_start, old_len, new_start, new_len, edits: slice }
+        })
+        .collect()
+}
+
+// Old and new line numbers (0-based) where the edit at `index` starts
+fn position(edits: &[Edit], index: usize) -> (usize, usize) {
+    edits[..index].iter().fold((0, 0), |(old, new), edit| match edit {
+        Edit::Equal(..) => (old + 1, new + 1),
+        Edit::Delete(_) => (old + 1, new),
+        Edit::Insert(_) => (old, new + 1),
+    })
+}
+
+// @@ -1,3 +1,4 @@
+pub fn hunk_header(hunk: &Hunk) -> String {
+    format!("@@ -{} +{} @@", range(hunk.old_start, hunk.old_len), range(hunk.new_start, hunk.new_len))
+}
+
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+// Renders hunks in unified format, without file headers
+pub fn unified(old: &[&str], new: &[&str], edits: &[Edit], context: usize) -> String {
+    let mut out = String::new();
+
+    for hunk in hunks(edits, context) {
+        out.push_str(&hunk_header(&hunk));
+        out.push('\n');
+
+        for edit in &hunk.edits {
+            let (prefix, line) = match *edit {
+                Edit::Equal(i, _) => (' ', old[i]),
+                Edit::Delete(i) => ('-', old[i]),
+                Edit::Insert(j) => ('+', new[j]),
+            };
+            out.push(prefix);
+            out.push_str(line);
+            if !line.ends_with('\n') { out.push_str("\n\\ No newline at end of file\n"); }
+        }
+    }
+
+    out
+}
+
+// Eugene W. Myers, "An O(ND) Difference Algorithm and Its Variations", in its linear space form:
+// the middle of a shortest edit path splits the problem in two halves that are diffed on their own
+fn myers(a: &[usize], b: &[usize], a_offset: usize, b_offset: usize, edits: &mut Vec<Edit>) {
+    // Common prefix and suffix never take part in the search
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix =
+        a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+
+    for i in 0..prefix { edits.push(Edit::Equal(a_offset + i, b_offset + i)); }
+
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+    let (a_mid_offset, b_mid_offset) = (a_offset + prefix, b_offset + prefix);
+
+    let split = if a_mid.is_empty() || b_mid.is_empty() { None } else { split_point(a_mid, b_mid) };
+    match split {
+        Some((x, y)) => {
+            myers(&a_mid[..x], &b_mid[..y], a_mid_offset, b_mid_offset, edits);
+            myers(&a_mid[x..], &b_mid[y..], a_mid_offset + x, b_mid_offset + y, edits);
+        }
+        None => {
+            edits.extend((0..a_mid.len()).map(|i| Edit::Delete(a_mid_offset + i)));
+            edits.extend((0..b_mid.len()).map(|j| Edit::Insert(b_mid_offset + j)));
+        }
+    }
+
+    for i in 0..suffix {
+        edits.push(Edit::Equal(a_offset + a.len() - suffix + i, b_offset + b.len() - suffix + i));
+    }
+}
+
+// Where the forward search from the start and the backward one from the end meet, only the
+// furthest reaching point of each diagonal is kept. Past a cost that grows with the square root of
+// the input, like Git's, the furthest point reached so far is taken instead of a minimal split, so
+// huge rewrites stay fast. None when the two sides have nothing in common
+fn split_point(a: &[usize], b: &[usize]) -> Option<(usize, usize)> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m + 1) / 2;
+    let max_cost = (((n + m) as f64).sqrt() as isize).max(256);
+    let offset = max;
+    let delta = n - m;
+    // With an odd delta the forward search meets the backward one, otherwise the other way around
+    let front = delta % 2 != 0;
+
+    let mut forward = vec![-1isize; 2 * max as usize + 2];
+    let mut backward = vec![-1isize; 2 * max as usize + 2];
+    forward[offset as usize + 1] = 0;
+    backward[offset as usize + 1] = 0;
+
+    // Diagonals that ran off the grid are not searched again
+    let (mut forward_start, mut forward_end, mut backward_start, mut backward_end) = (0, 0, 0, 0);
+    let mut furthest = None;
+
+    for d in 0..max {
+        if d > max_cost { return furthest; }
+
+        for k in (-d + forward_start..=d - forward_end).step_by(2) {
+            let index = (k + offset) as usize;
+            let mut x =
+                if k == -d || (k != d && forward[index - 1] < forward[index + 1]) {
+                    forward[index + 1]
+                } else {
+                    forward[index - 1] + 1
+                };
+            let mut y = x - k;
+
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[index] = x;
+
+            if x > n {
+                forward_end += 2;
+            } else if y > m {
+                forward_start += 2;
+            } else {
+                if x + y > 0 && (x, y) != (n, m) && furthest.is_none_or(|(fx, fy)| x + y > (fx + fy) as isize) {
+                    furthest = Some((x as usize, y as usize));
+                }
+
+                let other = offset + delta - k;
+                if front && other >= 0 && (other as usize) < backward.len() {
+                    let backward_x = backward[other as usize];
+                    if backward_x != -1 && x >= n - backward_x { return Some((x as usize, y as usize)); }
+                }
+            }
+        }
+
+        for k in (-d + backward_start..=d - backward_end).step_by(2) {
+            let index = (k + offset) as usize;
+            let mut x =
+                if k == -d || (k != d && backward[index - 1] < backward[index + 1]) {
+                    backward[index + 1]
+                } else {
+                    backward[index - 1] + 1
+                };
+            let mut y = x - k;
+
+            while x < n && y < m && a[(n - x - 1) as usize] == b[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[index] = x;
+
+            if x > n {
+                backward_end += 2;
+            } else if y > m {
+                backward_start += 2;
+            } else if !front {
+                let other = offset + delta - k;
+                if other >= 0 && (other as usize) < forward.len() && forward[other as usize] != -1 {
+                    let forward_x = forward[other as usize];
+                    let forward_y = forward_x - (other - offset);
+                    if forward_x >= n - x { return Some((forward_x as usize, forward_y as usize)); }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// Bram Cohen's patience diff: anchor on lines unique to both sides, recurse between anchors
+fn patience(a: &[usize], b: &[usize], a_offset: usize, b_offset: usize, edits: &mut Vec<Edit>) {
+    let anchors = unique_common_lines(a, b);
+
+    if anchors.is_empty() {
+        myers(a, b, a_offset, b_offset, edits);
+        return;
+    }
+
+    let (mut i, mut j) = (0, 0);
+    for (ai, bj) in anchors {
+        patience(&a[i..ai], &b[j..bj], a_offset + i, b_offset + j, edits);
+        edits.push(Edit::Equal(a_offset + ai, b_offset + bj));
+        i = ai + 1;
+        j = bj + 1;
+    }
+    patience(&a[i..], &b[j..], a_offset + i, b_offset + j, edits);
+}
+
+// Longest increasing sequence of lines that occur exactly once in both sides
+fn unique_common_lines(a: &[usize], b: &[usize]) -> Vec<(usize, usize)> {
+    let mut counts: HashMap<usize, (usize, usize, usize)> = HashMap::new();
+    for (i, line) in a.iter().enumerate() {
+        let entry = counts.entry(*line).or_insert((0, 0, 0));
+        entry.0 += 1;
+        entry.2 = i;
+    }
+    for line in b {
+        if let Some(entry) = counts.get_mut(line) { entry.1 += 1; }
+    }
+
+    let pairs: Vec<(usize, usize)> =
+        b.iter()
+            .enumerate()
+            .filter_map(|(j, line)| match counts.get(line) {
+                Some(&(1, 1, i)) => Some((i, j)),
+                _ => None,
+            })
+            .collect();
+
+    // Patience sorting over the positions in `a`
+    let mut piles: Vec<usize> = vec![];
+    let mut previous: Vec<Option<usize>> = vec![None; pairs.len()];
+    for (index, &(i, _)) in pairs.iter().enumerate() {
+        let pile = piles.partition_point(|&top| pairs[top].0 < i);
+        if pile > 0 { previous[index] = Some(piles[pile - 1]); }
+
+        if pile == piles.len() { piles.push(index); } else { piles[pile] = index; }
+    }
+
+    let mut sequence = vec![];
+    let mut current = piles.last().copied();
+    while let Some(index) = current {
+        sequence.push(pairs[index]);
+        current = previous[index];
+    }
+    sequence.reverse();
+
+    sequence
+}
@@ -3,33 +3,41 @@ pub mod serialization {
 
     pub fn call(object: &dyn GitObject) -> String {
         let content = object.content();
-        let header = format!("{} {}", object.git_type().to_string(), content.len());
+        let header = format!("{} {}", object.git_type(), content.len());
 
         format!("{}\0{}", header, content)
     }
 }
 
 pub mod deserialization {
-    use crate::formats::tree;
     use crate::structs::git_blob::GitBlob;
+    use crate::structs::git_commit::GitCommit;
     use crate::structs::git_object::GitObject;
+    use crate::structs::git_tree::GitTree;
+    use crate::structs::GitObjectType;
 
     pub fn call(data: String) -> Box<dyn GitObject> {
-        let parts: Vec<&str> = data.split("\0").take(2).collect();
-        let object: Box<dyn GitObject> =
-            match parts[..] {
-                [header, content] => {
-                    let header_parts: Vec<&str> = header.split(" ").take(2).collect();
-                    match header_parts.as_slice() {
-                        ["blob", _bytesize] => Box::new(GitBlob::new(content)),
-                        ["tree", _bytesize] => Box::new(tree::parse(content)),
-                        _ => panic!("invalid git object header")
-                    }
-                }
-                _ => panic!("invalid git object")
-            };
+        let (object_type, content) = split(&data);
 
-        object
+        match object_type {
+            GitObjectType::Blob => Box::new(GitBlob::new(content)),
+            GitObjectType::Tree => Box::new(GitTree::new(content)),
+            GitObjectType::Commit => Box::new(GitCommit::new(content)),
+        }
+    }
+
+    // "<type> <bytesize>\0<content>"
+    pub fn split(data: &str) -> (GitObjectType, &str) {
+        match data.split_once('\0') {
+            Some((header, content)) => {
+                let header_parts: Vec<&str> = header.split(' ').take(2).collect();
+                match header_parts.as_slice() {
+                    [object_type, _bytesize] => (GitObjectType::parse(object_type), content),
+                    _ => panic!("invalid git object header")
+                }
+            }
+            None => panic!("invalid git object")
+        }
     }
 }
 
@@ -38,7 +46,7 @@ pub mod tree {
     use crate::structs::git_tree::GitTree;
 
     pub fn parse(data: &str) -> GitTree {
-        GitTree { refs: data.lines().map(object_ref::parse).collect() }
+        GitTree { refs: data.lines().filter(|l| !l.is_empty()).map(object_ref::parse).collect() }
     }
 }
 
@@ -48,15 +56,73 @@ pub mod object_ref {
 
     // 100644 blob 2f781156939ad540b2434d012446154321e41e03	example_file.txt
     pub fn parse(line: &str) -> GitObjectRef {
-        let parts: Vec<&str> = line.split(" ").collect();
+        let parts: Vec<&str> = line.splitn(4, ' ').collect();
         let fragments: [&str; 4] = parts[0..=3].try_into().unwrap();
-        let raw_ref_type = fragments[1].parse::<String>().unwrap();
 
         GitObjectRef {
-            permissions: fragments[0].parse().unwrap(),
-            ref_type: GitObjectType::parse(&raw_ref_type.as_str()),
-            hash: fragments[2].parse().unwrap(),
-            content: fragments[3].parse().unwrap(),
+            permissions: parse_mode(fragments[0]).expect("invalid tree entry mode"),
+            ref_type: GitObjectType::parse(fragments[1]),
+            hash: fragments[2].to_string(),
+            content: fragments[3].to_string(),
+        }
+    }
+
+    // Modes are octal, like 100644. Trees written by the baseline hold them in decimal (33188),
+    // which is told apart by the length: the only five digit octal mode is the one of trees, 40000.
+    // Only for trees on disk, modes given by users are strictly octal
+    fn parse_mode(value: &str) -> Option<u32> {
+        let radix = if value.len() == 6 || value == "40000" { 8 } else { 10 };
+
+        u32::from_str_radix(value, radix).ok()
+    }
+}
+
+pub mod commit {
+    use crate::formats::signature;
+    use crate::structs::git_commit::GitCommit;
+
+    pub fn parse(data: &str) -> GitCommit {
+        let (header, message) = data.split_once("\n\n").unwrap_or((data, ""));
+
+        let mut tree = None;
+        let mut parents = vec![];
+        let mut author = None;
+        let mut committer = None;
+
+        for line in header.lines() {
+            match line.split_once(' ') {
+                Some(("tree", value)) => tree = Some(value.to_string()),
+                Some(("parent", value)) => parents.push(value.to_string()),
+                Some(("author", value)) => author = Some(signature::parse(value)),
+                Some(("committer", value)) => committer = Some(signature::parse(value)),
+                _ => {}
+            }
+        }
+
+        GitCommit {
+            tree: tree.expect("commit without tree"),
+            parents,
+            author: author.expect("commit without author"),
+            committer: committer.expect("commit without committer"),
+            message: message.to_string(),
+        }
+    }
+}
+
+pub mod signature {
+    use crate::structs::git_commit::GitSignature;
+
+    // Mihail Odebe <odebe@example.com> 1700000000 +0300
+    pub fn parse(line: &str) -> GitSignature {
+        let (name, rest) = line.split_once('<').expect("invalid signature");
+        let (email, date) = rest.split_once('>').expect("invalid signature");
+        let mut date_parts = date.split_whitespace();
+
+        GitSignature {
+            name: name.trim().to_string(),
+            email: email.to_string(),
+            time: date_parts.next().and_then(|t| t.parse().ok()).unwrap_or(0),
+            tz: date_parts.next().unwrap_or("+0000").to_string(),
         }
     }
 }
@@ -0,0 +1,395 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::Read;
+use flate2::read::ZlibDecoder;
+use sha2::{Digest, Sha256};
+
+use crate::pack::index::{HASH_SIZE, SHA1_SIZE};
+use crate::storage::Storage;
+use crate::structs::git_index::GitIndex;
+use crate::structs::git_tree::TREE_MODE;
+use crate::structs::GitObjectType;
+use crate::utils::hash;
+
+const BLOB_MODES: [u32; 3] = [0o100644, 0o100755, 0o120000];
+const GITLINK_MODE: u32 = 0o160000;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FsckOptions {
+    // Only read objects for their links, without validating their content
+    pub connectivity_only: bool,
+}
+
+// What was found while checking, in the order Git reports it
+#[derive(Debug, Default)]
+pub struct Report {
+    pub errors: Vec<String>,
+    pub notices: Vec<String>,
+    // Objects no root leads to, and among them the ones no other object points at either
+    pub unreachable: Vec<(String, GitObjectType)>,
+    pub dangling: Vec<(String, GitObjectType)>,
+}
+
+// An object that could be read, with the objects it points to
+struct Checked {
+    object_type: GitObjectType,
+    links: Vec<(String, GitObjectType)>,
+}
+
+// Reads every loose and packed object once, validates it, then walks from the refs, HEAD and the
+// index to find what is missing and what nothing leads to
+pub fn check(storage: &Storage, options: FsckOptions) -> Report {
+    let mut report = Report::default();
+    let mut objects: BTreeMap<String, Checked> = BTreeMap::new();
+
+    for name in storage.loose_objects() {
+        match read_loose(storage, &name) {
+            Ok((object_type, content)) => {
+                check_name(&name, &content, &mut report);
+                check_object(&name, object_type, &content, options, &mut objects, &mut report);
+            }
+            Err(error) => report.errors.push(format!("error: {}: object corrupt or missing: {}", name, error)),
+        }
+    }
+
+    for pack in storage.packs().iter() {
+        let (data, trailer) = pack.data.split_at(pack.data.len() - HASH_SIZE);
+        if Sha256::digest(data)[..] != *trailer {
+            report.errors.push(format!("error: {}: pack checksum mismatch", pack.path.display()));
+        }
+
+        for (name, offset) in pack.index.names.iter().zip(&pack.index.offsets) {
+            let name = hash::to_hex(name);
+            if objects.contains_key(&name) { continue; }
+
+            match pack.read_at(*offset, &|base| storage.read_raw(base)) {
+                Ok((object_type, content)) => {
+                    check_name(&name, &content, &mut report);
+                    check_object(&name, object_type, &content, options, &mut objects, &mut report);
+                }
+                Err(error) => report.errors.push(format!("error: {}: cannot read pack entry in {}: {}", name, pack.path.display(), error)),
+            }
+        }
+    }
+
+    // Reading an object of a pack written by Git checks it against its Git name
+    for pack in storage.packs().foreign() {
+        for name in pack.names() {
+            if objects.contains_key(&name) { continue; }
+
+            match pack.read(&name).unwrap() {
+                Ok((object_type, content)) => check_object(&name, object_type, &content, options, &mut objects, &mut report),
+                Err(error) => report.errors.push(format!("error: {}: cannot read pack entry in {}: {}", name, pack.path().display(), error)),
+            }
+        }
+    }
+
+    let mut roots = check_refs(storage, &objects, &mut report);
+    roots.extend(check_index(storage, &objects, &mut report));
+
+    let reachable = connectivity(&objects, roots, &mut report);
+    let referenced: HashSet<&String> = objects.values().flat_map(|object| object.links.iter().map(|(hash, _)| hash)).collect();
+    for (name, object) in &objects {
+        if reachable.contains(name) { continue; }
+
+        report.unreachable.push((name.clone(), object.object_type));
+        if !referenced.contains(name) { report.dangling.push((name.clone(), object.object_type)); }
+    }
+
+    report
+}
+
+fn read_loose(storage: &Storage, name: &str) -> Result<(GitObjectType, Vec<u8>), String> {
+    let compressed = fs::read(storage.object_path(name)).map_err(|e| e.to_string())?;
+    let mut data = vec![];
+    ZlibDecoder::new(&compressed[..]).read_to_end(&mut data).map_err(|e| e.to_string())?;
+
+    let separator = data.iter().position(|byte| *byte == 0).ok_or("missing object header")?;
+    let header = String::from_utf8_lossy(&data[..separator]).to_string();
+    let content = data[separator + 1..].to_vec();
+
+    let (object_type, size) = header.split_once(' ').ok_or("invalid object header")?;
+    let object_type = parse_type(object_type).ok_or_else(|| format!("unknown object type '{}'", object_type))?;
+    if size.parse::<usize>().ok() != Some(content.len()) { return Err("object size does not match its header".to_string()); }
+
+    Ok((object_type, content))
+}
+
+fn check_name(name: &str, content: &[u8], report: &mut Report) {
+    if hash::from_bytes(content) != name {
+        report.errors.push(format!("error: hash mismatch for {} (content hashes to {})", name, hash::from_bytes(content)));
+    }
+}
+
+fn check_object(
+    name: &str,
+    object_type: GitObjectType,
+    content: &[u8],
+    options: FsckOptions,
+    objects: &mut BTreeMap<String, Checked>,
+    report: &mut Report,
+) {
+    let text = String::from_utf8_lossy(content);
+    if !options.connectivity_only {
+        let problems =
+            match object_type {
+                GitObjectType::Tree => tree_problems(&text),
+                GitObjectType::Commit => commit_problems(&text),
+                GitObjectType::Blob => vec![],
+            };
+        for problem in problems {
+            report.errors.push(format!("error in {} {}: {}", object_type, name, problem));
+        }
+    }
+
+    objects.insert(name.to_string(), Checked { object_type, links: links(object_type, &text) });
+}
+
+// Objects a tree or commit points to, as far as its content can be parsed
+fn links(object_type: GitObjectType, text: &str) -> Vec<(String, GitObjectType)> {
+    match object_type {
+        GitObjectType::Blob => vec![],
+        GitObjectType::Tree => {
+            text.lines()
+                .filter_map(|line| {
+                    let parts: Vec<&str> = line.splitn(4, ' ').collect();
+                    let [_, kind, object, _] = parts.as_slice() else { return None };
+                    // Submodule commits live in another repository
+                    let kind = parse_type(kind).filter(|kind| *kind != GitObjectType::Commit)?;
+                    is_object_name(object).then(|| (object.to_string(), kind))
+                })
+                .collect()
+        }
+        GitObjectType::Commit => {
+            let header = text.split_once("\n\n").map_or(text, |(header, _)| header);
+            header
+                .lines()
+                .filter_map(|line| match line.split_once(' ') {
+                    Some(("tree", object)) => Some((object.to_string(), GitObjectType::Tree)),
+                    Some(("parent", object)) => Some((object.to_string(), GitObjectType::Commit)),
+                    _ => None,
+                })
+                .filter(|(object, _)| is_object_name(object))
+                .collect()
+        }
+    }
+}
+
+// "<mode> <type> <hash> <name>" lines with known modes matching their types, valid names, sorted
+// like Git sorts them and without duplicates
+fn tree_problems(text: &str) -> Vec<String> {
+    let mut problems = vec![];
+    let mut previous: Option<String> = None;
+
+    for line in text.lines() {
+        let parts: Vec<&str> = line.splitn(4, ' ').collect();
+        let [mode, kind, object, name] = parts.as_slice() else {
+            problems.push(format!("badTree: cannot parse entry '{}'", line));
+            continue;
+        };
+
+        let expected =
+            match u32::from_str_radix(mode, 8) {
+                Ok(TREE_MODE) => Some("tree"),
+                Ok(GITLINK_MODE) => Some("commit"),
+                Ok(mode) if BLOB_MODES.contains(&mode) => Some("blob"),
+                _ => None,
+            };
+        match expected {
+            None => problems.push(format!("badFilemode: '{}' has bad mode {}", name, mode)),
+            Some(expected) if expected != *kind => {
+                problems.push(format!("badType: '{}' is a {} with mode {}", name, kind, mode))
+            }
+            Some(_) => {}
+        }
+
+        if !is_object_name(object) { problems.push(format!("badObjectName: '{}' points to '{}'", name, object)); }
+
+        match *name {
+            "" => problems.push("emptyName: contains empty pathname".to_string()),
+            "." => problems.push("hasDot: contains '.'".to_string()),
+            ".." => problems.push("hasDotdot: contains '..'".to_string()),
+            ".rinit" | ".git" => problems.push(format!("hasDotgit: contains '{}'", name)),
+            _ if name.contains('/') => problems.push(format!("fullPathname: contains full pathname '{}'", name)),
+            _ => {}
+        }
+
+        // Directories sort as if their name ended with a slash
+        let key = if *kind == "tree" { format!("{}/", name) } else { name.to_string() };
+        if let Some(previous) = &previous {
+            if previous.trim_end_matches('/') == name.trim_end_matches('/') {
+                problems.push(format!("duplicateEntries: contains duplicate entry '{}'", name));
+            } else if *previous > key {
+                problems.push(format!("treeNotSorted: '{}' comes after '{}'", name, previous.trim_end_matches('/')));
+            }
+        }
+        previous = Some(key);
+    }
+
+    problems
+}
+
+// A tree line first, then parents, then one author and one committer with a valid signature
+fn commit_problems(text: &str) -> Vec<String> {
+    let Some((header, _message)) = text.split_once("\n\n") else {
+        return vec!["missingBlankLine: no blank line between header and message".to_string()];
+    };
+    let lines: Vec<(&str, &str)> = header.lines().map(|line| line.split_once(' ').unwrap_or((line, ""))).collect();
+    let mut problems = vec![];
+    let mut position = 0;
+
+    match lines.first() {
+        Some(("tree", object)) if is_object_name(object) => position += 1,
+        Some(("tree", object)) => {
+            problems.push(format!("badTreeSha1: invalid tree '{}'", object));
+            position += 1;
+        }
+        _ => problems.push("missingTree: no tree line".to_string()),
+    }
+
+    while let Some(("parent", object)) = lines.get(position) {
+        if !is_object_name(object) { problems.push(format!("badParentSha1: invalid parent '{}'", object)); }
+        position += 1;
+    }
+
+    for field in ["author", "committer"] {
+        match lines.get(position) {
+            Some((name, signature)) if *name == field => {
+                if let Some(problem) = signature_problem(signature) { problems.push(problem); }
+                position += 1;
+            }
+            _ => {
+                let id = if field == "author" { "missingAuthor" } else { "missingCommitter" };
+                problems.push(format!("{}: no {} line", id, field));
+            }
+        }
+    }
+
+    problems
+}
+
+// "Name <email> 1700000000 +0300"
+fn signature_problem(signature: &str) -> Option<String> {
+    let Some((_name, rest)) = signature.split_once('<') else { return Some("missingEmail: no email".to_string()) };
+    let Some((_email, date)) = rest.split_once('>') else { return Some("badEmail: unterminated email".to_string()) };
+
+    let mut parts = date.split_whitespace();
+    if !parts.next().is_some_and(|time| !time.is_empty() && time.chars().all(|c| c.is_ascii_digit())) {
+        return Some(format!("badDate: invalid date in '{}'", signature));
+    }
+    let valid_tz =
+        parts.next().is_some_and(|tz| {
+            tz.len() == 5 && tz.starts_with(['+', '-']) && tz[1..].chars().all(|c| c.is_ascii_digit())
+        });
+    if !valid_tz { return Some(format!("badTimezone: invalid timezone in '{}'", signature)); }
+
+    None
+}
+
+// Refs must point to existing objects and branches to commits. Returns what they point to
+fn check_refs(storage: &Storage, objects: &BTreeMap<String, Checked>, report: &mut Report) -> Vec<String> {
+    let mut roots = vec![];
+
+    for (name, value) in storage.list_refs("refs/") {
+        if !is_object_name(&value) {
+            report.errors.push(format!("error: {}: badRefContent: '{}'", name, value));
+            continue;
+        }
+
+        match objects.get(&value) {
+            None => report.errors.push(format!("error: {}: invalid object pointer {}", name, value)),
+            Some(object) if name.starts_with("refs/heads/") && object.object_type != GitObjectType::Commit => {
+                report.errors.push(format!("error: {}: not a commit ({} {})", name, object.object_type, value))
+            }
+            Some(_) => {}
+        }
+        roots.push(value);
+    }
+
+    match (storage.head_target(), storage.read_ref("HEAD")) {
+        (Some(branch), None) => {
+            let short = branch.strip_prefix("refs/heads/").unwrap_or(&branch).to_string();
+            report.notices.push(format!("notice: HEAD points to an unborn branch ({})", short));
+        }
+        (None, Some(value)) if !objects.contains_key(&value) => {
+            report.errors.push(format!("error: HEAD: detached HEAD points at nothing ({})", value));
+        }
+        (_, value) => roots.extend(value),
+    }
+
+    for name in ["ORIG_HEAD", "MERGE_HEAD"] {
+        roots.extend(storage.read_ref(name).filter(|value| objects.contains_key(value)));
+    }
+
+    roots
+}
+
+// The index must parse, match its checksum and only point to blobs that exist. Returns those blobs
+fn check_index(storage: &Storage, objects: &BTreeMap<String, Checked>, report: &mut Report) -> Vec<String> {
+    let path = storage.root().join("index");
+    if !path.exists() { return vec![]; }
+
+    let index =
+        match GitIndex::verify(path, &|prefix| storage.find_object(prefix)) {
+            Ok(index) => index,
+            Err(error) => {
+                report.errors.push(format!("error: index: {}", error));
+                return vec![];
+            }
+        };
+
+    let mut roots = vec![];
+    for entry in index.entries.iter().filter(|entry| entry.mode != GITLINK_MODE) {
+        let blob = entry.object_hash();
+
+        match objects.get(&blob) {
+            Some(object) if object.object_type == GitObjectType::Blob => roots.push(blob),
+            Some(object) => report.errors.push(format!("error: index: '{}' points to a {}, not a blob ({})", entry.path, object.object_type, blob)),
+            None => report.errors.push(format!("error: index: '{}' points to missing blob {}", entry.path, blob)),
+        }
+    }
+
+    roots
+}
+
+// Everything reachable from the roots. Links to objects that are missing or of another type than
+// announced are reported on the way
+fn connectivity(objects: &BTreeMap<String, Checked>, roots: Vec<String>, report: &mut Report) -> HashSet<String> {
+    let mut reachable = HashSet::new();
+    let mut missing = HashSet::new();
+    let mut stack = roots;
+
+    while let Some(name) = stack.pop() {
+        if !reachable.insert(name.clone()) { continue; }
+        let Some(object) = objects.get(&name) else { continue };
+
+        for (link, kind) in &object.links {
+            match objects.get(link) {
+                None => {
+                    if missing.insert(link.clone()) { report.errors.push(format!("missing {} {}", kind, link)); }
+                    report.errors.push(format!("broken link from {} {} to {} {}", object.object_type, name, kind, link));
+                }
+                Some(target) if target.object_type != *kind => {
+                    report.errors.push(format!("error in {} {}: {} is a {}, not a {}", object.object_type, name, link, target.object_type, kind));
+                }
+                Some(_) => stack.push(link.clone()),
+            }
+        }
+    }
+
+    reachable
+}
+
+// SHA-1 names are those of objects in packs written by Git
+fn is_object_name(value: &str) -> bool {
+    (value.len() == HASH_SIZE * 2 || value.len() == SHA1_SIZE * 2) && hash::is_hex(value)
+}
+
+fn parse_type(value: &str) -> Option<GitObjectType> {
+    match value {
+        "blob" => Some(GitObjectType::Blob),
+        "tree" => Some(GitObjectType::Tree),
+        "commit" => Some(GitObjectType::Commit),
+        _ => None,
+    }
+}
@@ -0,0 +1,181 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use crate::config;
+use crate::storage::Storage;
+use crate::utils::files::read_file;
+use crate::utils::wildmatch;
+
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".rinitignore"];
+
+#[derive(Debug, Clone)]
+pub struct IgnorePattern {
+    pub source: String,
+    pub line: usize,
+    pub original: String,
+    pub negated: bool,
+    pattern: String,
+    dir_only: bool,
+    anchored: bool,
+    // Directory of the ignore file the pattern came from, like "src/"
+    base: String,
+}
+
+// Ignore rules in Git's order of precedence, highest first: patterns from the command line,
+// per-directory ignore files (deeper directories win), `info/exclude` and `core.excludesFile`
+#[derive(Debug, Default)]
+pub struct IgnoreRules {
+    working_root: Option<PathBuf>,
+    command_line: Vec<IgnorePattern>,
+    per_directory: RefCell<HashMap<String, Vec<IgnorePattern>>>,
+    exclude: Vec<IgnorePattern>,
+    excludes_file: Vec<IgnorePattern>,
+}
+
+impl IgnorePattern {
+    pub fn parse(line: &str, base: &str, source: &str, number: usize) -> Option<Self> {
+        // Trailing spaces are ignored unless escaped with a backslash
+        let mut trimmed = line.trim_end_matches(' ');
+        if trimmed.ends_with('\\') && trimmed.len() < line.len() {
+            trimmed = &line[..trimmed.len() + 1];
+        }
+        if trimmed.is_empty() || trimmed.starts_with('#') { return None; }
+
+        let (negated, rest) = match trimmed.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed.strip_prefix('\\').filter(|r| r.starts_with(['!', '#'])).unwrap_or(trimmed))
+        };
+        let (dir_only, rest) = match rest.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, rest)
+        };
+        if rest.is_empty() { return None; }
+
+        Some(IgnorePattern {
+            source: source.to_string(),
+            line: number,
+            original: trimmed.to_string(),
+            negated,
+            pattern: rest.trim_start_matches('/').to_string(),
+            dir_only,
+            anchored: rest.contains('/'),
+            base: base.to_string(),
+        })
+    }
+
+    pub fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir { return false; }
+        let Some(relative) = path.strip_prefix(self.base.as_str()) else { return false };
+
+        if self.anchored {
+            wildmatch::matches(&self.pattern, relative)
+        } else {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            wildmatch::matches(&self.pattern, name)
+        }
+    }
+}
+
+impl IgnoreRules {
+    pub fn load(storage: &Storage) -> Self {
+        let config = storage.config();
+        let excludes_file = config.get_path("core.excludesFile").or_else(default_excludes_file);
+
+        let info_exclude = storage.root().join("info").join("exclude");
+
+        Self {
+            working_root: Some(storage.working_root.clone()),
+            exclude: read_patterns(&info_exclude, "", ".rinit/info/exclude"),
+            excludes_file: excludes_file
+                .map(|path| read_patterns(&path, "", &path.to_string_lossy()))
+                .unwrap_or_default(),
+            ..Self::default()
+        }
+    }
+
+    pub fn add_patterns(&mut self, patterns: &[String]) {
+        self.command_line.extend(
+            patterns
+                .iter()
+                .filter_map(|pattern| IgnorePattern::parse(pattern, "", "", 0))
+        );
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.working_root.is_none() && self.command_line.is_empty()
+    }
+
+    pub fn is_ignored(&self, path: &str) -> bool {
+        self.explain(path, false).is_some_and(|p| !p.negated)
+    }
+
+    pub fn is_ignored_dir(&self, path: &str) -> bool {
+        self.explain(path, true).is_some_and(|p| !p.negated)
+    }
+
+    // The pattern that decides whether the path is ignored. A file can not be re-included
+    // with a negated pattern if one of its parent directories is ignored
+    pub fn explain(&self, path: &str, is_dir: bool) -> Option<IgnorePattern> {
+        let path = path.trim_end_matches('/');
+        let components: Vec<&str> = path.split('/').collect();
+
+        for depth in 1..components.len() {
+            let parent = components[..depth].join("/");
+            if let Some(pattern) = self.last_match(&parent, true) {
+                if !pattern.negated { return Some(pattern); }
+            }
+        }
+
+        self.last_match(path, is_dir)
+    }
+
+    fn last_match(&self, path: &str, is_dir: bool) -> Option<IgnorePattern> {
+        let find = |patterns: &[IgnorePattern]| patterns.iter().rev().find(|p| p.matches(path, is_dir)).cloned();
+
+        if let Some(pattern) = find(&self.command_line) { return Some(pattern); }
+
+        if self.working_root.is_some() {
+            let components: Vec<&str> = path.split('/').collect();
+            for depth in (0..components.len()).rev() {
+                let dir = components[..depth].iter().map(|c| format!("{}/", c)).collect::<String>();
+                self.load_directory(&dir);
+
+                if let Some(pattern) = find(&self.per_directory.borrow()[&dir]) { return Some(pattern); }
+            }
+        }
+
+        find(&self.exclude).or_else(|| find(&self.excludes_file))
+    }
+
+    fn load_directory(&self, dir: &str) {
+        if self.per_directory.borrow().contains_key(dir) { return; }
+
+        let root = self.working_root.as_ref().unwrap();
+        let patterns =
+            IGNORE_FILES
+                .iter()
+                .flat_map(|name| read_patterns(&root.join(dir).join(name), dir, &format!("{}{}", dir, name)))
+                .collect();
+
+        self.per_directory.borrow_mut().insert(dir.to_string(), patterns);
+    }
+}
+
+fn read_patterns(path: &Path, base: &str, source: &str) -> Vec<IgnorePattern> {
+    if !path.is_file() { return vec![]; }
+
+    read_file(path)
+        .lines()
+        .enumerate()
+        .filter_map(|(number, line)| IgnorePattern::parse(line, base, source, number + 1))
+        .collect()
+}
+
+// $XDG_CONFIG_HOME/git/ignore or ~/.config/git/ignore
+fn default_excludes_file() -> Option<PathBuf> {
+    match env::var("XDG_CONFIG_HOME") {
+        Ok(xdg) if !xdg.is_empty() => Some(PathBuf::from(xdg).join("git").join("ignore")),
+        _ => config::home_dir().map(|home| home.join(".config").join("git").join("ignore"))
+    }
+}
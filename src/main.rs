@@ -3,6 +3,22 @@ mod utils;
 mod formats;
 mod commands;
 mod structs;
+mod revision;
+mod revwalk;
+mod reachability;
+mod worktree;
+mod pathspec;
+mod ignore;
+mod config;
+mod status;
+mod diff;
+mod tree_diff;
+mod checkout;
+mod merge;
+mod pack;
+mod maintenance;
+mod fsck;
+mod commit_graph;
 
 use clap::Parser;
 
@@ -30,11 +46,88 @@ fn main() {
             commands::cat_file::call(storage, args)
         },
         Commands::UpdateIndex(args) => {
-            if args.cacheinfo == true {
-                commands::update_index::call(storage, args);
-            } else {
-                println!("Only --cacheinfo implemented");
-            }
-        }
+            commands::update_index::call(storage, args)
+        },
+        Commands::CommitTree(args) => {
+            commands::commit_tree::call(storage, args)
+        },
+        Commands::Log(args) => {
+            commands::log::call(storage, args)
+        },
+        Commands::LsTree(args) => {
+            commands::ls_tree::call(storage, args)
+        },
+        Commands::LsFiles(args) => {
+            commands::ls_files::call(storage, args)
+        },
+        Commands::Add(args) => {
+            commands::add::call(storage, args)
+        },
+        Commands::CheckIgnore(args) => {
+            commands::check_ignore::call(storage, args)
+        },
+        Commands::Status(args) => {
+            commands::status::call(storage, args)
+        },
+        Commands::Diff(args) => {
+            commands::diff::call(storage, args)
+        },
+        Commands::Checkout(args) => {
+            commands::checkout::call(storage, args)
+        },
+        Commands::Switch(args) => {
+            commands::switch::call(storage, args)
+        },
+        Commands::Restore(args) => {
+            commands::restore::call(storage, args)
+        },
+        Commands::ReadTree(args) => {
+            commands::read_tree::call(storage, args)
+        },
+        Commands::Branch(args) => {
+            commands::branch::call(storage, args)
+        },
+        Commands::Merge(args) => {
+            commands::merge::call(storage, args)
+        },
+        Commands::MergeBase(args) => {
+            commands::merge_base::call(storage, args)
+        },
+        Commands::PackObjects(args) => {
+            commands::pack_objects::call(storage, args)
+        },
+        Commands::Gc(args) => {
+            commands::gc::call(storage, args)
+        },
+        Commands::Repack(args) => {
+            commands::repack::call(storage, args)
+        },
+        Commands::Prune(args) => {
+            commands::prune::call(storage, args)
+        },
+        Commands::CountObjects(args) => {
+            commands::count_objects::call(storage, args)
+        },
+        Commands::Fsck(args) => {
+            commands::fsck::call(storage, args)
+        },
+        Commands::IndexPack(args) => {
+            commands::index_pack::call(storage, args)
+        },
+        Commands::UnpackObjects(args) => {
+            commands::unpack_objects::call(storage, args)
+        },
+        Commands::MultiPackIndex(args) => {
+            commands::multi_pack_index::call(storage, args)
+        },
+        Commands::CommitGraph(args) => {
+            commands::commit_graph::call(storage, args)
+        },
+        Commands::RevList(args) => {
+            commands::rev_list::call(storage, args)
+        },
+        Commands::Config(args) => {
+            commands::config::call(storage, args)
+        },
     }
 }
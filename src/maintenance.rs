@@ -0,0 +1,182 @@
+use std::collections::HashSet;
+use std::fs;
+use std::time::UNIX_EPOCH;
+use crate::commit_graph;
+use crate::pack::bitmap;
+use crate::pack::midx::{self, MIDX_FILE};
+use crate::pack::write::{self, PackOptions};
+use crate::reachability;
+use crate::storage::Storage;
+use crate::structs::GitObjectType;
+use crate::utils::hash;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RepackOptions {
+    // Pack every reachable object instead of only the loose ones
+    pub all: bool,
+    // Delete packs whose objects all made it into the new pack, and loose copies of packed objects
+    pub delete_redundant: bool,
+    // Objects only found in deleted packs are written back as loose objects instead of being lost,
+    // so that `prune` decides about them
+    pub loosen_unreachable: bool,
+    // Writes a ".bitmap" next to the new pack, only when it has every reachable object
+    pub write_bitmap: bool,
+    pub pack: PackOptions,
+}
+
+// Objects the repository needs: everything reachable from refs, HEAD and a merge in progress, and
+// the blobs staged in the index. Objects found through pack bitmaps come without paths
+pub fn reachable(storage: &Storage, use_bitmaps: bool) -> Vec<(String, String)> {
+    let mut tips: Vec<String> = storage.list_refs("refs/").into_iter().map(|(_, hash)| hash).collect();
+    for name in ["HEAD", "ORIG_HEAD", "MERGE_HEAD"] {
+        tips.extend(storage.read_ref(name));
+    }
+
+    let mut objects = reachability::objects_between(storage, &tips, &[], use_bitmaps);
+    let mut seen: HashSet<String> = objects.iter().map(|(hash, _)| hash.clone()).collect();
+    for entry in storage.read_index().entries {
+        let blob = entry.object_hash();
+        if storage.object_exists(&blob) && seen.insert(blob.clone()) { objects.push((blob, entry.path)); }
+    }
+
+    objects
+}
+
+// Writes a new pack and returns its checksum, None when there was nothing to pack
+pub fn repack(storage: &Storage, options: RepackOptions) -> Option<String> {
+    let list: Vec<(String, String)> =
+        if options.all {
+            // Paths group similar objects for the delta search. Objects of packs written by Git
+            // stay in them
+            let packs = storage.packs();
+            reachable(storage, false).into_iter().filter(|(hash, _)| !packs.is_foreign(hash)).collect()
+        } else {
+            let packs = storage.packs();
+            storage
+                .loose_objects()
+                .into_iter()
+                .filter(|hash| !packs.iter().any(|pack| pack.contains(hash)))
+                .map(|hash| (hash, String::new()))
+                .collect()
+        };
+    if list.is_empty() { return None; }
+
+    let objects = write::collect(storage, &list);
+    let pack = write::build(storage, &objects, options.pack);
+    let checksum = write::write_files(&storage.pack_path().join("pack"), &pack);
+
+    if options.delete_redundant {
+        let packed: HashSet<[u8; 32]> = pack.entries.iter().map(|entry| entry.name).collect();
+
+        for old in storage.packs().iter().filter(|old| !old.path.to_string_lossy().contains(&checksum)) {
+            let missing: Vec<&[u8; 32]> = old.index.names.iter().filter(|name| !packed.contains(*name)).collect();
+            if !missing.is_empty() && !options.loosen_unreachable { continue; }
+
+            let mut loosened = true;
+            for name in missing {
+                let hash = hash::to_hex(name);
+                if !storage.object_path(&hash).exists() {
+                    let (object_type, content) = storage.read_raw(&hash).unwrap();
+                    if let Err(error) = storage.persist_raw(&hash, object_type, &content) {
+                        eprintln!("warning: keeping {}: {}", old.path.display(), error);
+                        loosened = false;
+                        break;
+                    }
+                }
+            }
+            if loosened { remove_pack(old.path.as_path()); }
+        }
+        storage.reload_packs();
+        prune_packed(storage);
+
+        // An index naming deleted packs would be ignored from now on
+        if storage.pack_path().join(MIDX_FILE).exists() { write_multi_pack_index(storage); }
+    }
+    storage.reload_packs();
+
+    if options.write_bitmap && options.all { write_bitmap(storage, &checksum); }
+
+    Some(checksum)
+}
+
+// Writes "pack-<checksum>.bitmap" unless objects reachable from the pack are missing from it
+pub fn write_bitmap(storage: &Storage, checksum: &str) {
+    let packs = storage.packs();
+    let Some(pack) = packs.iter().find(|pack| pack.path.to_string_lossy().contains(checksum)) else { return };
+
+    match bitmap::write(storage, pack) {
+        Some(data) => fs::write(pack.path.with_extension("bitmap"), data).expect("Can't write bitmap"),
+        None => eprintln!("warning: pack-{} is not closed under reachability, no bitmap written", checksum),
+    }
+}
+
+fn remove_pack(path: &std::path::Path) {
+    for extension in ["idx", "pack", "bitmap"] {
+        let _ = fs::remove_file(path.with_extension(extension));
+    }
+}
+
+// Records every commit reachable from refs in "objects/info/commit-graph"
+pub fn write_commit_graph(storage: &Storage, changed_paths: bool) {
+    let mut tips: Vec<String> = storage.list_refs("refs/").into_iter().map(|(_, hash)| hash).collect();
+    tips.extend(storage.read_ref("HEAD"));
+
+    let path = storage.commit_graph_path();
+    if tips.is_empty() {
+        let _ = fs::remove_file(&path);
+        return;
+    }
+    fs::create_dir_all(path.parent().unwrap()).expect("Can't create objects/info");
+    fs::write(&path, commit_graph::write(storage, &tips, changed_paths)).expect("Can't write commit-graph");
+}
+
+// Indexes every pack of the repository in "objects/pack/multi-pack-index"
+pub fn write_multi_pack_index(storage: &Storage) {
+    storage.reload_packs();
+    let path = storage.pack_path().join(MIDX_FILE);
+
+    match storage.packs().len() {
+        0 => { let _ = fs::remove_file(&path); }
+        _ => fs::write(&path, midx::write(&storage.packs())).expect("Can't write multi-pack-index"),
+    }
+    storage.reload_packs();
+}
+
+// Deletes loose objects that also exist in a pack, returns how many
+pub fn prune_packed(storage: &Storage) -> usize {
+    let packs = storage.packs();
+    let packed: Vec<String> =
+        storage.loose_objects().into_iter().filter(|hash| packs.iter().any(|pack| pack.contains(hash))).collect();
+
+    for hash in &packed { storage.remove_loose_object(hash); }
+
+    packed.len()
+}
+
+// Unreachable loose objects last modified before `expire`, with their types. Unless `dry_run` they
+// are deleted
+pub fn prune(storage: &Storage, expire: i64, dry_run: bool) -> Vec<(String, GitObjectType)> {
+    let reachable: HashSet<String> = reachable(storage, true).into_iter().map(|(hash, _)| hash).collect();
+
+    let pruned: Vec<(String, GitObjectType)> =
+        storage
+            .loose_objects()
+            .into_iter()
+            .filter(|hash| !reachable.contains(hash))
+            .filter(|hash| modified(storage, hash) <= expire)
+            .filter_map(|hash| storage.read_raw(&hash).map(|(object_type, _)| (hash, object_type)))
+            .collect();
+
+    if !dry_run {
+        for (hash, _) in &pruned { storage.remove_loose_object(hash); }
+    }
+
+    pruned
+}
+
+fn modified(storage: &Storage, hash: &str) -> i64 {
+    fs::metadata(storage.object_path(hash))
+        .and_then(|meta| meta.modified())
+        .map(|time| time.duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0))
+        .unwrap_or(0)
+}
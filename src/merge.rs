@@ -0,0 +1,326 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use crate::checkout::{self, Files};
+use crate::diff::{self, Algorithm, Edit};
+use crate::reachability::CommitGraph;
+use crate::storage::Storage;
+use crate::structs::git_blob::GitBlob;
+use crate::structs::git_index::{GitIndex, GitIndexEntry};
+use crate::structs::git_object::GitObject;
+use crate::tree_diff::{self, ChangeKind, RenameOptions, DEFAULT_SIMILARITY};
+use crate::worktree::{self, FileState, StatChecker};
+
+const TYPE_MASK: u32 = 0o170000;
+const REGULAR_FILE: u32 = 0o100000;
+
+// Mode and blob hash of a path on one side of the merge, None when the path is missing
+pub type Side = Option<(u32, String)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictStyle { Merge, Diff3 }
+
+// Names written after the conflict markers
+#[derive(Debug, Clone)]
+pub struct Labels {
+    pub base: String,
+    pub ours: String,
+    pub theirs: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    Clean(Side),
+    // Stages 1 to 3 for the index, and the file left in the work tree
+    Conflict { stages: [Side; 3], worktree: Side, message: String },
+}
+
+// Three-way merge of trees, path by path. Renames on either side are followed, so changes made to
+// a file on one side land on its new name on the other one. Every path of the three trees gets a
+// resolution, merged blobs are persisted
+pub fn merge_trees(storage: &Storage, base: Option<&str>, ours: &str, theirs: &str, labels: &Labels, style: ConflictStyle) -> BTreeMap<String, Resolution> {
+    let mut base_files = checkout::tree_files(storage, base);
+    let mut our_files = checkout::tree_files(storage, Some(ours));
+    let mut their_files = checkout::tree_files(storage, Some(theirs));
+    let original_ours = our_files.clone();
+
+    let our_renames = renames(storage, base, ours);
+    let their_renames = renames(storage, base, theirs);
+    // Files renamed by both sides stay where each side put them
+    for (old, new) in &their_renames {
+        if !our_renames.contains_key(old) { relocate(&mut base_files, &mut our_files, old, new); }
+    }
+    for (old, new) in &our_renames {
+        if !their_renames.contains_key(old) { relocate(&mut base_files, &mut their_files, old, new); }
+    }
+
+    let paths: BTreeSet<String> =
+        base_files.keys().chain(our_files.keys()).chain(their_files.keys()).chain(original_ours.keys()).cloned().collect();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let resolution = merge_path(storage, &path, [base_files.get(&path), our_files.get(&path), their_files.get(&path)], labels, style);
+            (path, resolution)
+        })
+        .collect()
+}
+
+// Renamed paths between the base and one side, old name to new name
+fn renames(storage: &Storage, base: Option<&str>, tree: &str) -> HashMap<String, String> {
+    if base.is_none() { return HashMap::new(); }
+
+    let changes = tree_diff::diff_trees(storage, base, Some(tree));
+    let limit = tree_diff::rename_limit(storage, "merge.renameLimit");
+    let options = RenameOptions { renames: Some(DEFAULT_SIMILARITY), copies: None, limit };
+
+    tree_diff::detect_renames(changes, &options, &|entry| blob_content(storage, &entry.hash))
+        .into_iter()
+        .filter(|change| change.kind == ChangeKind::Renamed)
+        .map(|change| (change.old_path().to_string(), change.path().to_string()))
+        .collect()
+}
+
+// Moves a path renamed on one side to its new name in the base and on the other side
+fn relocate(base: &mut Files, other: &mut Files, old: &str, new: &str) {
+    if base.contains_key(new) || other.contains_key(new) { return; }
+
+    if let Some(file) = base.remove(old) { base.insert(new.to_string(), file); }
+    if let Some(file) = other.remove(old) { other.insert(new.to_string(), file); }
+}
+
+fn merge_path(storage: &Storage, path: &str, sides: [Option<&(u32, String)>; 3], labels: &Labels, style: ConflictStyle) -> Resolution {
+    let [b, o, t] = sides;
+    let stages = [b.cloned(), o.cloned(), t.cloned()];
+
+    if o == t { return Resolution::Clean(o.cloned()); }
+    if b == o { return Resolution::Clean(t.cloned()); }
+    if b == t { return Resolution::Clean(o.cloned()); }
+
+    match (o, t) {
+        (Some(ours), Some(theirs)) if is_regular(ours) && is_regular(theirs) && b.is_none_or(is_regular) => {
+            let base_content = b.map(|(_, blob)| blob_content(storage, blob)).unwrap_or_default();
+            let (our_content, their_content) = (blob_content(storage, &ours.1), blob_content(storage, &theirs.1));
+            let kind = if b.is_none() { "add/add" } else { "content" };
+
+            if [&base_content, &our_content, &their_content].iter().any(|content| diff::is_binary(content.as_bytes())) {
+                let message = format!("warning: Cannot merge binary files: {} ({} vs. {})\nCONFLICT ({}): Merge conflict in {}",
+                                      path, labels.ours, labels.theirs, kind, path);
+                return Resolution::Conflict { stages, worktree: Some(ours.clone()), message };
+            }
+
+            // A mode change on one side is kept
+            let mode = if b.is_some_and(|(mode, _)| *mode == ours.0) { theirs.0 } else { ours.0 };
+            let (merged, conflicted) = merge_content(&base_content, &our_content, &their_content, labels, style);
+            let blob = GitBlob::new(&merged);
+            storage.persist_object(&blob);
+            let merged = Some((mode, blob.hash().to_string()));
+
+            if !conflicted { return Resolution::Clean(merged); }
+            Resolution::Conflict { stages, worktree: merged, message: format!("CONFLICT ({}): Merge conflict in {}", kind, path) }
+        }
+        (Some(ours), None) => Resolution::Conflict {
+            stages,
+            worktree: Some(ours.clone()),
+            message: format!("CONFLICT (modify/delete): {} deleted in {} and modified in {}. Version {} of {} left in tree.",
+                             path, labels.theirs, labels.ours, labels.ours, path),
+        },
+        (None, Some(theirs)) => Resolution::Conflict {
+            stages,
+            worktree: Some(theirs.clone()),
+            message: format!("CONFLICT (modify/delete): {} deleted in {} and modified in {}. Version {} of {} left in tree.",
+                             path, labels.ours, labels.theirs, labels.theirs, path),
+        },
+        // Symlinks, and files whose type changed, cannot be merged line by line
+        _ => Resolution::Conflict {
+            stages,
+            worktree: o.cloned(),
+            message: format!("CONFLICT (content): Merge conflict in {}", path),
+        },
+    }
+}
+
+fn is_regular(side: &(u32, String)) -> bool {
+    side.0 & TYPE_MASK == REGULAR_FILE
+}
+
+fn blob_content(storage: &Storage, blob: &str) -> String {
+    storage.read_object(blob).content().to_string()
+}
+
+// Line-level three-way merge. Lines both sides keep from the base split the files into chunks, a
+// chunk changed on both sides in different ways becomes a conflict. Returns the merged text and
+// whether it has conflict markers
+pub fn merge_content(base: &str, ours: &str, theirs: &str, labels: &Labels, style: ConflictStyle) -> (String, bool) {
+    let base_lines = diff::split_lines(base);
+    let our_lines = diff::split_lines(ours);
+    let their_lines = diff::split_lines(theirs);
+    let to_ours = matched_lines(&diff::diff_lines(&base_lines, &our_lines, Algorithm::Myers));
+    let to_theirs = matched_lines(&diff::diff_lines(&base_lines, &their_lines, Algorithm::Myers));
+
+    let mut out = String::new();
+    let mut conflicted = false;
+    let (mut o, mut a, mut b) = (0, 0, 0);
+
+    loop {
+        // Lines kept in place by both sides
+        let mut stable = 0;
+        while o + stable < base_lines.len()
+            && to_ours.get(&(o + stable)) == Some(&(a + stable))
+            && to_theirs.get(&(o + stable)) == Some(&(b + stable)) {
+            stable += 1;
+        }
+        if stable > 0 {
+            out.push_str(&base_lines[o..o + stable].concat());
+            (o, a, b) = (o + stable, a + stable, b + stable);
+            continue;
+        }
+
+        // Everything up to the next line both sides keep is one chunk
+        let next = (o..base_lines.len()).find(|line| to_ours.contains_key(line) && to_theirs.contains_key(line));
+        let (end, our_end, their_end) =
+            match next {
+                Some(line) => (line, to_ours[&line], to_theirs[&line]),
+                None => (base_lines.len(), our_lines.len(), their_lines.len()),
+            };
+
+        let chunk = [&base_lines[o..end], &our_lines[a..our_end], &their_lines[b..their_end]];
+        conflicted |= merge_chunk(&mut out, chunk, labels, style);
+
+        if next.is_none() { break; }
+        (o, a, b) = (end, our_end, their_end);
+    }
+
+    (out, conflicted)
+}
+
+// Base line to side line for every line the side kept
+fn matched_lines(edits: &[Edit]) -> HashMap<usize, usize> {
+    edits
+        .iter()
+        .filter_map(|edit| match *edit {
+            Edit::Equal(i, j) => Some((i, j)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn merge_chunk(out: &mut String, chunk: [&[&str]; 3], labels: &Labels, style: ConflictStyle) -> bool {
+    let [base, ours, theirs] = chunk;
+
+    if ours == theirs || base == theirs {
+        out.push_str(&ours.concat());
+        return false;
+    }
+    if base == ours {
+        out.push_str(&theirs.concat());
+        return false;
+    }
+
+    out.push_str(&format!("<<<<<<< {}\n", labels.ours));
+    push_lines(out, ours);
+    if style == ConflictStyle::Diff3 {
+        out.push_str(&format!("||||||| {}\n", labels.base));
+        push_lines(out, base);
+    }
+    out.push_str("=======\n");
+    push_lines(out, theirs);
+    out.push_str(&format!(">>>>>>> {}\n", labels.theirs));
+
+    true
+}
+
+// Markers always start on their own line
+fn push_lines(out: &mut String, lines: &[&str]) {
+    out.push_str(&lines.concat());
+    if !out.is_empty() && !out.ends_with('\n') { out.push('\n'); }
+}
+
+// Tree of the common ancestors. Several merge bases are merged into a virtual one first, with
+// their conflicts left in the files
+pub fn base_tree(storage: &Storage, graph: &mut CommitGraph, bases: &[String]) -> Option<String> {
+    let (first, rest) = bases.split_first()?;
+    let mut tree = storage.read_commit(first).tree;
+
+    for next in rest {
+        let inner_bases = graph.merge_bases(first, std::slice::from_ref(next));
+        let inner = base_tree(storage, graph, &inner_bases);
+        let labels = Labels {
+            base: "merged common ancestors".to_string(),
+            ours: "Temporary merge branch 1".to_string(),
+            theirs: "Temporary merge branch 2".to_string(),
+        };
+        let results = merge_trees(storage, inner.as_deref(), &tree, &storage.read_commit(next).tree, &labels, ConflictStyle::Merge);
+
+        let mut index = GitIndex::empty();
+        for (path, resolution) in results {
+            let file = match resolution {
+                Resolution::Clean(side) => side,
+                Resolution::Conflict { worktree, .. } => worktree,
+            };
+            if let Some((mode, blob)) = file {
+                index.add_entry(GitIndexEntry::new(&path, mode, &blob, Default::default()));
+            }
+        }
+        tree = storage.write_tree(&index);
+    }
+
+    Some(tree)
+}
+
+// Writes a merge result into the index and the work tree, starting from our tree. Conflicts are
+// recorded as stages 1 to 3. Returns the paths whose local changes are in the way, touching nothing
+pub fn apply(storage: &Storage, index: &mut GitIndex, ours: &Files, results: &BTreeMap<String, Resolution>) -> Result<(), Vec<String>> {
+    let checker = StatChecker::new(storage, index);
+    let changed: Vec<(&String, &Resolution)> =
+        results
+            .iter()
+            .filter(|(path, resolution)| **resolution != Resolution::Clean(ours.get(*path).cloned()))
+            .collect();
+
+    let blocked: Vec<String> =
+        changed
+            .iter()
+            .filter(|(path, _)| {
+                let staged = index.entry(path).map(|e| (e.mode, e.object_hash()));
+                match index.entry(path) {
+                    _ if staged.as_ref() != ours.get(*path) => true,
+                    Some(entry) => checker.check(entry) != FileState::Unchanged,
+                    None => storage.working_root.join(path).exists(),
+                }
+            })
+            .map(|(path, _)| path.to_string())
+            .collect();
+    if !blocked.is_empty() { return Err(blocked); }
+
+    // Removals go first so files can take the place of directories and the other way around
+    let mut changed = changed;
+    changed.sort_by_key(|(_, resolution)| !matches!(resolution, Resolution::Clean(None)));
+
+    for (path, resolution) in changed {
+        match resolution {
+            Resolution::Clean(Some((mode, blob))) => index.add_entry(worktree::checkout_file(storage, path, *mode, blob)),
+            Resolution::Clean(None) => {
+                index.remove_entry(path);
+                worktree::remove_file(storage, path);
+            }
+            Resolution::Conflict { stages, worktree: file, .. } => {
+                index.remove_entry(path);
+                for (stage, side) in stages.iter().enumerate() {
+                    if let Some((mode, blob)) = side {
+                        let mut entry = GitIndexEntry::new(path, *mode, blob, Default::default());
+                        entry.set_stage(stage as u16 + 1);
+                        index.add_entry(entry);
+                    }
+                }
+
+                match file {
+                    Some(side) if Some(side) == ours.get(path) => {}
+                    Some((mode, blob)) => { worktree::checkout_file(storage, path, *mode, blob); }
+                    None => worktree::remove_file(storage, path),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
@@ -0,0 +1,189 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Cursor, Read};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use sha2::{Digest, Sha256};
+
+use crate::formats::{object_ref, commit};
+use crate::pack::ewah::{self, Bitmap};
+use crate::pack::index::{invalid, HASH_SIZE};
+use crate::pack::Pack;
+use crate::reachability::CommitGraph;
+use crate::storage::Storage;
+use crate::structs::GitObjectType;
+use crate::utils::hash;
+
+pub const BITMAP_SIGNATURE: &[u8; 4] = b"BITM";
+pub const BITMAP_VERSION: u16 = 1;
+// Every object reachable from a bitmapped commit is in the pack
+const FULL_DAG: u16 = 1;
+// Besides ref tips, one commit out of this many gets a bitmap
+const SELECT_EVERY: usize = 100;
+
+// A pack's "pack-<checksum>.bitmap": for selected commits, which objects of the pack they reach.
+// Bit `i` stands for the `i`-th object of the pack in offset order
+#[derive(Debug)]
+pub struct PackBitmap {
+    pub objects: Vec<String>,
+    positions: HashMap<String, usize>,
+    entries: HashMap<String, Bitmap>,
+}
+
+impl PackBitmap {
+    // None when the pack has no bitmap
+    pub fn load(pack: &Pack) -> Option<io::Result<Self>> {
+        let data = fs::read(pack.path.with_extension("bitmap")).ok()?;
+
+        Some(Self::parse(&data, pack))
+    }
+
+    // Header, the bitmaps of commits, trees, blobs and tags, then one entry per selected commit:
+    // its position in the index, how many entries back the bitmap it is XORed with is, flags and
+    // the bitmap itself
+    pub fn parse(data: &[u8], pack: &Pack) -> io::Result<Self> {
+        if data.len() < 12 + HASH_SIZE * 2 { return Err(invalid("Bitmap is too short")); }
+        let (body, trailer) = data.split_at(data.len() - HASH_SIZE);
+        if Sha256::digest(body)[..] != *trailer { return Err(invalid("Bitmap checksum mismatch")); }
+        let mut reader = Cursor::new(body);
+
+        let mut signature = [0u8; 4];
+        reader.read_exact(&mut signature)?;
+        if &signature != BITMAP_SIGNATURE || reader.read_u16::<BigEndian>()? != BITMAP_VERSION {
+            return Err(invalid("Not a version 1 bitmap"));
+        }
+        reader.read_u16::<BigEndian>()?;
+        let count = reader.read_u32::<BigEndian>()? as usize;
+        let mut checksum = [0u8; HASH_SIZE];
+        reader.read_exact(&mut checksum)?;
+        if checksum != pack.index.pack_checksum { return Err(invalid("Bitmap does not match its pack")); }
+
+        // Objects by type, walks find the type of what they reach anyway
+        for _ in 0..4 { ewah::decode(&mut reader)?; }
+
+        let mut decoded: Vec<(String, Bitmap)> = Vec::with_capacity(count);
+        for _ in 0..count {
+            let position = reader.read_u32::<BigEndian>()? as usize;
+            let xor_offset = reader.read_u8()? as usize;
+            reader.read_u8()?;
+            let mut bitmap = ewah::decode(&mut reader)?;
+
+            if xor_offset > 0 {
+                let (_, base) = decoded.len().checked_sub(xor_offset).map(|i| &decoded[i]).ok_or_else(|| invalid("Bad bitmap XOR offset"))?;
+                bitmap.xor(base);
+            }
+            let name = pack.index.names.get(position).ok_or_else(|| invalid("Bitmap entry out of bounds"))?;
+            decoded.push((hash::to_hex(name), bitmap));
+        }
+
+        let objects = pack_order(pack);
+        let positions = objects.iter().enumerate().map(|(i, name)| (name.clone(), i)).collect();
+
+        Ok(Self { objects, positions, entries: decoded.into_iter().collect() })
+    }
+
+    pub fn position(&self, object: &str) -> Option<usize> {
+        self.positions.get(object).copied()
+    }
+
+    // The objects a selected commit reaches
+    pub fn get(&self, commit: &str) -> Option<&Bitmap> {
+        self.entries.get(commit)
+    }
+}
+
+// Names of the pack's objects ordered by offset
+fn pack_order(pack: &Pack) -> Vec<String> {
+    let mut order: Vec<usize> = (0..pack.index.len()).collect();
+    order.sort_by_key(|&i| pack.index.offsets[i]);
+
+    order.into_iter().map(|i| hash::to_hex(&pack.index.names[i])).collect()
+}
+
+// Bitmaps for the ref tips and a sample of the commits of a pack. None when some object reachable
+// from the pack's commits is not in it, bitmaps would be incomplete
+pub fn write(storage: &Storage, pack: &Pack) -> Option<Vec<u8>> {
+    let objects = pack_order(pack);
+    let positions: HashMap<&str, usize> = objects.iter().enumerate().map(|(i, name)| (name.as_str(), i)).collect();
+
+    let mut types = [Bitmap::new(), Bitmap::new(), Bitmap::new()];
+    let mut links: Vec<Vec<usize>> = vec![vec![]; objects.len()];
+    let mut commits = vec![];
+    for (i, name) in objects.iter().enumerate() {
+        let offset = pack.index.find(name)?;
+        let (object_type, content) = pack.read_at(offset, &|base| storage.read_raw(base)).ok()?;
+        let content = String::from_utf8_lossy(&content);
+
+        let targets: Vec<String> =
+            match object_type {
+                GitObjectType::Commit => {
+                    commits.push(name.clone());
+                    let commit = commit::parse(&content);
+                    std::iter::once(commit.tree).chain(commit.parents).collect()
+                }
+                GitObjectType::Tree => {
+                    content.lines()
+                        .filter(|line| !line.is_empty())
+                        .map(object_ref::parse)
+                        .filter(|object_ref| object_ref.ref_type != GitObjectType::Commit)
+                        .map(|object_ref| object_ref.hash)
+                        .collect()
+                }
+                GitObjectType::Blob => vec![],
+            };
+        for target in targets { links[i].push(*positions.get(target.as_str())?); }
+
+        let kind = match object_type { GitObjectType::Commit => 0, GitObjectType::Tree => 1, GitObjectType::Blob => 2 };
+        types[kind].set(i);
+    }
+
+    // Parents first, so a commit's bitmap can reuse the ones of the selected commits below it
+    let tips: HashSet<String> = storage.list_refs("refs/").into_iter().map(|(_, hash)| hash).chain(storage.read_ref("HEAD")).collect();
+    let mut graph = CommitGraph::new(storage);
+    let mut selected: Vec<&String> =
+        commits.iter().enumerate().filter(|(i, name)| tips.contains(*name) || i.is_multiple_of(SELECT_EVERY)).map(|(_, name)| name).collect();
+    selected.sort_by_key(|name| graph.generation(name));
+
+    let mut bitmaps: HashMap<usize, Bitmap> = HashMap::new();
+    let mut entries = vec![];
+    for name in selected {
+        let start = positions[name.as_str()];
+        let mut bitmap = Bitmap::new();
+        let mut stack = vec![start];
+
+        while let Some(object) = stack.pop() {
+            if bitmap.get(object) { continue; }
+            match bitmaps.get(&object) {
+                Some(reached) => bitmap.or(reached),
+                None => {
+                    bitmap.set(object);
+                    stack.extend(&links[object]);
+                }
+            }
+        }
+
+        entries.push((pack.index.position(name)?, start));
+        bitmaps.insert(start, bitmap);
+    }
+
+    let mut data = vec![];
+    data.extend_from_slice(BITMAP_SIGNATURE);
+    data.write_u16::<BigEndian>(BITMAP_VERSION).unwrap();
+    data.write_u16::<BigEndian>(FULL_DAG).unwrap();
+    data.write_u32::<BigEndian>(entries.len() as u32).unwrap();
+    data.extend_from_slice(&pack.index.pack_checksum);
+
+    for bitmap in &types { data.extend(ewah::encode(bitmap, objects.len())); }
+    // Tags are not supported
+    data.extend(ewah::encode(&Bitmap::new(), objects.len()));
+
+    for (position, start) in entries {
+        data.write_u32::<BigEndian>(position as u32).unwrap();
+        data.extend_from_slice(&[0, 0]);
+        data.extend(ewah::encode(&bitmaps[&start], objects.len()));
+    }
+
+    let checksum = Sha256::digest(&data);
+    data.extend_from_slice(&checksum);
+
+    Some(data)
+}
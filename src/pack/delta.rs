@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::io;
+
+use crate::pack::index::invalid;
+
+// Git's delta format: the base and result sizes as little-endian base-128 numbers, then
+// instructions that either copy a range of the base or insert literal bytes
+pub fn apply(base: &[u8], delta: &[u8]) -> io::Result<Vec<u8>> {
+    let mut position = 0;
+    let base_size = read_size(delta, &mut position)?;
+    let result_size = read_size(delta, &mut position)?;
+    if base_size != base.len() { return Err(invalid("Delta base size does not match")); }
+
+    let mut result = Vec::with_capacity(result_size);
+    while position < delta.len() {
+        let command = delta[position];
+        position += 1;
+
+        if command & 0x80 != 0 {
+            // Bits 0-3 say which offset bytes follow, bits 4-6 which size bytes
+            let mut offset = 0usize;
+            let mut size = 0usize;
+            for bit in 0..7 {
+                if command & (1 << bit) == 0 { continue; }
+
+                let byte = *delta.get(position).ok_or_else(|| invalid("Truncated delta"))? as usize;
+                position += 1;
+                if bit < 4 { offset |= byte << (8 * bit); } else { size |= byte << (8 * (bit - 4)); }
+            }
+            if size == 0 { size = 0x10000; }
+
+            let range = base.get(offset..offset + size).ok_or_else(|| invalid("Delta copies outside of its base"))?;
+            result.extend_from_slice(range);
+        } else if command != 0 {
+            let range = delta.get(position..position + command as usize).ok_or_else(|| invalid("Truncated delta"))?;
+            result.extend_from_slice(range);
+            position += command as usize;
+        } else {
+            return Err(invalid("Unexpected delta opcode 0"));
+        }
+    }
+
+    if result.len() != result_size { return Err(invalid("Delta result size does not match")); }
+
+    Ok(result)
+}
+
+fn read_size(delta: &[u8], position: &mut usize) -> io::Result<usize> {
+    let mut size = 0usize;
+    let mut shift = 0;
+
+    loop {
+        let byte = *delta.get(*position).ok_or_else(|| invalid("Truncated delta header"))?;
+        *position += 1;
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 { return Ok(size); }
+    }
+}
+
+// Source blocks of this size are indexed, shorter matches are inserted as literal bytes
+const BLOCK: usize = 16;
+// Copy instructions can address at most this many bytes
+const MAX_COPY: usize = 0x10000;
+const MAX_INSERT: usize = 0x7f;
+// Candidate offsets kept per block, repetitive content would make lookups quadratic otherwise
+const MAX_CANDIDATES: usize = 64;
+
+// Encodes `target` as copies from `base` and inserts. Gives up once the delta grows past `max_size`
+pub fn create(base: &[u8], target: &[u8], max_size: usize) -> Option<Vec<u8>> {
+    let mut blocks: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for offset in (0..base.len().saturating_sub(BLOCK - 1)).step_by(BLOCK) {
+        let candidates = blocks.entry(&base[offset..offset + BLOCK]).or_default();
+        if candidates.len() < MAX_CANDIDATES { candidates.push(offset); }
+    }
+
+    let mut delta = vec![];
+    write_size(&mut delta, base.len());
+    write_size(&mut delta, target.len());
+
+    let mut pending: Vec<u8> = vec![];
+    let mut position = 0;
+    while position < target.len() {
+        let (mut offset, mut length) = (0, 0);
+        if let Some(candidates) = target.get(position..position + BLOCK).and_then(|block| blocks.get(block)) {
+            for &candidate in candidates {
+                let common = base[candidate..].iter().zip(&target[position..]).take_while(|(a, b)| a == b).count();
+                if common > length { (offset, length) = (candidate, common); }
+            }
+        }
+
+        if length < BLOCK {
+            pending.push(target[position]);
+            position += 1;
+        } else {
+            position += length;
+            // The match may also cover literal bytes right before it
+            while offset > 0 && pending.last() == Some(&base[offset - 1]) {
+                pending.pop();
+                offset -= 1;
+                length += 1;
+            }
+            push_insert(&mut delta, &mut pending);
+            push_copy(&mut delta, offset, length);
+        }
+
+        if delta.len() + pending.len() > max_size { return None; }
+    }
+    push_insert(&mut delta, &mut pending);
+
+    (delta.len() <= max_size).then_some(delta)
+}
+
+fn write_size(delta: &mut Vec<u8>, mut size: usize) {
+    loop {
+        let byte = (size & 0x7f) as u8;
+        size >>= 7;
+        if size == 0 {
+            delta.push(byte);
+            return;
+        }
+        delta.push(byte | 0x80);
+    }
+}
+
+fn push_insert(delta: &mut Vec<u8>, pending: &mut Vec<u8>) {
+    for chunk in pending.chunks(MAX_INSERT) {
+        delta.push(chunk.len() as u8);
+        delta.extend_from_slice(chunk);
+    }
+    pending.clear();
+}
+
+// Only the non-zero bytes of the offset and size are written, flagged in the command byte
+fn push_copy(delta: &mut Vec<u8>, mut offset: usize, mut length: usize) {
+    while length > 0 {
+        let size = length.min(MAX_COPY);
+        let mut command = 0x80u8;
+        let mut operands = vec![];
+
+        for i in 0..4 {
+            let byte = (offset >> (8 * i)) as u8;
+            if byte != 0 {
+                command |= 1 << i;
+                operands.push(byte);
+            }
+        }
+        // A missing size means 0x10000
+        if size != MAX_COPY {
+            for i in 0..3 {
+                let byte = (size >> (8 * i)) as u8;
+                if byte != 0 {
+                    command |= 0x10 << i;
+                    operands.push(byte);
+                }
+            }
+        }
+
+        delta.push(command);
+        delta.extend(operands);
+        offset += size;
+        length -= size;
+    }
+}
@@ -0,0 +1,121 @@
+use std::io::{self, Cursor};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::pack::index::invalid;
+
+// A running-length word: bit 0 is the bit repeated by the run, the next 32 bits how many words the
+// run lasts, the top 31 bits how many literal words follow it
+const RUNNING_BITS: u32 = 32;
+const LITERAL_BITS: u32 = 31;
+const MAX_RUN: u64 = (1 << RUNNING_BITS) - 1;
+const MAX_LITERALS: u64 = (1 << LITERAL_BITS) - 1;
+
+// A plain bitmap, bit `i` is bit `i % 64` of word `i / 64`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bitmap {
+    words: Vec<u64>,
+}
+
+impl Bitmap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, bit: usize) {
+        let word = bit / 64;
+        if word >= self.words.len() { self.words.resize(word + 1, 0); }
+        self.words[word] |= 1 << (bit % 64);
+    }
+
+    pub fn get(&self, bit: usize) -> bool {
+        self.words.get(bit / 64).is_some_and(|word| word & (1 << (bit % 64)) != 0)
+    }
+
+    pub fn or(&mut self, other: &Bitmap) {
+        if other.words.len() > self.words.len() { self.words.resize(other.words.len(), 0); }
+        for (word, other) in self.words.iter_mut().zip(&other.words) { *word |= other; }
+    }
+
+    pub fn and_not(&mut self, other: &Bitmap) {
+        for (word, other) in self.words.iter_mut().zip(&other.words) { *word &= !other; }
+    }
+
+    pub fn xor(&mut self, other: &Bitmap) {
+        if other.words.len() > self.words.len() { self.words.resize(other.words.len(), 0); }
+        for (word, other) in self.words.iter_mut().zip(&other.words) { *word ^= other; }
+    }
+
+    pub fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words
+            .iter()
+            .enumerate()
+            .flat_map(|(i, word)| (0..64).filter(move |bit| word & (1 << bit) != 0).map(move |bit| i * 64 + bit))
+    }
+}
+
+// EWAH as Git serializes it: the size in bits, the number of 64-bit words, the words, then the
+// position of the last running-length word
+pub fn encode(bitmap: &Bitmap, bits: usize) -> Vec<u8> {
+    let words = &bitmap.words[..bitmap.words.len().min(bits.div_ceil(64))];
+    let mut buffer: Vec<u64> = vec![];
+    let mut last_rlw = 0;
+    let mut i = 0;
+
+    while i < words.len() || buffer.is_empty() {
+        let running_bit = words.get(i) == Some(&u64::MAX);
+        let mut run = 0;
+        while i < words.len() && run < MAX_RUN && words[i] == if running_bit { u64::MAX } else { 0 } {
+            run += 1;
+            i += 1;
+        }
+
+        let start = i;
+        while i < words.len() && (i - start) < MAX_LITERALS as usize && words[i] != 0 && words[i] != u64::MAX {
+            i += 1;
+        }
+
+        last_rlw = buffer.len();
+        buffer.push(running_bit as u64 | (run << 1) | (((i - start) as u64) << (1 + RUNNING_BITS)));
+        buffer.extend_from_slice(&words[start..i]);
+    }
+
+    let mut data = vec![];
+    data.write_u32::<BigEndian>(bits as u32).unwrap();
+    data.write_u32::<BigEndian>(buffer.len() as u32).unwrap();
+    for word in buffer { data.write_u64::<BigEndian>(word).unwrap(); }
+    data.write_u32::<BigEndian>(last_rlw as u32).unwrap();
+
+    data
+}
+
+pub fn decode(reader: &mut Cursor<&[u8]>) -> io::Result<Bitmap> {
+    let bits = reader.read_u32::<BigEndian>()? as usize;
+    let count = reader.read_u32::<BigEndian>()? as usize;
+    let mut buffer = Vec::with_capacity(count);
+    for _ in 0..count { buffer.push(reader.read_u64::<BigEndian>()?); }
+    reader.read_u32::<BigEndian>()?;
+
+    let mut words = vec![];
+    let mut position = 0;
+    while position < buffer.len() {
+        let rlw = buffer[position];
+        let running_bit = rlw & 1 == 1;
+        let run = (rlw >> 1) & MAX_RUN;
+        let literals = (rlw >> (1 + RUNNING_BITS)) as usize;
+        position += 1;
+
+        words.extend(std::iter::repeat_n(if running_bit { u64::MAX } else { 0 }, run as usize));
+        let literal_words = buffer.get(position..position + literals).ok_or_else(|| invalid("Truncated EWAH bitmap"))?;
+        words.extend_from_slice(literal_words);
+        position += literals;
+    }
+
+    if words.len() * 64 < bits { words.resize(bits.div_ceil(64), 0); }
+    words.truncate(bits.div_ceil(64));
+    // Bits past the size are not part of the bitmap
+    if !bits.is_multiple_of(64) {
+        if let Some(last) = words.last_mut() { *last &= (1u64 << (bits % 64)) - 1; }
+    }
+
+    Ok(Bitmap { words })
+}
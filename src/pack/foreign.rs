@@ -0,0 +1,119 @@
+use std::io;
+use std::path::Path;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::pack::index::{invalid, HASH_SIZE, SHA1_SIZE};
+use crate::pack::{Pack, PackedObject};
+use crate::structs::git_tree::GitObjectRef;
+use crate::structs::GitObjectType;
+use crate::utils::hash;
+
+const TYPE_MASK: u32 = 0o170000;
+const TREE_MODE: u32 = 0o040000;
+const GITLINK_MODE: u32 = 0o160000;
+
+// A pack written by Git, for repositories Git cloned or repacked. Its objects are named by the
+// hash of a "<type> <size>\0" header and their content, SHA-1 or SHA-256 after the object format
+// of the repository, and its trees are binary. Objects are checked against their names and trees
+// turned into rinit's text form as they are read, under their Git names. Nothing writes to these
+// packs, repacking leaves them and their objects where they are
+pub enum ForeignPack {
+    Sha1(Pack<SHA1_SIZE>),
+    Sha256(Pack<HASH_SIZE>),
+}
+
+impl ForeignPack {
+    pub fn path(&self) -> &Path {
+        match self {
+            ForeignPack::Sha1(pack) => &pack.path,
+            ForeignPack::Sha256(pack) => &pack.path,
+        }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        match self {
+            ForeignPack::Sha1(pack) => pack.index.names.iter().map(|name| hash::to_hex(name)).collect(),
+            ForeignPack::Sha256(pack) => pack.index.names.iter().map(|name| hash::to_hex(name)).collect(),
+        }
+    }
+
+    pub fn contains(&self, object: &str) -> bool {
+        match self {
+            ForeignPack::Sha1(pack) => pack.contains(object),
+            ForeignPack::Sha256(pack) => pack.contains(object),
+        }
+    }
+
+    pub fn find_prefix(&self, prefix: &str) -> Vec<String> {
+        match self {
+            ForeignPack::Sha1(pack) => pack.index.find_prefix(prefix),
+            ForeignPack::Sha256(pack) => pack.index.find_prefix(prefix),
+        }
+    }
+
+    // Git's packs on disk are never thin, every delta base is in the pack itself
+    pub fn read(&self, object: &str) -> Option<io::Result<PackedObject>> {
+        let (read, hash_size) =
+            match self {
+                ForeignPack::Sha1(pack) => (pack.index.find(object).map(|offset| pack.read_at(offset, &|_| None)), SHA1_SIZE),
+                ForeignPack::Sha256(pack) => (pack.index.find(object).map(|offset| pack.read_at(offset, &|_| None)), HASH_SIZE),
+            };
+
+        read.map(|result| result.and_then(|object_read| convert(object, object_read, hash_size)))
+    }
+}
+
+// Git's name of an object: the hash of its framed content, with SHA-1 for 20-byte names
+pub fn object_name(object_type: GitObjectType, content: &[u8], hash_size: usize) -> String {
+    let mut framed = format!("{} {}\0", object_type, content.len()).into_bytes();
+    framed.extend_from_slice(content);
+
+    match hash_size {
+        SHA1_SIZE => hash::to_hex(&Sha1::digest(&framed)),
+        _ => hash::to_hex(&Sha256::digest(&framed)),
+    }
+}
+
+// Objects are text in rinit, so contents that are not UTF-8 can't be read
+fn convert(name: &str, (object_type, content): PackedObject, hash_size: usize) -> io::Result<PackedObject> {
+    if object_name(object_type, &content, hash_size) != name { return Err(invalid("Pack entry does not match its name")); }
+
+    let content =
+        match object_type {
+            GitObjectType::Tree => text_tree(&content, hash_size)?.into_bytes(),
+            _ => content,
+        };
+    if std::str::from_utf8(&content).is_err() { return Err(invalid("Object is not UTF-8 text")); }
+
+    Ok((object_type, content))
+}
+
+// Git tree entries are "<octal mode> <name>\0" followed by the binary object name, rinit's are
+// lines of "mode type hash name"
+fn text_tree(content: &[u8], hash_size: usize) -> io::Result<String> {
+    let mut refs = vec![];
+    let mut rest = content;
+
+    while !rest.is_empty() {
+        let space = rest.iter().position(|byte| *byte == b' ').ok_or_else(|| invalid("Malformed tree entry"))?;
+        let null = rest.iter().position(|byte| *byte == 0).ok_or_else(|| invalid("Malformed tree entry"))?;
+        let object = rest.get(null + 1..null + 1 + hash_size).ok_or_else(|| invalid("Truncated tree entry"))?;
+
+        let mode = std::str::from_utf8(&rest[..space]).ok().and_then(|mode| u32::from_str_radix(mode, 8).ok());
+        let name = std::str::from_utf8(rest.get(space + 1..null).unwrap_or_default()).ok();
+        let (Some(mode), Some(name)) = (mode, name) else { return Err(invalid("Malformed tree entry")) };
+        if name.is_empty() || name.contains('\n') { return Err(invalid("Tree entry name can't be written as text")); }
+
+        let ref_type =
+            match mode & TYPE_MASK {
+                TREE_MODE => GitObjectType::Tree,
+                GITLINK_MODE => GitObjectType::Commit,
+                _ => GitObjectType::Blob,
+            };
+        refs.push(GitObjectRef { permissions: mode, ref_type, hash: hash::to_hex(object), content: name.to_string() });
+        rest = &rest[null + 1 + hash_size..];
+    }
+
+    Ok(refs.iter().map(|object_ref| object_ref.to_string()).collect::<Vec<_>>().join("\n"))
+}
@@ -0,0 +1,183 @@
+use std::io::{self, Cursor, Read};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use sha2::{Digest, Sha256};
+
+use crate::utils::hash;
+
+pub const IDX_SIGNATURE: &[u8; 4] = b"\xfftOc";
+pub const IDX_VERSION: u32 = 2;
+pub const HASH_SIZE: usize = 32;
+// Object names of packs written by Git with its default object format
+pub const SHA1_SIZE: usize = 20;
+// Offsets with the high bit set point into the table of 64-bit offsets
+pub const LARGE_OFFSET: u32 = 0x8000_0000;
+
+// One object of a pack being indexed
+#[derive(Debug, Clone)]
+pub struct IndexEntry {
+    pub name: [u8; HASH_SIZE],
+    pub offset: u64,
+    // CRC32 of the entry as stored in the pack, header included
+    pub crc: u32,
+}
+
+// Pack index version 2: a fan-out table of 256 cumulative counts by first byte, the sorted object
+// names, their CRC32s and pack offsets, then both checksums. Names and checksums have N bytes,
+// SHA1_SIZE for packs of Git's default object format
+#[derive(Debug)]
+pub struct PackIndex<const N: usize = HASH_SIZE> {
+    pub fanout: [u32; 256],
+    pub names: Vec<[u8; N]>,
+    pub offsets: Vec<u64>,
+    pub pack_checksum: [u8; N],
+}
+
+impl<const N: usize> PackIndex<N> {
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        let mut reader = Cursor::new(data);
+
+        let mut signature = [0u8; 4];
+        reader.read_exact(&mut signature)?;
+        if &signature != IDX_SIGNATURE || reader.read_u32::<BigEndian>()? != IDX_VERSION {
+            return Err(invalid("Not a version 2 pack index"));
+        }
+
+        let mut fanout = [0u32; 256];
+        for count in fanout.iter_mut() { *count = reader.read_u32::<BigEndian>()?; }
+        if fanout.windows(2).any(|pair| pair[0] > pair[1]) { return Err(invalid("Pack index fan-out is not sorted")); }
+
+        let count = fanout[255] as usize;
+        if data.len() < index_size(count, N) { return Err(invalid("Truncated pack index")); }
+
+        let mut names = Vec::with_capacity(count);
+        for _ in 0..count {
+            let mut name = [0u8; N];
+            reader.read_exact(&mut name)?;
+            names.push(name);
+        }
+
+        // CRC32s of the packed entries only matter when verifying the pack
+        for _ in 0..count { reader.read_u32::<BigEndian>()?; }
+
+        let mut small = Vec::with_capacity(count);
+        for _ in 0..count { small.push(reader.read_u32::<BigEndian>()?); }
+
+        let large_count = small.iter().filter(|offset| *offset & LARGE_OFFSET != 0).count();
+        let mut large = Vec::with_capacity(large_count);
+        for _ in 0..large_count { large.push(reader.read_u64::<BigEndian>()?); }
+
+        let offsets =
+            small
+                .into_iter()
+                .map(|offset| match offset & LARGE_OFFSET {
+                    0 => Ok(offset as u64),
+                    _ => large.get((offset & !LARGE_OFFSET) as usize).copied().ok_or_else(|| invalid("Bad 64-bit pack offset")),
+                })
+                .collect::<io::Result<Vec<u64>>>()?;
+
+        let mut pack_checksum = [0u8; N];
+        reader.read_exact(&mut pack_checksum)?;
+
+        Ok(Self { fanout, names, offsets, pack_checksum })
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    // Position of an object in the sorted name table. The fan-out narrows the binary search down
+    // to the names sharing the first byte
+    pub fn position(&self, object: &str) -> Option<usize> {
+        let name: [u8; N] = name_bytes(object)?;
+        let first = name[0] as usize;
+        let start = if first == 0 { 0 } else { self.fanout[first - 1] as usize };
+        let end = self.fanout[first] as usize;
+
+        self.names[start..end].binary_search(&name).ok().map(|i| start + i)
+    }
+
+    pub fn find(&self, object: &str) -> Option<u64> {
+        self.position(object).map(|i| self.offsets[i])
+    }
+
+    // Object names starting with an abbreviated hex prefix
+    pub fn find_prefix(&self, prefix: &str) -> Vec<String> {
+        let prefix = prefix.to_lowercase();
+        let Some(first) = prefix.get(..2).and_then(|byte| u8::from_str_radix(byte, 16).ok()) else { return vec![] };
+
+        let first = first as usize;
+        let start = if first == 0 { 0 } else { self.fanout[first - 1] as usize };
+        let end = self.fanout[first] as usize;
+
+        self.names[start..end]
+            .iter()
+            .map(|name| hash::to_hex(name))
+            .filter(|name| name.starts_with(&prefix))
+            .collect()
+    }
+}
+
+// Serializes a version 2 index for the entries of a pack
+pub fn write(entries: &[IndexEntry], pack_checksum: &[u8; HASH_SIZE]) -> Vec<u8> {
+    let mut entries = entries.to_vec();
+    entries.sort_by_key(|entry| entry.name);
+
+    let mut data = vec![];
+    data.extend_from_slice(IDX_SIGNATURE);
+    data.write_u32::<BigEndian>(IDX_VERSION).unwrap();
+
+    let mut count = 0;
+    for first in 0..=255u8 {
+        count += entries.iter().filter(|entry| entry.name[0] == first).count();
+        data.write_u32::<BigEndian>(count as u32).unwrap();
+    }
+
+    for entry in &entries { data.extend_from_slice(&entry.name); }
+    for entry in &entries { data.write_u32::<BigEndian>(entry.crc).unwrap(); }
+
+    let mut large = vec![];
+    for entry in &entries {
+        if entry.offset < LARGE_OFFSET as u64 {
+            data.write_u32::<BigEndian>(entry.offset as u32).unwrap();
+        } else {
+            data.write_u32::<BigEndian>(LARGE_OFFSET | large.len() as u32).unwrap();
+            large.push(entry.offset);
+        }
+    }
+    for offset in large { data.write_u64::<BigEndian>(offset).unwrap(); }
+
+    data.extend_from_slice(pack_checksum);
+    let checksum = Sha256::digest(&data);
+    data.extend_from_slice(&checksum);
+
+    data
+}
+
+// Size of an index with `hash_size` names and no 64-bit offsets
+fn index_size(count: usize, hash_size: usize) -> usize {
+    8 + 256 * 4 + count * (hash_size + 8) + 2 * hash_size
+}
+
+// Size of the object names of an index, told by its length: one with SHA-1 names is shorter than
+// one with SHA-256 names can be, even with every offset taking 64 bits
+pub fn name_size(data: &[u8]) -> usize {
+    let count = data.get(8 + 255 * 4..8 + 256 * 4).map_or(0, |bytes| u32::from_be_bytes(bytes.try_into().unwrap()) as usize);
+
+    if data.len() < index_size(count, HASH_SIZE) { SHA1_SIZE } else { HASH_SIZE }
+}
+
+// The bytes of a hex object name, None unless it is N bytes long
+fn name_bytes<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != N * 2 || !hash::is_hex(hex) { return None; }
+
+    let mut bytes = [0u8; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+
+    Some(bytes)
+}
+
+pub fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
@@ -0,0 +1,225 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read};
+use flate2::bufread::ZlibDecoder;
+use flate2::Crc;
+use sha2::{Digest, Sha256};
+
+use crate::pack::index::{invalid, IndexEntry, HASH_SIZE, SHA1_SIZE};
+use crate::pack::write::{compress, entry_header, type_code};
+use crate::pack::{delta, object_type, parse_entry_header, DeltaBase, PackedObject, PACK_SIGNATURE, PACK_VERSION};
+use crate::structs::GitObjectType;
+use crate::utils::hash;
+
+// Objects received are stored under rinit's names, Git's packs are only read in place
+const WRITTEN_BY_GIT: &str = "Pack was written by Git, put it in objects/pack with its .idx to read it";
+
+// An object of an incoming pack once its delta chain is resolved
+pub struct ReceivedObject {
+    pub name: String,
+    pub object_type: GitObjectType,
+    pub content: Vec<u8>,
+    pub offset: u64,
+    // CRC32 of the entry as stored in the pack, header included
+    pub crc: u32,
+}
+
+// Objects a thin pack's deltas are based on, by name
+pub type ExternalBases = Vec<(String, PackedObject)>;
+
+pub struct ReceivedPack {
+    pub objects: Vec<ReceivedObject>,
+    // Bases of REF_DELTA entries that were not in the pack: a thin pack relies on the receiver
+    // having them already
+    pub external: ExternalBases,
+    pub checksum: [u8; HASH_SIZE],
+}
+
+// A pack entry as read sequentially, before its delta is applied
+struct RawEntry {
+    offset: u64,
+    kind: u8,
+    base: Option<DeltaBase>,
+    data: Vec<u8>,
+    crc: u32,
+}
+
+// Reads a pack that comes without an index: entries are walked one after the other, each zlib
+// stream telling where the next entry starts. Deltas are resolved once their base is, REF_DELTA
+// bases missing from the pack come from `external`
+pub fn parse(data: &[u8], external: &dyn Fn(&str) -> Option<PackedObject>) -> io::Result<ReceivedPack> {
+    if data.len() < 12 + HASH_SIZE || &data[..4] != PACK_SIGNATURE { return Err(invalid("Not a pack file")); }
+    let version = u32::from_be_bytes(data[4..8].try_into().unwrap());
+    if version != PACK_VERSION && version != 3 { return Err(invalid("Unsupported pack version")); }
+
+    let count = u32::from_be_bytes(data[8..12].try_into().unwrap()) as usize;
+    let (body, trailer) = data.split_at(data.len() - HASH_SIZE);
+    if Sha256::digest(body)[..] != *trailer {
+        // A SHA-1 pack cannot be verified here, but its entries end right before a shorter trailer
+        let sha1_body = &data[..data.len() - SHA1_SIZE];
+        if walk(sha1_body, count).is_ok() { return Err(invalid(WRITTEN_BY_GIT)); }
+        return Err(invalid("Pack checksum mismatch"));
+    }
+    let checksum: [u8; HASH_SIZE] = trailer.try_into().unwrap();
+
+    let entries = walk(body, count)?;
+    let (objects, external) = resolve(entries, external)?;
+    // Objects are stored as text once unpacked
+    if let Some(object) = objects.iter().find(|object| std::str::from_utf8(&object.content).is_err()) {
+        return Err(invalid(&format!("Pack object {} is not UTF-8", object.name)));
+    }
+    if objects.iter().any(|object| object.object_type == GitObjectType::Tree && !is_text_tree(&object.content)) {
+        return Err(invalid(WRITTEN_BY_GIT));
+    }
+
+    Ok(ReceivedPack { objects, external, checksum })
+}
+
+// Entries of a pack body, which has to end with the last one
+fn walk(body: &[u8], count: usize) -> io::Result<Vec<RawEntry>> {
+    let mut entries = Vec::with_capacity(count);
+    let mut offset = 12u64;
+    for _ in 0..count {
+        let header = parse_entry_header(body, offset, HASH_SIZE)?;
+        let (inflated, consumed) = inflate(body, header.data_offset, header.size)?;
+        let end = header.data_offset + consumed;
+
+        let mut crc = Crc::new();
+        crc.update(&body[offset as usize..end]);
+        entries.push(RawEntry { offset, kind: header.kind, base: header.base, data: inflated, crc: crc.sum() });
+        offset = end as u64;
+    }
+    if offset as usize != body.len() { return Err(invalid("Pack has trailing garbage")); }
+
+    Ok(entries)
+}
+
+// rinit trees are lines of "mode type hash name", Git's are binary
+fn is_text_tree(content: &[u8]) -> bool {
+    let Ok(text) = std::str::from_utf8(content) else { return false };
+
+    text.lines().filter(|line| !line.is_empty()).all(|line| {
+        let parts: Vec<&str> = line.splitn(4, ' ').collect();
+        parts.len() == 4 && ["blob", "tree", "commit"].contains(&parts[1]) && hash::is_hex(parts[2])
+    })
+}
+
+// Inflates the zlib stream at `start`, returns the data and how many compressed bytes it took
+fn inflate(data: &[u8], start: usize, size: usize) -> io::Result<(Vec<u8>, usize)> {
+    let input = data.get(start..).ok_or_else(|| invalid("Pack entry out of bounds"))?;
+    let mut decoder = ZlibDecoder::new(input);
+    let mut output = Vec::with_capacity(size);
+    decoder.read_to_end(&mut output)?;
+
+    if output.len() != size { return Err(invalid("Pack entry size does not match")); }
+
+    Ok((output, decoder.total_in() as usize))
+}
+
+// Whole objects first, then passes over the deltas whose base is known by now. Bases outside of
+// the pack are only looked up when a pass makes no progress
+fn resolve(entries: Vec<RawEntry>, external: &dyn Fn(&str) -> Option<PackedObject>) -> io::Result<(Vec<ReceivedObject>, ExternalBases)> {
+    let positions: HashMap<u64, usize> = entries.iter().enumerate().map(|(i, entry)| (entry.offset, i)).collect();
+    let mut resolved: Vec<Option<PackedObject>> = vec![None; entries.len()];
+    let mut names: HashMap<String, usize> = HashMap::new();
+    let mut outside: ExternalBases = vec![];
+
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.base.is_none() {
+            resolved[i] = Some((object_type(entry.kind)?, entry.data.clone()));
+            names.insert(hash::from_bytes(&entry.data), i);
+        }
+    }
+
+    let mut use_external = false;
+    loop {
+        let mut progress = false;
+
+        for (i, entry) in entries.iter().enumerate() {
+            if resolved[i].is_some() { continue; }
+
+            let base: Option<&PackedObject> =
+                match entry.base.as_ref().unwrap() {
+                    DeltaBase::Offset(offset) => {
+                        let position = positions.get(offset).ok_or_else(|| invalid("Delta base is not an entry of the pack"))?;
+                        resolved[*position].as_ref()
+                    }
+                    DeltaBase::Name(name) => {
+                        match names.get(name) {
+                            Some(position) => resolved[*position].as_ref(),
+                            None if use_external => outside.iter().find(|(found, _)| found == name).map(|(_, object)| object),
+                            None => None,
+                        }
+                    }
+                };
+            let Some((base_type, base_content)) = base else { continue };
+
+            let object = (*base_type, delta::apply(base_content, &entry.data)?);
+            names.insert(hash::from_bytes(&object.1), i);
+            resolved[i] = Some(object);
+            progress = true;
+        }
+
+        if resolved.iter().all(Option::is_some) { break; }
+        if !progress && use_external {
+            let missing = resolved.iter().filter(|object| object.is_none()).count();
+            return Err(invalid(&format!("Pack has {} unresolved deltas", missing)));
+        }
+
+        // Nothing left to resolve from inside the pack, the remaining REF_DELTA bases may be local
+        if !progress {
+            use_external = true;
+            for (i, entry) in entries.iter().enumerate() {
+                let Some(DeltaBase::Name(name)) = &entry.base else { continue };
+                if resolved[i].is_some() || names.contains_key(name) || outside.iter().any(|(found, _)| found == name) { continue; }
+
+                if let Some(object) = external(name) { outside.push((name.clone(), object)); }
+            }
+        }
+    }
+
+    let objects =
+        entries
+            .into_iter()
+            .zip(resolved)
+            .map(|(entry, object)| {
+                let (object_type, content) = object.unwrap();
+                ReceivedObject { name: hash::from_bytes(&content), object_type, content, offset: entry.offset, crc: entry.crc }
+            })
+            .collect();
+
+    Ok((objects, outside))
+}
+
+// Appends the external bases of a thin pack as whole objects so that the pack stands on its own,
+// then rewrites the object count and the trailing checksum
+pub fn complete(data: &[u8], pack: &mut ReceivedPack) -> Vec<u8> {
+    let mut completed = data[..data.len() - HASH_SIZE].to_vec();
+
+    for (name, (object_type, content)) in pack.external.drain(..) {
+        let offset = completed.len() as u64;
+        let mut entry = entry_header(type_code(object_type), content.len());
+        entry.extend(compress(&content));
+
+        let mut crc = Crc::new();
+        crc.update(&entry);
+        completed.append(&mut entry);
+        pack.objects.push(ReceivedObject { name, object_type, content, offset, crc: crc.sum() });
+    }
+
+    completed[8..12].copy_from_slice(&(pack.objects.len() as u32).to_be_bytes());
+    pack.checksum = Sha256::digest(&completed).into();
+    completed.extend_from_slice(&pack.checksum);
+
+    completed
+}
+
+// Index entries of the objects, the first copy wins when a pack holds an object twice
+pub fn index_entries(pack: &ReceivedPack) -> Vec<IndexEntry> {
+    let mut seen = HashSet::new();
+
+    pack.objects
+        .iter()
+        .filter(|object| seen.insert(object.name.as_str()))
+        .map(|object| IndexEntry { name: hash::to_bytes(&object.name).unwrap(), offset: object.offset, crc: object.crc })
+        .collect()
+}
@@ -0,0 +1,246 @@
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use sha2::{Digest, Sha256};
+
+use crate::pack::index::{invalid, HASH_SIZE, LARGE_OFFSET};
+use crate::pack::Pack;
+use crate::utils::hash;
+
+pub const MIDX_FILE: &str = "multi-pack-index";
+pub const MIDX_SIGNATURE: &[u8; 4] = b"MIDX";
+pub const MIDX_VERSION: u8 = 1;
+// Object names are SHA-256
+const HASH_VERSION: u8 = 2;
+
+const CHUNK_PACK_NAMES: u32 = 0x504e_414d;
+const CHUNK_FANOUT: u32 = 0x4f49_4446;
+const CHUNK_NAMES: u32 = 0x4f49_444c;
+const CHUNK_OFFSETS: u32 = 0x4f4f_4646;
+const CHUNK_LARGE_OFFSETS: u32 = 0x4c4f_4646;
+
+// One index over the objects of several packs: a header, a table of chunks, the names of the
+// covered `.idx` files, then a fan-out, sorted object names and for each the pack it is read from
+// and its offset there. Objects found in several packs are listed once
+#[derive(Debug)]
+pub struct MultiPackIndex {
+    pub pack_names: Vec<String>,
+    pub fanout: [u32; 256],
+    pub names: Vec<[u8; HASH_SIZE]>,
+    // Position of the pack in `pack_names` and offset of the object in that pack
+    pub locations: Vec<(u32, u64)>,
+}
+
+impl MultiPackIndex {
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read(path)?)
+    }
+
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        if data.len() < 12 + HASH_SIZE { return Err(invalid("Multi-pack-index is too short")); }
+        let body = &data[..data.len() - HASH_SIZE];
+        let mut reader = Cursor::new(body);
+
+        let mut signature = [0u8; 4];
+        reader.read_exact(&mut signature)?;
+        if &signature != MIDX_SIGNATURE || reader.read_u8()? != MIDX_VERSION {
+            return Err(invalid("Not a version 1 multi-pack-index"));
+        }
+        if reader.read_u8()? != HASH_VERSION { return Err(invalid("Multi-pack-index uses another hash")); }
+        let chunk_count = reader.read_u8()? as usize;
+        if reader.read_u8()? != 0 { return Err(invalid("Incremental multi-pack-indexes are not supported")); }
+        let pack_count = reader.read_u32::<BigEndian>()? as usize;
+
+        let mut table = Vec::with_capacity(chunk_count + 1);
+        for _ in 0..=chunk_count {
+            table.push((reader.read_u32::<BigEndian>()?, reader.read_u64::<BigEndian>()? as usize));
+        }
+        let chunk = |id: u32| -> io::Result<&[u8]> {
+            let position = table.iter().position(|(found, _)| *found == id);
+            let (start, end) = position.map(|i| (table[i].1, table[i + 1].1)).ok_or_else(|| invalid("Missing multi-pack-index chunk"))?;
+            body.get(start..end).ok_or_else(|| invalid("Multi-pack-index chunk out of bounds"))
+        };
+
+        let pack_names: Vec<String> =
+            chunk(CHUNK_PACK_NAMES)?
+                .split(|byte| *byte == 0)
+                .filter(|name| !name.is_empty())
+                .map(|name| String::from_utf8_lossy(name).to_string())
+                .collect();
+        if pack_names.len() != pack_count { return Err(invalid("Multi-pack-index pack count does not match")); }
+
+        let mut fanout = [0u32; 256];
+        let mut reader = Cursor::new(chunk(CHUNK_FANOUT)?);
+        for count in fanout.iter_mut() { *count = reader.read_u32::<BigEndian>()?; }
+        let count = fanout[255] as usize;
+
+        let names: Vec<[u8; HASH_SIZE]> =
+            chunk(CHUNK_NAMES)?.chunks_exact(HASH_SIZE).map(|name| name.try_into().unwrap()).collect();
+        if names.len() != count { return Err(invalid("Multi-pack-index object count does not match")); }
+
+        let large: Vec<u64> =
+            match table.iter().any(|(id, _)| *id == CHUNK_LARGE_OFFSETS) {
+                true => chunk(CHUNK_LARGE_OFFSETS)?.chunks_exact(8).map(|offset| u64::from_be_bytes(offset.try_into().unwrap())).collect(),
+                false => vec![],
+            };
+
+        let mut reader = Cursor::new(chunk(CHUNK_OFFSETS)?);
+        let mut locations = Vec::with_capacity(count);
+        for _ in 0..count {
+            let pack = reader.read_u32::<BigEndian>()?;
+            let offset = reader.read_u32::<BigEndian>()?;
+            if pack as usize >= pack_count { return Err(invalid("Multi-pack-index points to an unknown pack")); }
+
+            let offset =
+                match offset & LARGE_OFFSET {
+                    0 => offset as u64,
+                    _ => *large.get((offset & !LARGE_OFFSET) as usize).ok_or_else(|| invalid("Bad 64-bit multi-pack-index offset"))?,
+                };
+            locations.push((pack, offset));
+        }
+
+        Ok(Self { pack_names, fanout, names, locations })
+    }
+
+    // Where an object is, as a position in `pack_names` and an offset in that pack
+    pub fn find(&self, object: &str) -> Option<(usize, u64)> {
+        let name = hash::to_bytes(object).ok()?;
+        let (start, end) = self.range(name[0]);
+        let i = self.names[start..end].binary_search(&name).ok()? + start;
+        let (pack, offset) = self.locations[i];
+
+        Some((pack as usize, offset))
+    }
+
+    pub fn find_prefix(&self, prefix: &str) -> Vec<String> {
+        let prefix = prefix.to_lowercase();
+        let Some(first) = prefix.get(..2).and_then(|byte| u8::from_str_radix(byte, 16).ok()) else { return vec![] };
+        let (start, end) = self.range(first);
+
+        self.names[start..end].iter().map(|name| hash::to_hex(name)).filter(|name| name.starts_with(&prefix)).collect()
+    }
+
+    fn range(&self, first: u8) -> (usize, usize) {
+        let first = first as usize;
+        let start = if first == 0 { 0 } else { self.fanout[first - 1] as usize };
+
+        (start, self.fanout[first] as usize)
+    }
+}
+
+// Serializes an index over `packs`, given newest first: an object found in several packs is read
+// from the newest one
+pub fn write(packs: &[Pack]) -> Vec<u8> {
+    let mut pack_names: Vec<String> = packs.iter().map(idx_name).collect();
+    pack_names.sort();
+    let ids: Vec<u32> = packs.iter().map(|pack| pack_names.binary_search(&idx_name(pack)).unwrap() as u32).collect();
+
+    let mut objects: Vec<([u8; HASH_SIZE], u32, u64)> = vec![];
+    for (pack, id) in packs.iter().zip(&ids) {
+        objects.extend(pack.index.names.iter().zip(&pack.index.offsets).map(|(name, offset)| (*name, *id, *offset)));
+    }
+    // Stable, so the newest pack stays first among duplicates
+    objects.sort_by_key(|(name, _, _)| *name);
+    objects.dedup_by_key(|(name, _, _)| *name);
+
+    let mut names_chunk = vec![];
+    for name in &pack_names {
+        names_chunk.extend_from_slice(name.as_bytes());
+        names_chunk.push(0);
+    }
+    while names_chunk.len() % 4 != 0 { names_chunk.push(0); }
+
+    let mut fanout_chunk = vec![];
+    let mut count = 0;
+    for first in 0..=255u8 {
+        count += objects.iter().filter(|(name, _, _)| name[0] == first).count();
+        fanout_chunk.write_u32::<BigEndian>(count as u32).unwrap();
+    }
+
+    let oid_chunk: Vec<u8> = objects.iter().flat_map(|(name, _, _)| name.to_vec()).collect();
+
+    let mut offsets_chunk = vec![];
+    let mut large_chunk = vec![];
+    for (_, id, offset) in &objects {
+        offsets_chunk.write_u32::<BigEndian>(*id).unwrap();
+        if *offset < LARGE_OFFSET as u64 {
+            offsets_chunk.write_u32::<BigEndian>(*offset as u32).unwrap();
+        } else {
+            offsets_chunk.write_u32::<BigEndian>(LARGE_OFFSET | (large_chunk.len() / 8) as u32).unwrap();
+            large_chunk.write_u64::<BigEndian>(*offset).unwrap();
+        }
+    }
+
+    let mut chunks = vec![
+        (CHUNK_PACK_NAMES, names_chunk),
+        (CHUNK_FANOUT, fanout_chunk),
+        (CHUNK_NAMES, oid_chunk),
+        (CHUNK_OFFSETS, offsets_chunk),
+    ];
+    if !large_chunk.is_empty() { chunks.push((CHUNK_LARGE_OFFSETS, large_chunk)); }
+
+    let mut data = vec![];
+    data.extend_from_slice(MIDX_SIGNATURE);
+    data.extend_from_slice(&[MIDX_VERSION, HASH_VERSION, chunks.len() as u8, 0]);
+    data.write_u32::<BigEndian>(pack_names.len() as u32).unwrap();
+
+    let mut offset = data.len() + (chunks.len() + 1) * 12;
+    for (id, chunk) in &chunks {
+        data.write_u32::<BigEndian>(*id).unwrap();
+        data.write_u64::<BigEndian>(offset as u64).unwrap();
+        offset += chunk.len();
+    }
+    data.write_u32::<BigEndian>(0).unwrap();
+    data.write_u64::<BigEndian>(offset as u64).unwrap();
+
+    for (_, chunk) in chunks { data.extend(chunk); }
+    let checksum = Sha256::digest(&data);
+    data.extend_from_slice(&checksum);
+
+    data
+}
+
+// Problems found comparing an index with the packs it covers: its checksum, its order, and every
+// object of those packs being listed at the right offset
+pub fn verify(data: &[u8], packs: &[Pack]) -> Vec<String> {
+    let (body, trailer) = data.split_at(data.len().saturating_sub(HASH_SIZE));
+    if Sha256::digest(body)[..] != *trailer { return vec!["incorrect checksum".to_string()]; }
+
+    let midx =
+        match MultiPackIndex::parse(data) {
+            Ok(midx) => midx,
+            Err(error) => return vec![error.to_string()],
+        };
+
+    let mut problems = vec![];
+    if midx.names.windows(2).any(|pair| pair[0] >= pair[1]) { problems.push("object names are not sorted".to_string()); }
+
+    let covered: Vec<Option<&Pack>> =
+        midx.pack_names.iter().map(|name| packs.iter().find(|pack| idx_name(pack) == *name)).collect();
+    for (name, pack) in midx.pack_names.iter().zip(&covered) {
+        if pack.is_none() { problems.push(format!("failed to load pack '{}'", name)); }
+    }
+
+    for (name, (id, offset)) in midx.names.iter().zip(&midx.locations) {
+        let Some(pack) = covered[*id as usize] else { continue };
+        let name = hash::to_hex(name);
+        if pack.index.find(&name) != Some(*offset) {
+            problems.push(format!("incorrect object offset for {} in {}", name, idx_name(pack)));
+        }
+    }
+
+    for pack in covered.into_iter().flatten() {
+        for name in &pack.index.names {
+            let name = hash::to_hex(name);
+            if midx.find(&name).is_none() { problems.push(format!("object {} of {} is missing", name, idx_name(pack))); }
+        }
+    }
+
+    problems
+}
+
+// "pack-<checksum>.idx", how the index names the packs it covers
+pub fn idx_name(pack: &Pack) -> String {
+    pack.path.with_extension("idx").file_name().unwrap().to_string_lossy().to_string()
+}
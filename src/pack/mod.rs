@@ -0,0 +1,338 @@
+use std::fs;
+use std::io::{self, Read};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use flate2::read::ZlibDecoder;
+
+use crate::pack::foreign::ForeignPack;
+use crate::pack::index::{invalid, PackIndex, HASH_SIZE, SHA1_SIZE};
+use crate::pack::midx::{MultiPackIndex, MIDX_FILE};
+use crate::structs::GitObjectType;
+use crate::utils::hash;
+
+pub mod bitmap;
+pub mod delta;
+pub mod ewah;
+pub mod foreign;
+pub mod index;
+pub mod ingest;
+pub mod midx;
+pub mod write;
+
+pub const PACK_SIGNATURE: &[u8; 4] = b"PACK";
+pub const PACK_VERSION: u32 = 2;
+
+// Entry types of the pack format
+pub const OBJ_COMMIT: u8 = 1;
+pub const OBJ_TREE: u8 = 2;
+pub const OBJ_BLOB: u8 = 3;
+pub const OBJ_TAG: u8 = 4;
+pub const OBJ_OFS_DELTA: u8 = 6;
+pub const OBJ_REF_DELTA: u8 = 7;
+
+// Longer chains mean a corrupt pack with a delta loop
+const MAX_DELTA_CHAIN: usize = 10_000;
+
+// Object content read from a pack, with the type of the object at the bottom of its delta chain
+pub type PackedObject = (GitObjectType, Vec<u8>);
+
+// A `.pack` file and its `.idx`. The pack is read into memory once, `path` is the `.pack` one.
+// Object names have N bytes
+pub struct Pack<const N: usize = HASH_SIZE> {
+    pub path: PathBuf,
+    pub data: Vec<u8>,
+    pub index: PackIndex<N>,
+}
+
+// The packs of a repository, newest first. Objects of the packs a multi-pack-index covers are
+// found with a single lookup in it, the other packs are searched one after the other. Packs
+// written by Git are searched last and are not part of the slice maintenance works on
+pub struct PackSet {
+    packs: Vec<Pack>,
+    // The index and, for each pack it names, the position of that pack in `packs`
+    midx: Option<(MultiPackIndex, Vec<usize>)>,
+    uncovered: Vec<usize>,
+    foreign: Vec<ForeignPack>,
+}
+
+// What a delta is applied to: an entry earlier in the same pack or an object named by its hash
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeltaBase {
+    Offset(u64),
+    Name(String),
+}
+
+#[derive(Debug)]
+pub struct EntryHeader {
+    pub kind: u8,
+    // Size of the inflated data, for deltas the size of the delta itself
+    pub size: usize,
+    pub base: Option<DeltaBase>,
+    // Where the zlib stream starts
+    pub data_offset: usize,
+}
+
+impl<const N: usize> Pack<N> {
+    // Opens "pack-<checksum>.pack" next to its already read "pack-<checksum>.idx"
+    pub fn open(idx_path: &Path, index: PackIndex<N>) -> io::Result<Self> {
+        let path = idx_path.with_extension("pack");
+        let data = fs::read(&path)?;
+
+        if data.len() < 12 + N || &data[..4] != PACK_SIGNATURE {
+            return Err(invalid("Not a pack file"));
+        }
+        let version = u32::from_be_bytes(data[4..8].try_into().unwrap());
+        if version != PACK_VERSION && version != 3 { return Err(invalid("Unsupported pack version")); }
+
+        let count = u32::from_be_bytes(data[8..12].try_into().unwrap());
+        if count as usize != index.len() { return Err(invalid("Pack and index object counts differ")); }
+        if data[data.len() - N..] != index.pack_checksum { return Err(invalid("Pack does not match its index")); }
+
+        Ok(Self { path, data, index })
+    }
+
+    // rinit names objects by the hash of their content alone, Git by the hash of a "<type> <size>\0"
+    // header and the content. The first entry, whole unless the pack was completed from a thin one,
+    // tells which the pack follows
+    fn named_by_git(&self) -> io::Result<bool> {
+        let Some(position) = self.index.offsets.iter().position(|offset| *offset == 12) else { return Ok(false) };
+        let header = self.entry_header(12)?;
+        if header.base.is_some() { return Ok(false); }
+
+        let content = self.inflate(header.data_offset, header.size)?;
+        let name = hash::to_hex(&self.index.names[position]);
+        if hash::from_bytes(&content) == name { return Ok(false); }
+        if foreign::object_name(object_type(header.kind)?, &content, N) == name { return Ok(true); }
+
+        Err(invalid("Pack entry does not match its name"))
+    }
+
+    pub fn contains(&self, object: &str) -> bool {
+        self.index.position(object).is_some()
+    }
+
+    // Follows the delta chain down to a whole object, then applies the deltas back up. REF_DELTA
+    // bases missing from this pack come from `external`
+    pub fn read_at(&self, offset: u64, external: &dyn Fn(&str) -> Option<PackedObject>) -> io::Result<PackedObject> {
+        let mut deltas = vec![];
+        let mut offset = offset;
+
+        let (kind, mut content) =
+            loop {
+                if deltas.len() > MAX_DELTA_CHAIN { return Err(invalid("Delta chain is too long")); }
+
+                let header = self.entry_header(offset)?;
+                let data = self.inflate(header.data_offset, header.size)?;
+
+                match header.base {
+                    None => break (object_type(header.kind)?, data),
+                    Some(DeltaBase::Offset(base)) => {
+                        deltas.push(data);
+                        offset = base;
+                    }
+                    Some(DeltaBase::Name(name)) => {
+                        deltas.push(data);
+                        match self.index.find(&name) {
+                            Some(base) => offset = base,
+                            None => break external(&name).ok_or_else(|| invalid(&format!("Missing delta base {}", name)))?,
+                        }
+                    }
+                }
+            };
+
+        for delta in deltas.iter().rev() {
+            content = delta::apply(&content, delta)?;
+        }
+
+        Ok((kind, content))
+    }
+
+    pub fn entry_header(&self, offset: u64) -> io::Result<EntryHeader> {
+        parse_entry_header(&self.data, offset, N)
+    }
+
+    pub fn inflate(&self, start: usize, size: usize) -> io::Result<Vec<u8>> {
+        let input = self.data.get(start..).ok_or_else(|| invalid("Pack entry out of bounds"))?;
+        let mut output = Vec::with_capacity(size);
+        ZlibDecoder::new(input).take(size as u64 + 1).read_to_end(&mut output)?;
+
+        if output.len() != size { return Err(invalid("Pack entry size does not match")); }
+
+        Ok(output)
+    }
+}
+
+// Type and size as a base-128 number with the type in bits 4-6 of the first byte, followed by the
+// negative base offset of an OFS_DELTA or the base name of a REF_DELTA, `hash_size` bytes long
+pub fn parse_entry_header(data: &[u8], offset: u64, hash_size: usize) -> io::Result<EntryHeader> {
+    let mut position = offset as usize;
+    let mut next = || {
+        let byte = *data.get(position).ok_or_else(|| invalid("Truncated pack entry"))?;
+        position += 1;
+        Ok::<u8, io::Error>(byte)
+    };
+
+    let mut byte = next()?;
+    let kind = (byte >> 4) & 0x7;
+    let mut size = (byte & 0x0f) as usize;
+    let mut shift = 4;
+
+    while byte & 0x80 != 0 {
+        byte = next()?;
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+    }
+
+    let base =
+        match kind {
+            OBJ_OFS_DELTA => {
+                byte = next()?;
+                let mut distance = (byte & 0x7f) as u64;
+                while byte & 0x80 != 0 {
+                    byte = next()?;
+                    distance = ((distance + 1) << 7) | (byte & 0x7f) as u64;
+                }
+
+                if distance == 0 || distance > offset { return Err(invalid("Delta base offset out of bounds")); }
+                Some(DeltaBase::Offset(offset - distance))
+            }
+            OBJ_REF_DELTA => {
+                let name = data.get(position..position + hash_size).ok_or_else(|| invalid("Truncated pack entry"))?;
+                position += hash_size;
+                Some(DeltaBase::Name(hash::to_hex(name)))
+            }
+            _ => None,
+        };
+
+    Ok(EntryHeader { kind, size, base, data_offset: position })
+}
+
+impl PackSet {
+    pub fn load(dir: &Path) -> Self {
+        let (packs, foreign) = load_all(dir);
+        let midx = load_midx(dir, &packs);
+        let uncovered =
+            (0..packs.len())
+                .filter(|i| !midx.as_ref().is_some_and(|(_, covered)| covered.contains(i)))
+                .collect();
+
+        Self { packs, midx, uncovered, foreign }
+    }
+
+    // The pack holding an object and where in it
+    pub fn find(&self, object: &str) -> Option<(&Pack, u64)> {
+        if let Some((midx, covered)) = &self.midx {
+            if let Some((id, offset)) = midx.find(object) { return Some((&self.packs[covered[id]], offset)); }
+        }
+
+        self.uncovered.iter().map(|&i| &self.packs[i]).find_map(|pack| pack.index.find(object).map(|offset| (pack, offset)))
+    }
+
+    pub fn contains(&self, object: &str) -> bool {
+        self.find(object).is_some() || self.is_foreign(object)
+    }
+
+    // Objects only packs written by Git have, they can't go into packs of ours
+    pub fn is_foreign(&self, object: &str) -> bool {
+        self.find(object).is_none() && self.foreign.iter().any(|pack| pack.contains(object))
+    }
+
+    pub fn foreign(&self) -> &[ForeignPack] {
+        &self.foreign
+    }
+
+    pub fn read(&self, object: &str, external: &dyn Fn(&str) -> Option<PackedObject>) -> Option<io::Result<PackedObject>> {
+        match self.find(object) {
+            Some((pack, offset)) => Some(pack.read_at(offset, external)),
+            None => self.foreign.iter().find_map(|pack| pack.read(object)),
+        }
+    }
+
+    pub fn find_prefix(&self, prefix: &str) -> Vec<String> {
+        let covered = self.midx.iter().flat_map(|(midx, _)| midx.find_prefix(prefix));
+        let foreign = self.foreign.iter().flat_map(|pack| pack.find_prefix(prefix));
+
+        covered.chain(self.uncovered.iter().flat_map(|&i| self.packs[i].index.find_prefix(prefix))).chain(foreign).collect()
+    }
+}
+
+impl Deref for PackSet {
+    type Target = [Pack];
+
+    fn deref(&self) -> &[Pack] {
+        &self.packs
+    }
+}
+
+// The multi-pack-index of the directory, unless it names a pack that is gone
+fn load_midx(dir: &Path, packs: &[Pack]) -> Option<(MultiPackIndex, Vec<usize>)> {
+    let path = dir.join(MIDX_FILE);
+    if !path.is_file() { return None; }
+
+    let midx =
+        match MultiPackIndex::from_path(&path) {
+            Ok(midx) => midx,
+            Err(error) => {
+                eprintln!("warning: ignoring multi-pack-index: {}", error);
+                return None;
+            }
+        };
+
+    let covered: Option<Vec<usize>> =
+        midx.pack_names
+            .iter()
+            .map(|name| packs.iter().position(|pack| midx::idx_name(pack) == *name))
+            .collect();
+
+    covered.map(|covered| (midx, covered))
+}
+
+pub fn object_type(kind: u8) -> io::Result<GitObjectType> {
+    match kind {
+        OBJ_COMMIT => Ok(GitObjectType::Commit),
+        OBJ_TREE => Ok(GitObjectType::Tree),
+        OBJ_BLOB => Ok(GitObjectType::Blob),
+        OBJ_TAG => Err(invalid("Tag objects are not supported")),
+        _ => Err(invalid(&format!("Unknown pack entry type {}", kind))),
+    }
+}
+
+// Every pack of a directory that has an index, newest first, apart from those written by Git.
+// Broken packs are skipped with a warning
+fn load_all(dir: &Path) -> (Vec<Pack>, Vec<ForeignPack>) {
+    let Ok(entries) = fs::read_dir(dir) else { return (vec![], vec![]) };
+
+    let mut indexes: Vec<(std::time::SystemTime, PathBuf)> =
+        entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "idx"))
+            .map(|path| (fs::metadata(&path).and_then(|meta| meta.modified()).unwrap_or(std::time::UNIX_EPOCH), path))
+            .collect();
+    indexes.sort_by(|a, b| b.cmp(a));
+
+    let (mut packs, mut foreign) = (vec![], vec![]);
+    for (_, path) in indexes {
+        match open(&path) {
+            Ok(Ok(pack)) => packs.push(pack),
+            Ok(Err(pack)) => foreign.push(pack),
+            Err(error) => eprintln!("warning: ignoring pack {}: {}", path.display(), error),
+        }
+    }
+
+    (packs, foreign)
+}
+
+// A pack of ours, or one written by Git
+fn open(idx_path: &Path) -> io::Result<Result<Pack, ForeignPack>> {
+    let data = fs::read(idx_path)?;
+    if index::name_size(&data) == SHA1_SIZE {
+        return Ok(Err(ForeignPack::Sha1(Pack::open(idx_path, PackIndex::parse(&data)?)?)));
+    }
+
+    let pack = Pack::open(idx_path, PackIndex::parse(&data)?)?;
+    match pack.named_by_git()? {
+        true => Ok(Err(ForeignPack::Sha256(pack))),
+        false => Ok(Ok(pack)),
+    }
+}
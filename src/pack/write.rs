@@ -0,0 +1,296 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use flate2::write::ZlibEncoder;
+use flate2::{Compression, Crc};
+use sha2::{Digest, Sha256};
+
+use crate::pack::index::{self, IndexEntry, HASH_SIZE};
+use crate::pack::{delta, DeltaBase, Pack, OBJ_BLOB, OBJ_COMMIT, OBJ_OFS_DELTA, OBJ_TREE, PACK_SIGNATURE, PACK_VERSION};
+use crate::storage::Storage;
+use crate::structs::GitObjectType;
+use crate::utils::hash;
+use crate::utils::process::die;
+
+// An object to pack, with the path it was found at for the name heuristic
+pub struct PackObject {
+    pub hash: String,
+    pub object_type: GitObjectType,
+    pub content: Vec<u8>,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PackOptions {
+    // How many of the previous objects in size order are tried as delta bases
+    pub window: usize,
+    // Longest chain of deltas leading to an object
+    pub depth: usize,
+    // Deltas already stored in existing packs are kept when their base is packed too
+    pub reuse_deltas: bool,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        Self { window: 10, depth: 50, reuse_deltas: true }
+    }
+}
+
+pub struct WrittenPack {
+    pub data: Vec<u8>,
+    pub entries: Vec<IndexEntry>,
+    pub checksum: [u8; HASH_SIZE],
+    // Objects stored as deltas
+    pub deltas: usize,
+    pub reused: usize,
+}
+
+// Reads the objects to pack, `list` pairs object names with the paths they were found at
+pub fn collect(storage: &Storage, list: &[(String, String)]) -> Vec<PackObject> {
+    let mut seen = HashSet::new();
+
+    list.iter()
+        .filter(|(hash, _)| seen.insert(hash.clone()))
+        .map(|(hash, name)| {
+            if storage.packs().is_foreign(hash) { die(format!("object {} is in a pack written by Git and can't be repacked", hash)); }
+
+            let (object_type, content) = storage.read_raw(hash).unwrap_or_else(|| panic!("object {} not found", hash));
+            PackObject { hash: hash.clone(), object_type, content, name: name.clone() }
+        })
+        .collect()
+}
+
+// Lays objects out as a pack: similar objects become deltas against each other, and every delta
+// is written after its base so it can point back at it by offset
+pub fn build(storage: &Storage, objects: &[PackObject], options: PackOptions) -> WrittenPack {
+    let positions: HashMap<&str, usize> = objects.iter().enumerate().map(|(i, object)| (object.hash.as_str(), i)).collect();
+    let mut bases: Vec<Option<(usize, Vec<u8>)>> = vec![None; objects.len()];
+
+    let mut reused = vec![false; objects.len()];
+    if options.reuse_deltas {
+        for (hash, (base, delta)) in stored_deltas(storage, &positions) {
+            let (object, base) = (positions[hash.as_str()], positions[base.as_str()]);
+            // Packs made at different times may store two objects as deltas of each other
+            if chain_contains(&bases, base, object) { continue; }
+
+            bases[object] = Some((base, delta));
+            reused[object] = true;
+        }
+    }
+
+    // Same type, similar names and decreasing sizes end up next to each other, bigger objects make
+    // better bases
+    let mut order: Vec<usize> = (0..objects.len()).collect();
+    order.sort_by_key(|&i| {
+        let object = &objects[i];
+        (type_code(object.object_type), name_hash(&object.name), std::cmp::Reverse(object.content.len()))
+    });
+
+    for (k, &i) in order.iter().enumerate() {
+        if bases[i].is_some() { continue; }
+
+        let target = &objects[i];
+        let mut best: Option<(usize, Vec<u8>)> = None;
+        for &j in order[k.saturating_sub(options.window)..k].iter().rev() {
+            let base = &objects[j];
+            if base.object_type != target.object_type || chain_depth(&bases, j) + 1 > options.depth { continue; }
+            // Sizes too far apart never make a useful delta
+            if base.content.len() / 32 > target.content.len() || target.content.len() / 32 > base.content.len() { continue; }
+            if chain_contains(&bases, j, i) { continue; }
+
+            let max_size =
+                match &best {
+                    Some((_, delta)) => delta.len().saturating_sub(1),
+                    None => (target.content.len() / 2).saturating_sub(HASH_SIZE),
+                };
+            if let Some(delta) = delta::create(&base.content, &target.content, max_size) {
+                best = Some((j, delta));
+            }
+        }
+
+        bases[i] = best;
+    }
+
+    // Bases go first. Reused deltas can still make chains too deep, those objects are stored whole
+    let mut written = vec![false; objects.len()];
+    let mut depths = vec![0; objects.len()];
+    let mut layout = vec![];
+    for &i in &order {
+        let mut chain = vec![i];
+        while let Some((base, _)) = &bases[*chain.last().unwrap()] {
+            if written[*base] { break; }
+            chain.push(*base);
+        }
+
+        for &object in chain.iter().rev() {
+            if written[object] { continue; }
+            if let Some((base, _)) = &bases[object] {
+                depths[object] = depths[*base] + 1;
+                if depths[object] > options.depth {
+                    bases[object] = None;
+                    depths[object] = 0;
+                }
+            }
+            written[object] = true;
+            layout.push(object);
+        }
+    }
+
+    let mut data = vec![];
+    data.extend_from_slice(PACK_SIGNATURE);
+    data.extend_from_slice(&PACK_VERSION.to_be_bytes());
+    data.extend_from_slice(&(objects.len() as u32).to_be_bytes());
+
+    let mut offsets = vec![0u64; objects.len()];
+    let mut entries = vec![];
+    for i in layout {
+        let offset = data.len() as u64;
+        offsets[i] = offset;
+
+        let mut entry =
+            match &bases[i] {
+                Some((base, delta)) => {
+                    let mut header = entry_header(OBJ_OFS_DELTA, delta.len());
+                    header.extend(encode_distance(offset - offsets[*base]));
+                    header.extend(compress(delta));
+                    header
+                }
+                None => {
+                    let object = &objects[i];
+                    let mut header = entry_header(type_code(object.object_type), object.content.len());
+                    header.extend(compress(&object.content));
+                    header
+                }
+            };
+
+        let mut crc = Crc::new();
+        crc.update(&entry);
+        entries.push(IndexEntry { name: hash::to_bytes(&objects[i].hash).unwrap(), offset, crc: crc.sum() });
+        data.append(&mut entry);
+    }
+
+    let checksum: [u8; HASH_SIZE] = Sha256::digest(&data).into();
+    data.extend_from_slice(&checksum);
+    let deltas = bases.iter().filter(|base| base.is_some()).count();
+    let reused = (0..objects.len()).filter(|&i| reused[i] && bases[i].is_some()).count();
+
+    WrittenPack { data, entries, checksum, deltas, reused }
+}
+
+// Writes "<prefix>-<checksum>.pack" and its index, the index last so readers never see a pack
+// without its data. Returns the checksum in hex
+pub fn write_files(prefix: &Path, pack: &WrittenPack) -> String {
+    let checksum = hash::to_hex(&pack.checksum);
+    let base = format!("{}-{}", prefix.display(), checksum);
+
+    fs::write(format!("{}.pack", base), &pack.data).expect("Can't write pack");
+    fs::write(format!("{}.idx", base), index::write(&pack.entries, &pack.checksum)).expect("Can't write pack index");
+
+    checksum
+}
+
+// Deltas of existing packs whose base is one of the objects being packed
+fn stored_deltas(storage: &Storage, positions: &HashMap<&str, usize>) -> HashMap<String, (String, Vec<u8>)> {
+    let mut found = HashMap::new();
+
+    for pack in storage.packs().iter() {
+        let names = offset_names(pack);
+
+        for (offset, hash) in &names {
+            if !positions.contains_key(hash.as_str()) || found.contains_key(hash) { continue; }
+            let Ok(header) = pack.entry_header(*offset) else { continue };
+
+            let base =
+                match header.base {
+                    Some(DeltaBase::Offset(base)) => names.get(&base).cloned(),
+                    Some(DeltaBase::Name(name)) => Some(name),
+                    None => None,
+                };
+            let Some(base) = base.filter(|base| positions.contains_key(base.as_str())) else { continue };
+
+            if let Ok(delta) = pack.inflate(header.data_offset, header.size) {
+                found.insert(hash.clone(), (base, delta));
+            }
+        }
+    }
+
+    found
+}
+
+fn offset_names(pack: &Pack) -> HashMap<u64, String> {
+    pack.index.offsets.iter().zip(&pack.index.names).map(|(offset, name)| (*offset, hash::to_hex(name))).collect()
+}
+
+fn chain_depth(bases: &[Option<(usize, Vec<u8>)>], mut object: usize) -> usize {
+    let mut depth = 0;
+    while let Some((base, _)) = &bases[object] {
+        depth += 1;
+        object = *base;
+    }
+
+    depth
+}
+
+// Whether `object` is `start` or one of its bases, a delta against it would make a loop
+fn chain_contains(bases: &[Option<(usize, Vec<u8>)>], start: usize, object: usize) -> bool {
+    let mut current = Some(start);
+    while let Some(index) = current {
+        if index == object { return true; }
+        current = bases[index].as_ref().map(|(base, _)| *base);
+    }
+
+    false
+}
+
+pub fn type_code(object_type: GitObjectType) -> u8 {
+    match object_type {
+        GitObjectType::Commit => OBJ_COMMIT,
+        GitObjectType::Tree => OBJ_TREE,
+        GitObjectType::Blob => OBJ_BLOB,
+    }
+}
+
+// Git's name hash: the last characters of a path weigh the most, so files with the same name or
+// extension sort together
+fn name_hash(name: &str) -> u32 {
+    name.bytes()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .fold(0u32, |hash, byte| (hash >> 2).wrapping_add((byte as u32) << 24))
+}
+
+// Type in bits 4-6 of the first byte, the size in the low 4 bits and then 7 bits per byte
+pub fn entry_header(kind: u8, size: usize) -> Vec<u8> {
+    let mut header = vec![(kind << 4) | (size & 0x0f) as u8];
+    let mut rest = size >> 4;
+
+    while rest > 0 {
+        *header.last_mut().unwrap() |= 0x80;
+        header.push((rest & 0x7f) as u8);
+        rest >>= 7;
+    }
+
+    header
+}
+
+// Big-endian base-128 with an offset of one added at each continuation
+fn encode_distance(mut distance: u64) -> Vec<u8> {
+    let mut bytes = vec![(distance & 0x7f) as u8];
+    distance >>= 7;
+
+    while distance > 0 {
+        distance -= 1;
+        bytes.push(0x80 | (distance & 0x7f) as u8);
+        distance >>= 7;
+    }
+    bytes.reverse();
+
+    bytes
+}
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+
+    encoder.finish().unwrap()
+}
@@ -0,0 +1,20 @@
+use crate::utils::wildmatch;
+
+// A pathspec selects the path itself and everything below it, wildcards may cross directories
+pub fn matches(spec: &str, path: &str) -> bool {
+    let spec = spec.trim_start_matches("./").trim_end_matches('/');
+
+    if spec.is_empty() || spec == "." { return true; }
+    if path == spec || path.starts_with(&format!("{}/", spec)) { return true; }
+
+    has_wildcards(spec) && wildmatch::matches_across(spec, path)
+}
+
+// An empty list of pathspecs selects everything
+pub fn matches_any(specs: &[String], path: &str) -> bool {
+    specs.is_empty() || specs.iter().any(|spec| matches(spec, path))
+}
+
+pub fn has_wildcards(spec: &str) -> bool {
+    spec.contains(['*', '?', '['])
+}
@@ -0,0 +1,324 @@
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::rc::Rc;
+use crate::commit_graph::CommitGraphFile;
+use crate::pack::bitmap::PackBitmap;
+use crate::pack::ewah::Bitmap;
+use crate::storage::Storage;
+use crate::structs::GitObjectType;
+
+const ONE: u8 = 1;
+const OTHERS: u8 = 2;
+const STALE: u8 = 4;
+
+// Parents, dates and generation numbers of the commits queries touch, each commit is read once.
+// They are kept from one query to the next, so a command asks all its questions of one graph.
+// Root commits have generation 1, others one more than their highest parent, so a commit can only
+// reach commits of a lower generation and walks stop as soon as they go below their target.
+// The commit-graph file provides all of this without reading commit objects
+pub struct CommitGraph<'a> {
+    storage: &'a Storage,
+    graph: Option<Rc<CommitGraphFile>>,
+    commits: HashMap<String, Node>,
+}
+
+struct Node {
+    parents: Vec<String>,
+    time: i64,
+    generation: Option<u32>,
+}
+
+impl<'a> CommitGraph<'a> {
+    pub fn new(storage: &'a Storage) -> Self {
+        Self { storage, graph: storage.commit_graph(), commits: HashMap::new() }
+    }
+
+    // From the commit-graph file when it has the commit, otherwise from the commit object
+    fn node(&mut self, hash: &str) -> &Node {
+        if !self.commits.contains_key(hash) {
+            let node =
+                match self.graph.as_ref().and_then(|graph| graph.get(hash)) {
+                    Some(commit) => Node { parents: commit.parents, time: commit.time, generation: Some(commit.generation) },
+                    None => {
+                        let commit = self.storage.read_commit(hash);
+                        Node { parents: commit.parents, time: commit.committer.time, generation: None }
+                    }
+                };
+            self.commits.insert(hash.to_string(), node);
+        }
+
+        &self.commits[hash]
+    }
+
+    pub fn parents(&mut self, hash: &str) -> Vec<String> {
+        self.node(hash).parents.clone()
+    }
+
+    pub fn time(&mut self, hash: &str) -> i64 {
+        self.node(hash).time
+    }
+
+    // Computed bottom-up without recursion, histories can be deep
+    pub fn generation(&mut self, hash: &str) -> u32 {
+        let mut stack = vec![hash.to_string()];
+
+        while let Some(top) = stack.last().cloned() {
+            if self.node(&top).generation.is_some() {
+                stack.pop();
+                continue;
+            }
+
+            let parents = self.parents(&top);
+            let missing: Vec<String> =
+                parents.iter().filter(|parent| self.node(parent).generation.is_none()).cloned().collect();
+            if !missing.is_empty() {
+                stack.extend(missing);
+                continue;
+            }
+
+            let generation = parents.iter().map(|parent| self.commits[parent].generation.unwrap()).max().unwrap_or(0) + 1;
+            self.commits.get_mut(&top).unwrap().generation = Some(generation);
+            stack.pop();
+        }
+
+        self.commits[hash].generation.unwrap()
+    }
+
+    // True when `ancestor` can be reached from `descendant` by following parents, commits reach themselves
+    pub fn is_ancestor(&mut self, ancestor: &str, descendant: &str) -> bool {
+        let minimum = self.generation(ancestor);
+        let mut seen = HashSet::new();
+        let mut stack = vec![descendant.to_string()];
+
+        while let Some(hash) = stack.pop() {
+            if hash == ancestor { return true; }
+            if self.generation(&hash) <= minimum || !seen.insert(hash.clone()) { continue; }
+
+            stack.extend(self.parents(&hash));
+        }
+
+        false
+    }
+
+    // Best common ancestors of `one` and any of `others`: common ancestors that are not ancestors of
+    // another one. Both histories are painted from the highest generation down, commits reached from
+    // both sides are candidates and stop the painting of their own ancestors
+    pub fn merge_bases(&mut self, one: &str, others: &[String]) -> Vec<String> {
+        if others.iter().any(|other| other == one) { return vec![one.to_string()]; }
+
+        let mut flags: HashMap<String, u8> = HashMap::new();
+        let mut queue: BinaryHeap<(u32, i64, String)> = BinaryHeap::new();
+        for (hash, flag) in std::iter::once((one, ONE)).chain(others.iter().map(|other| (other.as_str(), OTHERS))) {
+            *flags.entry(hash.to_string()).or_default() |= flag;
+            queue.push((self.generation(hash), self.time(hash), hash.to_string()));
+        }
+
+        let mut candidates = vec![];
+        while queue.iter().any(|(_, _, hash)| flags[hash] & STALE == 0) {
+            let Some((_, _, hash)) = queue.pop() else { break };
+            let mut flag = flags[&hash];
+
+            if flag & (ONE | OTHERS) == ONE | OTHERS && flag & STALE == 0 {
+                candidates.push(hash.clone());
+                flag |= STALE;
+                flags.insert(hash.clone(), flag);
+            }
+
+            for parent in self.parents(&hash) {
+                let previous = flags.get(&parent).copied().unwrap_or(0);
+                if previous | flag != previous {
+                    flags.insert(parent.clone(), previous | flag);
+                    queue.push((self.generation(&parent), self.time(&parent), parent));
+                }
+            }
+        }
+
+        self.independent(&candidates)
+    }
+
+    // Common ancestors of all the commits, merged pairwise like `git merge-base --octopus`
+    pub fn octopus_bases(&mut self, commits: &[String]) -> Vec<String> {
+        let Some((first, rest)) = commits.split_first() else { return vec![] };
+        let mut bases = vec![first.clone()];
+
+        for next in rest {
+            let mut merged: Vec<String> = vec![];
+            for base in &bases {
+                for found in self.merge_bases(base, std::slice::from_ref(next)) {
+                    if !merged.contains(&found) { merged.push(found); }
+                }
+            }
+            bases = merged;
+        }
+
+        bases
+    }
+
+    // Drops commits reachable from another one of the list, and duplicates
+    pub fn independent(&mut self, commits: &[String]) -> Vec<String> {
+        let mut unique: Vec<String> = vec![];
+        for commit in commits {
+            if !unique.contains(commit) { unique.push(commit.clone()); }
+        }
+
+        let mut result = vec![];
+        for commit in &unique {
+            let reachable = unique.iter().any(|other| other != commit && self.is_ancestor(commit, other));
+            if !reachable { result.push(commit.clone()); }
+        }
+
+        result
+    }
+}
+
+// Objects reachable from `include` but not from `exclude`. With `use_bitmaps`, the history covered
+// by a pack bitmap is read from it instead of walked, and the objects found there come without paths
+pub fn objects_between(storage: &Storage, include: &[String], exclude: &[String], use_bitmaps: bool) -> Vec<(String, String)> {
+    let bitmap = if use_bitmaps { storage.packs().iter().find_map(|pack| PackBitmap::load(pack)?.ok()) } else { None };
+
+    match bitmap {
+        Some(bitmap) => {
+            let mut reached = Bitmap::new();
+            let mut seen = HashSet::new();
+            let mut outside = vec![];
+            bitmap_walk(storage, &bitmap, exclude, &mut reached, &mut seen, &mut outside);
+            let excluded = reached.clone();
+
+            outside.clear();
+            bitmap_walk(storage, &bitmap, include, &mut reached, &mut seen, &mut outside);
+            reached.and_not(&excluded);
+
+            reached.ones().map(|bit| (bitmap.objects[bit].clone(), String::new())).chain(outside).collect()
+        }
+        None => {
+            let mut seen: HashSet<String> = walk_objects(storage, exclude, &mut HashSet::new()).into_iter().map(|(hash, _)| hash).collect();
+            walk_objects(storage, include, &mut seen)
+        }
+    }
+}
+
+// Sets the bits of the pack objects reachable from `tips` and lists the other ones. Commits with a
+// bitmap are not walked any further
+fn bitmap_walk(
+    storage: &Storage,
+    bitmap: &PackBitmap,
+    tips: &[String],
+    reached: &mut Bitmap,
+    seen: &mut HashSet<String>,
+    outside: &mut Vec<(String, String)>,
+) {
+    // True the first time an object is met
+    let mut visit = |hash: &str, path: &str, reached: &mut Bitmap| -> bool {
+        match bitmap.position(hash) {
+            Some(bit) if reached.get(bit) => false,
+            Some(bit) => {
+                reached.set(bit);
+                true
+            }
+            None if seen.insert(hash.to_string()) => {
+                outside.push((hash.to_string(), path.to_string()));
+                true
+            }
+            None => false,
+        }
+    };
+
+    let mut trees = vec![];
+    let mut stack: Vec<String> = tips.to_vec();
+    while let Some(hash) = stack.pop() {
+        if let Some(commits) = bitmap.get(&hash) {
+            reached.or(commits);
+            continue;
+        }
+        if !visit(&hash, "", reached) { continue; }
+
+        let commit = storage.read_commit(&hash);
+        stack.extend(commit.parents);
+        trees.push((commit.tree, String::new()));
+    }
+
+    while let Some((tree, path)) = trees.pop() {
+        if !visit(&tree, &path, reached) { continue; }
+
+        for object_ref in storage.read_tree(&tree).refs {
+            let child = if path.is_empty() { object_ref.content.clone() } else { format!("{}/{}", path, object_ref.content) };
+
+            match object_ref.ref_type {
+                GitObjectType::Tree => trees.push((object_ref.hash, child)),
+                GitObjectType::Commit => {}
+                GitObjectType::Blob => { visit(&object_ref.hash, &child, reached); }
+            }
+        }
+    }
+}
+
+// Objects reachable from the tips that are not in `seen` yet: the commits first, then the trees and
+// blobs they point to, each with the path it was first seen at
+fn walk_objects(storage: &Storage, tips: &[String], seen: &mut HashSet<String>) -> Vec<(String, String)> {
+    let mut commits = vec![];
+    let mut stack: Vec<String> = tips.to_vec();
+
+    while let Some(hash) = stack.pop() {
+        if !seen.insert(hash.clone()) { continue; }
+
+        let commit = storage.read_commit(&hash);
+        stack.extend(commit.parents);
+        commits.push((hash, commit.tree));
+    }
+
+    let mut objects: Vec<(String, String)> = commits.iter().map(|(hash, _)| (hash.clone(), String::new())).collect();
+    for (_, tree) in commits {
+        tree_objects(storage, &tree, "", seen, &mut objects, None);
+    }
+
+    objects
+}
+
+// Trees and blobs below the `trees` of some commits, in order, leaving out everything below the
+// `hidden` trees. Given `missing`, objects absent from the repository are collected there instead
+pub fn commit_objects(storage: &Storage, trees: &[String], hidden: &[String], mut missing: Option<&mut Vec<String>>) -> Vec<(String, String)> {
+    let mut seen = HashSet::new();
+    let mut ignored = (vec![], vec![]);
+    for tree in hidden {
+        let check = missing.is_some().then_some(&mut ignored.1);
+        tree_objects(storage, tree, "", &mut seen, &mut ignored.0, check);
+    }
+
+    let mut objects = vec![];
+    for tree in trees {
+        tree_objects(storage, tree, "", &mut seen, &mut objects, missing.as_deref_mut());
+    }
+
+    objects
+}
+
+// A tree and everything below it that was not seen yet. Given `missing`, objects absent from the
+// repository are collected there instead of being read or listed
+pub fn tree_objects(
+    storage: &Storage,
+    tree: &str,
+    path: &str,
+    seen: &mut HashSet<String>,
+    objects: &mut Vec<(String, String)>,
+    mut missing: Option<&mut Vec<String>>,
+) {
+    if !seen.insert(tree.to_string()) { return; }
+    if let Some(missing) = missing.as_deref_mut() {
+        if !storage.object_exists(tree) { return missing.push(tree.to_string()); }
+    }
+    objects.push((tree.to_string(), path.to_string()));
+
+    for object_ref in storage.read_tree(tree).refs {
+        let child = if path.is_empty() { object_ref.content.clone() } else { format!("{}/{}", path, object_ref.content) };
+
+        match object_ref.ref_type {
+            GitObjectType::Tree => tree_objects(storage, &object_ref.hash, &child, seen, objects, missing.as_deref_mut()),
+            // Submodule commits live in another repository
+            GitObjectType::Commit => {}
+            GitObjectType::Blob if !seen.insert(object_ref.hash.clone()) => {}
+            GitObjectType::Blob => match missing.as_deref_mut() {
+                Some(missing) if !storage.object_exists(&object_ref.hash) => missing.push(object_ref.hash),
+                _ => objects.push((object_ref.hash, child)),
+            },
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use crate::storage::Storage;
+use crate::structs::GitObjectType;
+
+// Resolves revisions like `HEAD`, `master~2`, `a1b2c3d^2` into a commit hash
+pub fn resolve(storage: &Storage, rev: &str) -> Option<String> {
+    let split_at = rev.find(['^', '~']).unwrap_or(rev.len());
+    let (name, mut suffix) = rev.split_at(split_at);
+    let mut hash = storage.resolve_name(name)?;
+
+    while let Some(operator) = suffix.chars().next() {
+        let digits: String = suffix[1..].chars().take_while(|c| c.is_ascii_digit()).collect();
+        suffix = &suffix[1 + digits.len()..];
+        let number: usize = if digits.is_empty() { 1 } else { digits.parse().ok()? };
+
+        hash = match operator {
+            '^' if number == 0 => hash,
+            '^' => storage.read_commit(&hash).parents.get(number - 1)?.to_string(),
+            _ => {
+                for _ in 0..number {
+                    hash = storage.read_commit(&hash).parents.first()?.to_string();
+                }
+                hash
+            }
+        };
+    }
+
+    Some(hash)
+}
+
+// Peels a commit down to its tree, trees are returned as is
+pub fn resolve_tree(storage: &Storage, rev: &str) -> Option<String> {
+    let hash = resolve(storage, rev)?;
+
+    match storage.read_object(&hash).git_type() {
+        GitObjectType::Commit => Some(storage.read_commit(&hash).tree),
+        GitObjectType::Tree => Some(hash),
+        _ => None
+    }
+}
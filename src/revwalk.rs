@@ -0,0 +1,266 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::rc::Rc;
+use crate::commit_graph::CommitGraphFile;
+use crate::pathspec;
+use crate::storage::Storage;
+use crate::structs::git_commit::GitCommit;
+
+#[derive(Debug, Default)]
+pub struct RevWalkOptions {
+    pub first_parent: bool,
+    pub topo_order: bool,
+    pub reverse: bool,
+    pub max_count: Option<usize>,
+    // Committer dates, as unix timestamps, outside of which commits are skipped
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WalkEntry {
+    pub hash: String,
+    pub commit: GitCommit,
+}
+
+// What the walk needs of a commit: read from the commit-graph when it has the commit, parsed from
+// the object otherwise
+#[derive(Debug, Clone)]
+pub struct WalkCommit {
+    pub hash: String,
+    pub tree: String,
+    pub parents: Vec<String>,
+    pub time: i64,
+}
+
+struct QueueItem {
+    time: i64,
+    order: Reverse<usize>,
+    entry: WalkCommit,
+}
+
+// Walks commit history newest first, ordered by committer date
+pub struct RevWalk<'a> {
+    storage: &'a Storage,
+    graph: Option<Rc<CommitGraphFile>>,
+    options: RevWalkOptions,
+    queue: BinaryHeap<QueueItem>,
+    seen: HashSet<String>,
+    hidden: HashSet<String>,
+    counter: usize,
+}
+
+impl<'a> RevWalk<'a> {
+    pub fn new(storage: &'a Storage, options: RevWalkOptions) -> Self {
+        Self {
+            storage,
+            graph: storage.commit_graph(),
+            options,
+            queue: BinaryHeap::new(),
+            seen: HashSet::new(),
+            hidden: HashSet::new(),
+            counter: 0,
+        }
+    }
+
+    pub fn push(&mut self, hash: &str) {
+        if self.seen.insert(hash.to_string()) {
+            let commit = self.lookup(hash);
+            self.enqueue(commit);
+        }
+    }
+
+    // Excludes the commit and all of its ancestors, as in `^rev` or `rev..`
+    pub fn hide(&mut self, hash: &str) {
+        let mut stack = vec![hash.to_string()];
+
+        while let Some(hash) = stack.pop() {
+            if self.hidden.insert(hash.clone()) { stack.extend(self.lookup(&hash).parents); }
+        }
+    }
+
+    // The commits to show, with their commit objects parsed
+    pub fn run(self) -> Vec<WalkEntry> {
+        let storage = self.storage;
+
+        self.run_commits()
+            .into_iter()
+            .map(|commit| WalkEntry { commit: storage.read_commit(&commit.hash), hash: commit.hash })
+            .collect()
+    }
+
+    // The commits to show, only parsed when the commit-graph does not have them
+    pub fn run_commits(mut self) -> Vec<WalkCommit> {
+        let limit = self.options.max_count.unwrap_or(usize::MAX);
+        let mut entries = vec![];
+
+        if self.options.topo_order {
+            let mut all = vec![];
+            while let Some(entry) = self.next_by_date() { all.push(entry); }
+
+            for entry in topo_sort(all) {
+                if entries.len() >= limit { break; }
+                if self.is_interesting(&entry) { entries.push(entry); }
+            }
+        } else {
+            while entries.len() < limit {
+                match self.next_by_date() {
+                    Some(entry) if self.is_interesting(&entry) => entries.push(entry),
+                    Some(_) => continue,
+                    None => break,
+                }
+            }
+        }
+
+        if self.options.reverse { entries.reverse(); }
+
+        entries
+    }
+
+    fn lookup(&self, hash: &str) -> WalkCommit {
+        match self.graph.as_ref().and_then(|graph| graph.get(hash)) {
+            Some(commit) => WalkCommit { hash: hash.to_string(), tree: commit.tree, parents: commit.parents, time: commit.time },
+            None => {
+                let commit = self.storage.read_commit(hash);
+                WalkCommit { hash: hash.to_string(), tree: commit.tree, parents: commit.parents, time: commit.committer.time }
+            }
+        }
+    }
+
+    fn enqueue(&mut self, entry: WalkCommit) {
+        self.counter += 1;
+        self.queue.push(QueueItem {
+            time: entry.time,
+            order: Reverse(self.counter),
+            entry,
+        });
+    }
+
+    fn next_by_date(&mut self) -> Option<WalkCommit> {
+        loop {
+            let QueueItem { entry, .. } = self.queue.pop()?;
+
+            for parent in self.parents_of(&entry) {
+                self.push(&parent);
+            }
+
+            if !self.hidden.contains(&entry.hash) { return Some(entry); }
+        }
+    }
+
+    fn parents_of(&self, commit: &WalkCommit) -> Vec<String> {
+        self.followed_parents(commit)
+            .iter()
+            .filter(|p| !self.hidden.contains(*p))
+            .cloned()
+            .collect()
+    }
+
+    fn followed_parents<'c>(&self, commit: &'c WalkCommit) -> &'c [String] {
+        if self.options.first_parent {
+            &commit.parents[..commit.parents.len().min(1)]
+        } else {
+            &commit.parents
+        }
+    }
+
+    // Commits outside of the date range are not shown. With path limiting a commit is shown only if
+    // it is not TREESAME to any of its parents
+    fn is_interesting(&self, entry: &WalkCommit) -> bool {
+        let time = entry.time;
+        if self.options.since.is_some_and(|since| time < since) || self.options.until.is_some_and(|until| time > until) {
+            return false;
+        }
+        if self.options.paths.is_empty() { return true; }
+        if self.bloom_says_unchanged(entry) { return false; }
+
+        let own = self.limited_tree(&entry.tree);
+        let parents = self.followed_parents(entry);
+
+        if parents.is_empty() { return !own.is_empty(); }
+
+        parents.iter().all(|parent| self.limited_tree(&self.lookup(parent).tree) != own)
+    }
+
+    // The changed-path filters of the commit-graph answer for commits with a single parent when
+    // every pathspec is a plain path: a definite "not changed" makes the commit TREESAME
+    fn bloom_says_unchanged(&self, entry: &WalkCommit) -> bool {
+        let Some(graph) = &self.graph else { return false };
+        if entry.parents.len() != 1 { return false; }
+
+        self.options.paths.iter().all(|spec| {
+            let path = spec.trim_start_matches("./").trim_end_matches('/');
+            let literal = !path.is_empty() && path != "." && !pathspec::has_wildcards(path);
+
+            literal && graph.maybe_changed(&entry.hash, path) == Some(false)
+        })
+    }
+
+    fn limited_tree(&self, tree: &str) -> BTreeMap<String, String> {
+        self.storage
+            .read_tree_files(tree)
+            .into_iter()
+            .filter(|(path, _)| pathspec::matches_any(&self.options.paths, path))
+            .map(|(path, object_ref)| (path, format!("{:o} {}", object_ref.permissions, object_ref.hash)))
+            .collect()
+    }
+}
+
+// Kahn's algorithm: no parent is shown before all of its children, ties broken by date
+fn topo_sort(entries: Vec<WalkCommit>) -> Vec<WalkCommit> {
+    let mut children: HashMap<String, usize> = HashMap::new();
+    for entry in &entries {
+        for parent in &entry.parents {
+            *children.entry(parent.clone()).or_default() += 1;
+        }
+    }
+
+    let mut by_hash: HashMap<String, WalkCommit> = HashMap::new();
+    let mut ready = BinaryHeap::new();
+    for (order, entry) in entries.into_iter().enumerate() {
+        if !children.contains_key(&entry.hash) {
+            ready.push(QueueItem { time: entry.time, order: Reverse(order), entry: entry.clone() });
+        }
+        by_hash.insert(entry.hash.clone(), entry);
+    }
+
+    let mut sorted = vec![];
+    let mut counter = by_hash.len();
+    while let Some(QueueItem { entry, .. }) = ready.pop() {
+        for parent in &entry.parents {
+            let remaining = children.get_mut(parent).unwrap();
+            *remaining -= 1;
+
+            if *remaining == 0 {
+                if let Some(parent_entry) = by_hash.get(parent) {
+                    counter += 1;
+                    ready.push(QueueItem {
+                        time: parent_entry.time,
+                        order: Reverse(counter),
+                        entry: parent_entry.clone(),
+                    });
+                }
+            }
+        }
+        sorted.push(entry);
+    }
+
+    sorted
+}
+
+impl PartialEq for QueueItem {
+    fn eq(&self, other: &Self) -> bool { self.cmp(other) == Ordering::Equal }
+}
+
+impl Eq for QueueItem {}
+
+impl PartialOrd for QueueItem {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl Ord for QueueItem {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.time.cmp(&other.time).then(self.order.cmp(&other.order))
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use crate::ignore::IgnoreRules;
+use crate::pathspec;
+use crate::storage::Storage;
+use crate::structs::flags::{ExtendedFlags, Flags};
+use crate::structs::git_index::GitIndex;
+use crate::utils::files;
+use crate::worktree::{self, FileState, StatChecker};
+
+const TYPE_MASK: u32 = 0o170000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change { Added, Modified, Deleted, TypeChanged }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UntrackedMode { No, Normal, All }
+
+// Mode and object hash of a path on one side of a comparison
+pub type Side = Option<(u32, String)>;
+
+#[derive(Debug)]
+pub struct StatusEntry {
+    pub path: String,
+    pub head: Side,
+    pub index: Side,
+    pub worktree_mode: Option<u32>,
+    pub staged: Option<Change>,
+    pub unstaged: Option<Change>,
+}
+
+#[derive(Debug)]
+pub struct Conflict {
+    pub path: String,
+    // base, ours and theirs, as stored in index stages 1, 2 and 3
+    pub stages: [Side; 3],
+    pub worktree_mode: Option<u32>,
+}
+
+#[derive(Debug)]
+pub struct Status {
+    pub branch: Option<String>,
+    pub head: Option<String>,
+    pub changes: Vec<StatusEntry>,
+    pub conflicts: Vec<Conflict>,
+    pub untracked: Vec<String>,
+    pub ignored: Vec<String>,
+}
+
+impl Change {
+    pub fn code(&self) -> char {
+        match self {
+            Change::Added => 'A',
+            Change::Modified => 'M',
+            Change::Deleted => 'D',
+            Change::TypeChanged => 'T',
+        }
+    }
+
+    pub fn describe(&self) -> &str {
+        match self {
+            Change::Added => "new file",
+            Change::Modified => "modified",
+            Change::Deleted => "deleted",
+            Change::TypeChanged => "typechange",
+        }
+    }
+}
+
+impl Conflict {
+    // Two letter code used by short and porcelain formats, like "UU" or "AA"
+    pub fn code(&self) -> &str {
+        match (self.stages[0].is_some(), self.stages[1].is_some(), self.stages[2].is_some()) {
+            (true, true, true) => "UU",
+            (false, true, true) => "AA",
+            (true, true, false) => "UD",
+            (true, false, true) => "DU",
+            (false, true, false) => "AU",
+            (false, false, true) => "UA",
+            _ => "DD",
+        }
+    }
+
+    pub fn describe(&self) -> &str {
+        match self.code() {
+            "UU" => "both modified",
+            "AA" => "both added",
+            "UD" => "deleted by them",
+            "DU" => "deleted by us",
+            "AU" => "added by us",
+            "UA" => "added by them",
+            _ => "both deleted",
+        }
+    }
+}
+
+impl Status {
+    pub fn is_clean(&self) -> bool {
+        self.changes.is_empty() && self.conflicts.is_empty()
+    }
+
+    pub fn has_staged(&self) -> bool {
+        self.changes.iter().any(|e| e.staged.is_some())
+    }
+}
+
+pub fn compute(storage: &Storage, paths: &[String], untracked_mode: UntrackedMode, show_ignored: bool) -> Status {
+    let index = storage.read_index();
+    let head = storage.read_ref("HEAD");
+    let head_files: BTreeMap<String, (u32, String)> =
+        match &head {
+            Some(commit) => storage
+                .read_tree_files(&storage.read_commit(commit).tree)
+                .into_iter()
+                .map(|(path, object_ref)| (path, (object_ref.permissions, object_ref.hash)))
+                .collect(),
+            None => BTreeMap::new()
+        };
+
+    let checker = StatChecker::new(storage, &index);
+    let mut changes = vec![];
+    let mut conflicts: BTreeMap<String, Conflict> = BTreeMap::new();
+
+    for entry in index.entries.iter().filter(|e| pathspec::matches_any(paths, &e.path)) {
+        let worktree_mode = worktree_mode(storage, &entry.path);
+
+        if entry.stage() != 0 {
+            let conflict = conflicts.entry(entry.path.clone()).or_insert_with(|| Conflict {
+                path: entry.path.clone(),
+                stages: [None, None, None],
+                worktree_mode,
+            });
+            conflict.stages[entry.stage() as usize - 1] = Some((entry.mode, entry.object_hash()));
+            continue;
+        }
+
+        let index_side = (entry.mode, entry.object_hash());
+        let head_side = head_files.get(&entry.path).cloned();
+        let staged = compare(head_side.as_ref(), Some(&index_side));
+
+        let skip_worktree =
+            entry.flags.intersects(Flags::ASSUME_VALID)
+                || entry.extended_flags.intersects(ExtendedFlags::SKIP_WORKTREE);
+        let unstaged =
+            if skip_worktree {
+                None
+            } else {
+                match checker.check(entry) {
+                    FileState::Unchanged => None,
+                    FileState::Deleted => Some(Change::Deleted),
+                    FileState::Modified if worktree_mode.is_some_and(|m| m & TYPE_MASK != entry.mode & TYPE_MASK) => {
+                        Some(Change::TypeChanged)
+                    }
+                    FileState::Modified => Some(Change::Modified),
+                }
+            };
+
+        if staged.is_some() || unstaged.is_some() {
+            changes.push(StatusEntry {
+                path: entry.path.clone(),
+                head: head_side,
+                index: Some(index_side),
+                worktree_mode,
+                staged,
+                unstaged,
+            });
+        }
+    }
+
+    for (path, head_side) in head_files.iter().filter(|(path, _)| pathspec::matches_any(paths, path)) {
+        if index.contains(path) { continue; }
+
+        changes.push(StatusEntry {
+            path: path.clone(),
+            head: Some(head_side.clone()),
+            index: None,
+            worktree_mode: worktree_mode(storage, path),
+            staged: Some(Change::Deleted),
+            unstaged: None,
+        });
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let rules = IgnoreRules::load(storage);
+    let untracked =
+        if untracked_mode == UntrackedMode::No {
+            vec![]
+        } else {
+            let files = files::walk_pruned(&storage.working_root, &|dir| rules.is_ignored_dir(dir));
+            let files: Vec<String> =
+                files
+                    .into_iter()
+                    .filter(|path| !index.contains(path) && !rules.is_ignored(path))
+                    .filter(|path| pathspec::matches_any(paths, path))
+                    .collect();
+
+            if untracked_mode == UntrackedMode::All { files } else { collapse_untracked(files, &index) }
+        };
+
+    let ignored =
+        if show_ignored {
+            let files = files::walk(&storage.working_root);
+            collapse_ignored(
+                files
+                    .into_iter()
+                    .filter(|path| !index.contains(path) && rules.is_ignored(path))
+                    .filter(|path| pathspec::matches_any(paths, path))
+                    .collect(),
+                &rules,
+            )
+        } else {
+            vec![]
+        };
+
+    Status {
+        branch: storage.head_target().map(|target| target.trim_start_matches("refs/heads/").to_string()),
+        head,
+        changes,
+        conflicts: conflicts.into_values().collect(),
+        untracked,
+        ignored,
+    }
+}
+
+fn compare(from: Option<&(u32, String)>, to: Option<&(u32, String)>) -> Option<Change> {
+    match (from, to) {
+        (None, None) => None,
+        (None, Some(_)) => Some(Change::Added),
+        (Some(_), None) => Some(Change::Deleted),
+        (Some(a), Some(b)) if a == b => None,
+        (Some(a), Some(b)) if a.0 & TYPE_MASK != b.0 & TYPE_MASK => Some(Change::TypeChanged),
+        _ => Some(Change::Modified),
+    }
+}
+
+fn worktree_mode(storage: &Storage, path: &str) -> Option<u32> {
+    fs::symlink_metadata(storage.working_root.join(path))
+        .ok()
+        .filter(|meta| !meta.is_dir())
+        .map(|meta| worktree::file_mode(&meta))
+}
+
+// Directories without any tracked files are shown as a whole, like "target/"
+fn collapse_untracked(files: Vec<String>, index: &GitIndex) -> Vec<String> {
+    let tracked_dirs: BTreeSet<String> =
+        index.entries
+            .iter()
+            .flat_map(|e| parent_dirs(&e.path))
+            .collect();
+
+    collapse(files, |dir| !tracked_dirs.contains(dir))
+}
+
+fn collapse_ignored(files: Vec<String>, rules: &IgnoreRules) -> Vec<String> {
+    collapse(files, |dir| rules.is_ignored_dir(dir))
+}
+
+fn collapse(files: Vec<String>, whole: impl Fn(&str) -> bool) -> Vec<String> {
+    let mut collapsed: BTreeSet<String> = BTreeSet::new();
+
+    for path in files {
+        match parent_dirs(&path).into_iter().find(|dir| whole(dir)) {
+            Some(dir) => collapsed.insert(format!("{}/", dir)),
+            None => collapsed.insert(path),
+        };
+    }
+
+    collapsed.into_iter().collect()
+}
+
+// "a/b/c.txt" -> ["a", "a/b"]
+fn parent_dirs(path: &str) -> Vec<String> {
+    let components: Vec<&str> = path.split('/').collect();
+
+    (1..components.len()).map(|depth| components[..depth].join("/")).collect()
+}
@@ -1,23 +1,45 @@
+use std::cell::{OnceCell, RefCell};
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::rc::Rc;
 use std::path::{PathBuf};
-use crate::utils::files::{create_dir, create_file, read_object_file, read_file};
+use crate::utils::files::{create_dir, create_file, read_object_file, read_file, walk, write_file};
+use crate::utils::hash;
+use crate::utils::process::die;
 use crate::formats::{serialization, deserialization};
+use crate::commit_graph::CommitGraphFile;
+use crate::config::Config;
+use crate::pack::{PackSet, PackedObject};
+use crate::structs::git_commit::GitCommit;
 use crate::structs::git_index::GitIndex;
 use crate::structs::git_object::GitObject;
+use crate::structs::git_tree::{GitObjectRef, GitTree};
+use crate::structs::GitObjectType;
+use crate::worktree;
 
 pub struct Storage {
-    pub working_root: PathBuf
+    pub working_root: PathBuf,
+    // Packs are opened on the first lookup that misses the loose objects
+    packs: RefCell<Option<Rc<PackSet>>>,
+    commit_graph: OnceCell<Option<Rc<CommitGraphFile>>>,
+    config: OnceCell<Rc<Config>>,
 }
 
 impl Storage {
     pub fn new(path: PathBuf) -> Self {
-        Self { working_root: path }
+        Self { working_root: path, packs: RefCell::new(None), commit_graph: OnceCell::new(), config: OnceCell::new() }
     }
 
     pub(crate) fn root(&self) -> PathBuf { self.working_root.join(".rinit") }
     fn objects_path(&self) -> PathBuf { self.root().join("objects") }
     fn info_path(&self) -> PathBuf { self.objects_path().join("info") }
-    fn pack_path(&self) -> PathBuf { self.objects_path().join("pack") }
+    pub(crate) fn commit_graph_path(&self) -> PathBuf { self.info_path().join("commit-graph") }
+    pub(crate) fn pack_path(&self) -> PathBuf { self.objects_path().join("pack") }
     fn index_path(&self) -> PathBuf { self.root().join("index") }
+    fn head_path(&self) -> PathBuf { self.root().join("HEAD") }
+    fn refs_path(&self) -> PathBuf { self.root().join("refs") }
+    fn packed_refs_path(&self) -> PathBuf { self.root().join("packed-refs") }
 
     pub fn object_path(&self, hash: &str) -> PathBuf {
         let (catalog, index) = hash.split_at(2);
@@ -25,13 +47,78 @@ impl Storage {
     }
 
     pub fn object_exists(&self, hash: &str) -> bool {
-        self.object_path(hash).exists()
+        self.object_path(hash).exists() || self.packs().contains(hash)
+    }
+
+    // Names of the objects stored as single files
+    pub fn loose_objects(&self) -> Vec<String> {
+        walk(&self.objects_path())
+            .into_iter()
+            .filter_map(|path| {
+                let (catalog, rest) = path.split_once('/')?;
+                let hash = format!("{}{}", catalog, rest);
+                (catalog.len() == 2 && hash.len() == 64 && hash::is_hex(&hash)).then_some(hash)
+            })
+            .collect()
+    }
+
+    // Deletes a loose object, and its fan-out directory once empty
+    pub fn remove_loose_object(&self, hash: &str) {
+        let path = self.object_path(hash);
+        fs::remove_file(&path).unwrap_or_else(|_| panic!("object {} is not loose", hash));
+        if let Some(dir) = path.parent() { let _ = fs::remove_dir(dir); }
+    }
+
+    // Writes an object read from a pack back as a loose one. Loose objects are text, and the
+    // object has to keep the name the pack gave it
+    pub fn persist_raw(&self, name: &str, object_type: GitObjectType, content: &[u8]) -> Result<(), String> {
+        let content = std::str::from_utf8(content).map_err(|_| format!("object {} is not UTF-8", name))?;
+        let object = deserialization::call(format!("{} {}\0{}", object_type, content.len(), content));
+        if *object.hash() != name { return Err(format!("object {} does not match its content", name)); }
+
+        self.persist_object(object.as_ref());
+        Ok(())
+    }
+
+    pub fn packs(&self) -> Rc<PackSet> {
+        Rc::clone(self.packs.borrow_mut().get_or_insert_with(|| Rc::new(PackSet::load(&self.pack_path()))))
+    }
+
+    // Read once per process, commits made since it was written are simply not in it
+    pub fn commit_graph(&self) -> Option<Rc<CommitGraphFile>> {
+        self.commit_graph
+            .get_or_init(|| {
+                let path = self.commit_graph_path();
+                if !path.is_file() { return None; }
+
+                CommitGraphFile::from_path(&path)
+                    .map_err(|error| eprintln!("warning: ignoring commit-graph: {}", error))
+                    .ok()
+                    .map(Rc::new)
+            })
+            .clone()
+    }
+
+    // Read once per process, so a broken system or global file is only warned about once
+    pub fn config(&self) -> Rc<Config> {
+        Rc::clone(self.config.get_or_init(|| Rc::new(Config::load(self))))
+    }
+
+    // Forgets the opened packs, the next lookup sees packs written or removed since
+    pub fn reload_packs(&self) {
+        self.packs.replace(None);
     }
 
     pub fn init(&self) {
         create_dir(&self.root());
-        create_dir(&self.root().join("objects/info"));
-        create_dir(&self.root().join("objects/pack"));
+        create_dir(&self.info_path());
+        create_dir(&self.pack_path());
+        create_dir(&self.refs_path().join("heads"));
+        create_dir(&self.refs_path().join("tags"));
+
+        if !self.head_path().exists() {
+            write_file(&self.head_path(), "ref: refs/heads/master\n");
+        }
     }
 
     pub fn persist_object(&self, object: &dyn GitObject) {
@@ -45,28 +132,314 @@ impl Storage {
         create_file(&file_path, &body);
     }
 
-    pub fn read_object(&self, hash: &String) -> Box<dyn GitObject> {
-        let (catalog, index) = hash.split_at(2);
-        let obj_path = self.objects_path().join(catalog).join(index);
+    pub fn read_object(&self, hash: &str) -> Box<dyn GitObject> {
+        deserialization::call(self.read_object_data(hash))
+    }
+
+    pub fn read_commit(&self, hash: &str) -> GitCommit {
+        let data = self.read_object_data(hash);
+
+        match deserialization::split(&data) {
+            (GitObjectType::Commit, content) => GitCommit::new(content),
+            (object_type, _) => panic!("object {} is a {}, not a commit", hash, object_type)
+        }
+    }
+
+    pub fn read_tree(&self, hash: &str) -> GitTree {
+        let data = self.read_object_data(hash);
+
+        match deserialization::split(&data) {
+            (GitObjectType::Tree, content) => {
+                let mut tree = GitTree::new(content);
+                // Trees written by the baseline hold the first 20 characters of each hash
+                for object_ref in tree.refs.iter_mut().filter(|object_ref| object_ref.hash.len() < 64) {
+                    if let Some(full) = self.find_object(&object_ref.hash) { object_ref.hash = full; }
+                }
+                tree
+            }
+            (object_type, _) => panic!("object {} is a {}, not a tree", hash, object_type)
+        }
+    }
+
+    // All files of a tree and its subtrees keyed by their full path
+    pub fn read_tree_files(&self, hash: &str) -> BTreeMap<String, GitObjectRef> {
+        let mut files = BTreeMap::new();
+        self.collect_tree_files(hash, "", &mut files);
+
+        files
+    }
+
+    fn collect_tree_files(&self, hash: &str, prefix: &str, files: &mut BTreeMap<String, GitObjectRef>) {
+        for mut object_ref in self.read_tree(hash).refs {
+            let path = format!("{}{}", prefix, object_ref.content);
+
+            match object_ref.ref_type {
+                GitObjectType::Tree => self.collect_tree_files(&object_ref.hash, &format!("{}/", path), files),
+                _ => {
+                    object_ref.content = path.clone();
+                    files.insert(path, object_ref);
+                }
+            }
+        }
+    }
+
+    fn read_object_data(&self, hash: &str) -> String {
+        let path = self.object_path(hash);
+        if path.exists() { return read_object_file(path); }
+
+        let (object_type, content) = self.read_packed(hash).unwrap_or_else(|| panic!("object {} not found", hash));
+        let content = String::from_utf8(content).expect("String parsing error");
+
+        format!("{} {}\0{}", object_type, content.len(), content)
+    }
+
+    // Type and content of an object, loose or packed
+    pub fn read_raw(&self, hash: &str) -> Option<PackedObject> {
+        let path = self.object_path(hash);
+        if !path.exists() { return self.read_packed(hash); }
+
+        let data = read_object_file(path);
+        let (object_type, content) = deserialization::split(&data);
+        Some((object_type, content.as_bytes().to_vec()))
+    }
+
+    // Packs may have been replaced since they were opened, so a miss rescans them once
+    fn read_packed(&self, hash: &str) -> Option<PackedObject> {
+        let find = || {
+            self.packs()
+                .read(hash, &|base| self.read_raw(base))
+                .map(|result| result.unwrap_or_else(|error| panic!("corrupt pack entry for {}: {}", hash, error)))
+        };
+
+        find().or_else(|| {
+            self.reload_packs();
+            find()
+        })
+    }
+
+    // Expands an abbreviated object name into a full hash if it is unambiguous
+    pub fn find_object(&self, prefix: &str) -> Option<String> {
+        if prefix.len() < 4 || !hash::is_hex(prefix) { return None; }
+        if self.object_exists(prefix) { return Some(prefix.to_string()); }
+
+        let (catalog, rest) = prefix.split_at(2);
+        let mut matches: Vec<String> =
+            fs::read_dir(self.objects_path().join(catalog))
+                .into_iter()
+                .flatten()
+                .filter_map(|e| e.ok())
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .filter(|name| name.starts_with(rest))
+                .map(|name| format!("{}{}", catalog, name))
+                .chain(self.packs().find_prefix(prefix))
+                .collect();
+        matches.sort();
+        matches.dedup();
+
+        match matches.as_slice() {
+            [hash] => Some(hash.to_string()),
+            _ => None
+        }
+    }
+
+    // Follows symbolic refs ("ref: refs/heads/master") down to an object hash. Only "HEAD"-style
+    // names and paths under refs/ are refs, other files of the repository are not
+    pub fn read_ref(&self, name: &str) -> Option<String> {
+        if !Self::is_ref_path(name) { return None; }
+        let path = self.root().join(name);
+        if !path.is_file() { return self.packed_refs().remove(name); }
+
+        let content = fs::read_to_string(&path).unwrap_or_else(|_| die(format!("ref {} is not a text file", name)));
+        let value = content.trim();
 
-        deserialization::call(read_object_file(obj_path))
+        match value.strip_prefix("ref: ") {
+            Some(target) => self.read_ref(target),
+            // SHA-1 names point into packs written by Git
+            None if (value.len() == 64 || value.len() == 40) && hash::is_hex(value) => Some(value.to_string()),
+            None => die(format!("ref {} is corrupt: '{}' is not an object name", name, value)),
+        }
+    }
+
+    // "HEAD", "MERGE_HEAD" and the like, or "refs/..." without empty, "." or ".." components
+    fn is_ref_path(name: &str) -> bool {
+        let pseudo = !name.is_empty() && name.chars().all(|c| c.is_ascii_uppercase() || c == '_');
+        let under_refs = name.strip_prefix("refs/").is_some_and(|rest| {
+            rest.split('/').all(|component| !component.is_empty() && component != "." && component != "..")
+        });
+
+        pseudo || under_refs
+    }
+
+    // The branch HEAD points to, like "refs/heads/master", or None when HEAD is detached
+    pub fn head_target(&self) -> Option<String> {
+        if !self.head_path().is_file() { return None; }
+
+        read_file(self.head_path())
+            .trim()
+            .strip_prefix("ref: ")
+            .map(str::to_string)
+    }
+
+    // Refs under a namespace like "refs/heads/", sorted by name. Loose refs win over packed ones
+    pub fn list_refs(&self, prefix: &str) -> Vec<(String, String)> {
+        let mut refs: BTreeMap<String, String> =
+            self.packed_refs().into_iter().filter(|(name, _)| name.starts_with(prefix)).collect();
+
+        for path in walk(&self.root().join(prefix)) {
+            let name = format!("{}{}", prefix, path);
+            if let Some(hash) = self.read_ref(&name) { refs.insert(name, hash); }
+        }
+
+        refs.into_iter().collect()
+    }
+
+    pub fn ref_exists(&self, name: &str) -> bool {
+        self.root().join(name).is_file() || self.packed_refs().contains_key(name)
+    }
+
+    // "packed-refs" holds one "<hash> <name>" line per ref that `pack_refs` moved there
+    fn packed_refs(&self) -> BTreeMap<String, String> {
+        if !self.packed_refs_path().is_file() { return BTreeMap::new(); }
+
+        read_file(self.packed_refs_path())
+            .lines()
+            .filter(|line| !line.starts_with(['#', '^']))
+            .filter_map(|line| line.split_once(' '))
+            .map(|(hash, name)| (name.to_string(), hash.to_string()))
+            .collect()
+    }
+
+    fn write_packed_refs(&self, refs: &BTreeMap<String, String>) {
+        if refs.is_empty() {
+            let _ = fs::remove_file(self.packed_refs_path());
+            return;
+        }
+
+        let lines: String = refs.iter().map(|(name, hash)| format!("{} {}\n", hash, name)).collect();
+        write_file(&self.packed_refs_path(), &format!("# pack-refs with: peeled fully-peeled sorted \n{}", lines));
+    }
+
+    // Moves every loose ref under refs/ into "packed-refs"
+    pub fn pack_refs(&self) {
+        let mut packed = self.packed_refs();
+        let loose: Vec<String> = walk(&self.refs_path()).into_iter().map(|path| format!("refs/{}", path)).collect();
+
+        for name in &loose {
+            if let Some(hash) = self.read_ref(name) { packed.insert(name.clone(), hash); }
+        }
+        self.write_packed_refs(&packed);
+
+        for name in &loose { self.remove_loose_ref(name); }
+    }
+
+    // Removes a ref, loose or packed
+    pub fn delete_ref(&self, name: &str) {
+        let mut packed = self.packed_refs();
+        let was_packed = packed.remove(name).is_some();
+        let was_loose = self.root().join(name).is_file();
+        if !was_packed && !was_loose { panic!("ref {} does not exist", name); }
+
+        if was_packed { self.write_packed_refs(&packed); }
+        if was_loose { self.remove_loose_ref(name); }
+    }
+
+    // Removes the file of a ref and the directories it leaves empty, like "refs/heads/feature/" for "feature/x"
+    fn remove_loose_ref(&self, name: &str) {
+        let path = self.root().join(name);
+        fs::remove_file(&path).unwrap_or_else(|_| panic!("ref {} does not exist", name));
+
+        let refs = self.refs_path();
+        let mut dir = path.parent();
+        while let Some(current) = dir.filter(|d| d.starts_with(&refs) && d.components().count() > refs.components().count() + 1) {
+            if fs::remove_dir(current).is_err() { break; }
+            dir = current.parent();
+        }
+    }
+
+    pub fn rename_ref(&self, old: &str, new: &str) {
+        let hash = self.read_ref(old).unwrap_or_else(|| panic!("ref {} does not exist", old));
+        self.delete_ref(old);
+        self.update_ref(new, &hash);
+    }
+
+    // Points a ref at an object, creating missing directories
+    pub fn update_ref(&self, name: &str, hash: &str) {
+        let path = self.root().join(name);
+        create_dir(path.parent().unwrap());
+        write_file(&path, &format!("{}\n", hash));
+    }
+
+    // Moves the current branch, or a detached HEAD, to a new commit
+    pub fn update_head(&self, hash: &str) {
+        match self.head_target() {
+            Some(branch) => self.update_ref(&branch, hash),
+            None => self.set_head(hash),
+        }
+    }
+
+    // HEAD either names a branch ("refs/heads/master") or holds a commit hash when detached
+    pub fn set_head(&self, target: &str) {
+        let content = if target.starts_with("refs/") { format!("ref: {}\n", target) } else { format!("{}\n", target) };
+        write_file(&self.head_path(), &content);
+    }
+
+    // Checks the rules of `git check-ref-format` that matter for branch names
+    pub fn is_valid_ref_name(name: &str) -> bool {
+        !name.is_empty()
+            && !name.starts_with(['-', '/', '.'])
+            && !name.ends_with(['/', '.'])
+            && !name.ends_with(".lock")
+            && !name.contains("..")
+            && !name.contains("//")
+            && !name.contains("@{")
+            && !name.contains("/.")
+            && name != "@"
+            && !name.chars().any(|c| c.is_control() || " ~^:?*[\\".contains(c))
+    }
+
+    pub fn resolve_name(&self, name: &str) -> Option<String> {
+        let candidates = [
+            name.to_string(),
+            format!("refs/{}", name),
+            format!("refs/tags/{}", name),
+            format!("refs/heads/{}", name),
+        ];
+
+        candidates
+            .iter()
+            .find_map(|candidate| self.read_ref(candidate))
+            .or_else(|| self.find_object(name))
     }
 
     pub fn read_index(&self) -> GitIndex {
         let path = self.index_path();
 
         if path.exists() {
-            GitIndex::from_path(&path)
+            GitIndex::from_path(&path, &|prefix| self.find_object(prefix))
         } else {
             GitIndex::empty()
         }
     }
 
-    pub fn save_index(&self, index: &GitIndex) {
+    // Entries are racy against the index file being written, so it is written first, then again
+    // if that smudged any entry
+    pub fn save_index(&self, index: &mut GitIndex) {
         index.persist(self.index_path());
+        let meta = fs::metadata(self.index_path()).unwrap();
+        index.timestamp = Some((meta.mtime() as u32, meta.mtime_nsec() as u32));
+
+        if worktree::smudge_racy_entries(self, index) { index.persist(self.index_path()); }
+    }
+
+    // Persists the trees of the index and returns the root one
+    pub fn write_tree(&self, index: &GitIndex) -> String {
+        let trees = GitTree::from_index(index);
+        for tree in &trees { self.persist_object(tree); }
+
+        trees.last().unwrap().hash().to_string()
     }
 
     pub fn read_file(&self, path: String) -> String {
         read_file(self.working_root.join(path))
     }
-}
\ No newline at end of file
+}
@@ -14,12 +14,19 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// Extended flags, only present in index version 3 and later
+    #[derive(Copy, Clone, Debug)]
+    pub struct ExtendedFlags: u16 {
+        /// The entry is only marked as going to be added, it has no content yet.
+        const INTENT_TO_ADD = 0x2000;
+        /// If set, the entry is not checked out into the working tree.
+        const SKIP_WORKTREE = 0x4000;
+    }
+}
+
 impl Flags {
     pub fn as_u16(&self) -> u16 {
         self.bits()
     }
-
-    pub fn to_memory(&self) -> Flags {
-        Flags::from_bits_retain(self.bits())
-    }
 }
@@ -0,0 +1,75 @@
+use std::fmt;
+use std::fmt::Display;
+use std::rc::Rc;
+use crate::formats::commit;
+use crate::structs::git_object::GitObject;
+use crate::structs::GitObjectType;
+use crate::utils::{hash, time};
+
+#[derive(Debug, Clone)]
+pub struct GitSignature {
+    // Mihail Odebe <odebe@example.com> 1700000000 +0300
+    pub name: String,
+    pub email: String,
+    pub time: i64,
+    pub tz: String
+}
+
+#[derive(Debug, Clone)]
+pub struct GitCommit {
+    pub tree: String,
+    pub parents: Vec<String>,
+    pub author: GitSignature,
+    pub committer: GitSignature,
+    pub message: String
+}
+
+impl GitSignature {
+    pub fn new(name: &str, email: &str, time: i64, tz: &str) -> Self {
+        Self { name: name.to_string(), email: email.to_string(), time, tz: tz.to_string() }
+    }
+
+    pub fn date(&self) -> String {
+        time::format(self.time, &self.tz)
+    }
+}
+
+impl GitCommit {
+    pub fn new(content: &str) -> Self {
+        commit::parse(content)
+    }
+
+    pub fn subject(&self) -> &str {
+        self.message.lines().next().unwrap_or("")
+    }
+
+    pub fn body(&self) -> &str {
+        match self.message.split_once("\n\n") {
+            Some((_subject, body)) => body,
+            None => ""
+        }
+    }
+}
+
+impl GitObject for GitCommit {
+    fn content(&self) -> Rc<String> {
+        let mut value = format!("tree {}\n", self.tree);
+        for parent in &self.parents {
+            value.push_str(&format!("parent {}\n", parent));
+        }
+        value.push_str(&format!("author {}\n", self.author));
+        value.push_str(&format!("committer {}\n", self.committer));
+        value.push('\n');
+        value.push_str(&self.message);
+
+        Rc::new(value)
+    }
+    fn hash(&self) -> Rc<String> { Rc::new(hash::from_string(&self.content())) }
+    fn git_type(&self) -> GitObjectType { GitObjectType::Commit }
+}
+
+impl Display for GitSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} <{}> {} {}", self.name, self.email, self.time, self.tz)
+    }
+}
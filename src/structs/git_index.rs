@@ -3,12 +3,15 @@ use std::fs::Metadata;
 use std::os::unix::fs::MetadataExt;
 use std::path::PathBuf;
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use std::io::{self, Read, Write};
+use std::io::{self, Cursor, Read, Write};
+use std::ops::Range;
+use sha2::{Digest, Sha256};
 
-use crate::structs::flags::Flags;
+use crate::structs::flags::{ExtendedFlags, Flags};
 use crate::utils::hash;
+use crate::utils::process::die;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct GitIndexEntryStats {
     pub ctime_seconds: u32,
     pub ctime_nanoseconds: u32,
@@ -25,14 +28,18 @@ pub struct GitIndexEntryStats {
 pub struct GitIndexEntry {
     pub stats: GitIndexEntryStats,
     pub mode: u32,
-    pub hash: [u8; 20],
+    pub hash: [u8; 32],
     pub flags: Flags,
+    pub extended_flags: ExtendedFlags,
     pub path: String,
 }
 
 #[derive(Debug)]
 pub struct GitIndex {
-    pub entries: Vec<GitIndexEntry>
+    pub entries: Vec<GitIndexEntry>,
+    /// Modification time of the index file when it was last read or written, entries changed at or
+    /// after it are racy
+    pub timestamp: Option<(u32, u32)>,
 }
 
 impl From<Metadata> for GitIndexEntryStats {
@@ -53,66 +60,151 @@ impl From<Metadata> for GitIndexEntryStats {
 
 const GIT_INDEX_HEADER: &[u8; 4] = b"DIRC";
 const GIT_INDEX_VERSIONS: u32 = 2;
+const GIT_INDEX_EXTENDED_VERSION: u32 = 3;
+const GIT_INDEX_CHECKSUM_SIZE: usize = 32;
+// The baseline wrote the first 20 characters of each hex hash as ASCII instead of the hash itself
+const LEGACY_HASH_SIZE: usize = 20;
+const LEGACY_CACHEINFO_MODES: [u32; 5] = [0o100644, 0o100755, 0o120000, 0o40000, 0o160000];
+
+// Turns such a prefix into the full hash of the object it abbreviates
+pub type ExpandHash<'a> = &'a dyn Fn(&str) -> Option<String>;
 
 impl GitIndex {
-    pub fn from_path(path: impl Into<PathBuf>) -> Self {
-        parse_git_index(&path.into()).unwrap()
+    // `expand` turns the abbreviated hashes of an index written by the baseline into full ones
+    pub fn from_path(path: impl Into<PathBuf>, expand: ExpandHash) -> Self {
+        let path = path.into();
+        let mut index = Self::verify(&path, expand).unwrap_or_else(|error| die(format!("index file corrupt: {}", error)));
+        let meta = fs::metadata(&path).unwrap();
+        index.timestamp = Some((meta.mtime() as u32, meta.mtime_nsec() as u32));
+
+        index
     }
 
     pub fn empty() -> Self {
-        Self { entries: vec![] }
+        Self { entries: vec![], timestamp: None }
     }
 
+    // Keeps entries sorted by path and stage, a resolved (stage 0) entry replaces all conflict stages
     pub fn add_entry(&mut self, entry: GitIndexEntry) {
-        // TODO: sorting and stuff
-        self.entries.push(entry);
+        let range = self.range(&entry.path);
+        let stage = entry.stage();
+        let mut stages: Vec<GitIndexEntry> =
+            self.entries
+                .drain(range.clone())
+                .filter(|e| stage != 0 && e.stage() != 0 && e.stage() != stage)
+                .collect();
+        stages.push(entry);
+        stages.sort_by_key(|e| e.stage());
+
+        self.entries.splice(range.start..range.start, stages);
     }
 
-    pub fn persist(&self, path: impl Into<PathBuf>) {
-        write_git_index(&path.into(), self).unwrap();
+    pub fn remove_entry(&mut self, path: &str) -> bool {
+        let range = self.range(path);
+        let removed = !range.is_empty();
+        self.entries.drain(range);
+
+        removed
     }
-}
 
-impl GitIndexEntry {
-    pub fn from_path(
-        path: impl Into<PathBuf>,
-        mode: Option<u32>,
-        sha1: Option<String>
-    ) -> Self {
-        let binding = path.into();
+    pub fn entry(&self, path: &str) -> Option<&GitIndexEntry> {
+        self.stages(path).iter().find(|e| e.stage() == 0)
+    }
+
+    pub fn entry_mut(&mut self, path: &str) -> Option<&mut GitIndexEntry> {
+        let range = self.range(path);
+        self.entries[range].iter_mut().find(|e| e.stage() == 0)
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        !self.range(path).is_empty()
+    }
 
-        let meta= fs::metadata(&binding).unwrap();
-        let hash = sha1.unwrap_or_else(|| hash::from_path(&binding));
-        let mode = mode.unwrap_or_else(|| meta.mode());
+    // All entries of a path, the resolved one or its conflict stages
+    pub fn stages(&self, path: &str) -> &[GitIndexEntry] {
+        &self.entries[self.range(path)]
+    }
+
+    // Entries are sorted by path, so the ones of a path are found by binary search
+    fn range(&self, path: &str) -> Range<usize> {
+        let start = self.entries.partition_point(|e| e.path.as_str() < path);
+        let end = start + self.entries[start..].partition_point(|e| e.path == path);
+
+        start..end
+    }
+
+    pub fn persist(&self, path: impl Into<PathBuf>) {
+        write_git_index(&path.into(), self).unwrap();
+    }
 
-        let path_ = binding.as_path();
-        let hash_bytes : [u8; 20] = hash.as_bytes()[0..20].try_into().unwrap();
+    // Parses an index file without panicking and checks the SHA-256 of its content that follows the
+    // entries. Indexes written before the checksum existed end right after their entries, and the
+    // ones written by the baseline also hold hex prefixes instead of hashes, see `LEGACY_HASH_SIZE`
+    pub fn verify(path: impl Into<PathBuf>, expand: ExpandHash) -> Result<Self, String> {
+        let data = fs::read(path.into()).map_err(|e| e.to_string())?;
+        let mut reader = Cursor::new(&data[..]);
+
+        let error =
+            match read_git_index(&mut reader, None) {
+                Ok(index) => {
+                    let (content, trailer) = data.split_at(reader.position() as usize);
+                    match trailer.len() {
+                        0 => return Ok(index),
+                        GIT_INDEX_CHECKSUM_SIZE if Sha256::digest(content)[..] == *trailer => return Ok(index),
+                        GIT_INDEX_CHECKSUM_SIZE => return Err("index file checksum mismatch".to_string()),
+                        _ => "index file has trailing garbage".to_string(),
+                    }
+                }
+                Err(error) => error.to_string(),
+            };
 
-        let mut flags = Flags::empty();
-        let len = binding.as_path().to_str().unwrap().len();
-        flags = Flags::from_bits_retain(len as u16 & 0x0fff);
+        let mut reader = Cursor::new(&data[..]);
+        match read_git_index(&mut reader, Some(expand)) {
+            Ok(index) if reader.position() as usize == data.len() => Ok(index),
+            _ => Err(error),
+        }
+    }
+}
 
+impl GitIndexEntry {
+    pub fn new(path: &str, mode: u32, hash: &str, stats: GitIndexEntryStats) -> Self {
         GitIndexEntry {
             mode,
-            flags,
-            stats: meta.into(),
-            hash: hash_bytes,
-            path: path_.display().to_string()
+            flags: Flags::from_bits_retain(path.len().min(0x0fff) as u16),
+            extended_flags: ExtendedFlags::empty(),
+            stats,
+            // Objects of Git's SHA-1 packs can be read but not staged
+            hash: hash::to_bytes(hash).unwrap_or_else(|_| die(format!("{} can't be staged, the index holds SHA-256 names", hash))),
+            path: path.to_string()
         }
     }
+
+    pub fn object_hash(&self) -> String {
+        hash::to_hex(&self.hash)
+    }
+
+    // 0 for normal entries, 1-3 for the base/ours/theirs sides of a conflict
+    pub fn stage(&self) -> u16 {
+        (self.flags & Flags::STAGE_MASK).bits() >> 12
+    }
+
+    pub fn set_stage(&mut self, stage: u16) {
+        self.flags.remove(Flags::STAGE_MASK);
+        self.flags.insert(Flags::from_bits_retain((stage & 0x3) << 12));
+    }
 }
 
 fn parse_entry_path<R: Read>(reader: &mut R) -> io::Result<String> {
-    let mut string = String::new();
+    let mut bytes = vec![];
     loop {
         let byte = reader.read_u8()?;
         if byte == 0 {
             break;
         }
-        string.push(byte as char);
+        bytes.push(byte);
     }
 
-    Ok(string)
+    String::from_utf8(bytes).map_err(|_| invalid("Entry path is not UTF-8".to_string()))
 }
 
 fn write_entry_path<W: Write>(writer: &mut W, s: &str) -> io::Result<()> {
@@ -135,18 +227,29 @@ fn write_git_index_entry<W: Write>(writer: &mut W, entry: &GitIndexEntry) -> io:
     writer.write_u32::<BigEndian>(stats.gid)?;
     writer.write_u32::<BigEndian>(stats.size)?;
     writer.write_all(&entry.hash)?;
-    writer.write_u16::<BigEndian>(entry.flags.as_u16())?;
+    if entry.extended_flags.is_empty() {
+        writer.write_u16::<BigEndian>(entry.flags.as_u16())?;
+    } else {
+        writer.write_u16::<BigEndian>((entry.flags | Flags::EXTENDED).as_u16())?;
+        writer.write_u16::<BigEndian>(entry.extended_flags.bits())?;
+    }
     write_entry_path(writer, &entry.path)?;
 
     Ok(())
 }
 
 fn write_git_index(file_path: &PathBuf, index: &GitIndex) -> io::Result<()> {
-    let mut file = fs::File::create(file_path)?;
+    let mut file = Vec::new();
 
     // Write the header
     file.write_all(GIT_INDEX_HEADER)?;
-    file.write_u32::<BigEndian>(GIT_INDEX_VERSIONS)?; // Index version
+    let version =
+        if index.entries.iter().any(|e| !e.extended_flags.is_empty()) {
+            GIT_INDEX_EXTENDED_VERSION
+        } else {
+            GIT_INDEX_VERSIONS
+        };
+    file.write_u32::<BigEndian>(version)?; // Index version
     file.write_u32::<BigEndian>(index.entries.len() as u32)?; // Entry count
 
     // Write entries
@@ -154,11 +257,15 @@ fn write_git_index(file_path: &PathBuf, index: &GitIndex) -> io::Result<()> {
         write_git_index_entry(&mut file, entry)?;
     }
 
-    Ok(())
+    // Checksum of everything above
+    let checksum = Sha256::digest(&file);
+    file.extend_from_slice(&checksum);
+
+    fs::write(file_path, file)
 }
 
-fn parse_git_index(file_path: &PathBuf) -> io::Result<GitIndex> {
-    let mut file = fs::File::open(file_path)?;
+// With `expand`, entries are read in the baseline layout and their hex prefixes expanded
+fn read_git_index<R: Read>(file: &mut R, expand: Option<ExpandHash>) -> io::Result<GitIndex> {
     let mut header = [0u8; 4];
     file.read_exact(&mut header)?;
 
@@ -171,7 +278,7 @@ fn parse_git_index(file_path: &PathBuf) -> io::Result<GitIndex> {
 
     let version = file.read_u32::<BigEndian>()?;
 
-    if version != GIT_INDEX_VERSIONS {
+    if version != GIT_INDEX_VERSIONS && version != GIT_INDEX_EXTENDED_VERSION {
         return Err(io::Error::new(
             io::ErrorKind::InvalidData,
             "Not a valid Git index file version",
@@ -193,22 +300,53 @@ fn parse_git_index(file_path: &PathBuf) -> io::Result<GitIndex> {
         let gid = file.read_u32::<BigEndian>()?;
         let size = file.read_u32::<BigEndian>()?;
 
-        let mut hash = [0u8; 20];
-        file.read_exact(&mut hash)?;
+        let hash =
+            match expand {
+                Some(expand) => {
+                    let mut prefix = [0u8; LEGACY_HASH_SIZE];
+                    file.read_exact(&mut prefix)?;
+                    let prefix = String::from_utf8_lossy(&prefix).to_string();
+                    let full = expand(&prefix).ok_or_else(|| invalid(format!("no object matches the hash prefix {}", prefix)))?;
+                    hash::to_bytes(&full).map_err(invalid)?
+                }
+                None => {
+                    let mut hash = [0u8; 32];
+                    file.read_exact(&mut hash)?;
+                    hash
+                }
+            };
 
-        let raw_flags = file.read_u16::<BigEndian>()?;
-        let flags = Flags::from_bits_retain(raw_flags);
+        // The baseline read --cacheinfo modes as decimal numbers, so 100644 is stored as is
+        let mode =
+            match expand {
+                Some(_) => LEGACY_CACHEINFO_MODES.into_iter().find(|octal| format!("{:o}", octal) == mode.to_string()).unwrap_or(mode),
+                None => mode,
+            };
 
-        if flags.intersects(Flags::EXTENDED) {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Not supported entry flag (EXTENDED)",
-            ));
-        };
+        let raw_flags = file.read_u16::<BigEndian>()?;
+        let mut flags = Flags::from_bits_retain(raw_flags);
+
+        let extended_flags =
+            if flags.intersects(Flags::EXTENDED) {
+                if version < GIT_INDEX_EXTENDED_VERSION {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Not supported entry flag (EXTENDED)",
+                    ));
+                }
+                flags.remove(Flags::EXTENDED);
+                ExtendedFlags::from_bits_retain(file.read_u16::<BigEndian>()?)
+            } else {
+                ExtendedFlags::empty()
+            };
 
         let path =
             if flags.intersects(Flags::PATH_LEN) {
-                parse_entry_path(&mut file)?
+                let path = parse_entry_path(file)?;
+                if path.len().min(0x0fff) != (flags & Flags::PATH_LEN).bits() as usize {
+                    return Err(invalid("Entry path does not match its length".to_string()));
+                }
+                path
             } else {
                 return Err(io::Error::new(
                     io::ErrorKind::InvalidData,
@@ -231,11 +369,19 @@ fn parse_git_index(file_path: &PathBuf) -> io::Result<GitIndex> {
             mode,
             hash,
             flags,
+            extended_flags,
             path,
         };
 
         entries.push(entry);
     }
 
-    Ok(GitIndex { entries })
-}
\ No newline at end of file
+    // Lookups rely on the order, which indexes written by the baseline do not have
+    entries.sort_by(|a, b| (a.path.as_str(), a.stage()).cmp(&(b.path.as_str(), b.stage())));
+
+    Ok(GitIndex { entries, timestamp: None })
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
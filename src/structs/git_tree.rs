@@ -7,7 +7,9 @@ use crate::structs::git_object::GitObject;
 use crate::structs::GitObjectType;
 use crate::utils::hash;
 
-#[derive(Debug)]
+pub const TREE_MODE: u32 = 0o40000;
+
+#[derive(Debug, Clone)]
 pub struct GitObjectRef {
     // 100644 blob 2f781156939ad540b2434d012446154321e41e03	example_file.txt
     pub permissions: u32,
@@ -25,6 +27,65 @@ impl GitTree {
     pub fn new(content: &str) -> Self {
         tree::parse(content)
     }
+
+    // Builds nested trees out of index paths, subtrees always come before their parents
+    pub fn from_index(index: &GitIndex) -> Vec<GitTree> {
+        let mut entries: Vec<&GitIndexEntry> = index.entries.iter().collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut trees = vec![];
+
+        build_tree(&entries, "", &mut trees);
+
+        trees
+    }
+}
+
+fn build_tree(entries: &[&GitIndexEntry], prefix: &str, trees: &mut Vec<GitTree>) -> String {
+    let mut refs = vec![];
+    let mut position = 0;
+
+    while position < entries.len() {
+        let name = &entries[position].path[prefix.len()..];
+
+        match name.split_once('/') {
+            Some((dir, _rest)) => {
+                let dir_prefix = format!("{}{}/", prefix, dir);
+                let len = entries[position..].iter().take_while(|e| e.path.starts_with(&dir_prefix)).count();
+                let hash = build_tree(&entries[position..position + len], &dir_prefix, trees);
+
+                refs.push(GitObjectRef {
+                    permissions: TREE_MODE,
+                    ref_type: GitObjectType::Tree,
+                    hash,
+                    content: dir.to_string(),
+                });
+                position += len;
+            }
+            None => {
+                let mut object_ref: GitObjectRef = entries[position].into();
+                object_ref.content = name.to_string();
+                refs.push(object_ref);
+                position += 1;
+            }
+        }
+    }
+
+    refs.sort_by_key(|r| r.sort_key());
+    let tree = GitTree { refs };
+    let hash = tree.hash().to_string();
+    trees.push(tree);
+
+    hash
+}
+
+impl GitObjectRef {
+    // Git orders tree entries as if directory names had a trailing slash
+    pub fn sort_key(&self) -> String {
+        match self.ref_type {
+            GitObjectType::Tree => format!("{}/", self.content),
+            _ => self.content.clone()
+        }
+    }
 }
 
 impl GitObject for GitTree {
@@ -44,7 +105,7 @@ impl GitObject for GitTree {
 
 impl Display for GitObjectRef {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} {} {} {}", self.permissions, self.ref_type, self.hash, self.content)
+        write!(f, "{:o} {} {} {}", self.permissions, self.ref_type, self.hash, self.content)
     }
 }
 
@@ -53,20 +114,8 @@ impl From<&GitIndexEntry> for GitObjectRef {
         GitObjectRef {
             permissions: item.mode,
             ref_type: GitObjectType::Blob,
-            hash: String::from_utf8(Vec::from(item.hash)).unwrap(),
+            hash: item.object_hash(),
             content: item.path.to_string()
         }
     }
 }
-
-impl From<GitIndex> for GitTree {
-    fn from(item: GitIndex) -> Self {
-        let refs =
-            item.entries
-                .iter()
-                .map(|e| e.into())
-                .collect::<Vec<GitObjectRef>>();
-
-        GitTree { refs }
-    }
-}
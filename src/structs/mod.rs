@@ -6,15 +6,17 @@ pub mod git_blob;
 pub mod git_tree;
 pub mod git_object;
 pub mod git_index;
+pub mod git_commit;
 
-#[derive(Debug)]
-pub enum GitObjectType { Blob, Tree }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitObjectType { Blob, Tree, Commit }
 
 impl Display for GitObjectType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             GitObjectType::Blob => write!(f, "blob"),
             GitObjectType::Tree => write!(f, "tree"),
+            GitObjectType::Commit => write!(f, "commit"),
         }
     }
 }
@@ -24,8 +26,8 @@ impl GitObjectType {
         match data {
             "blob" => GitObjectType::Blob,
             "tree" => GitObjectType::Tree,
+            "commit" => GitObjectType::Commit,
             _ => panic!("not supported git object type")
         }
     }
 }
-
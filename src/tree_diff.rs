@@ -0,0 +1,264 @@
+use std::collections::{BTreeMap, HashMap};
+use crate::storage::Storage;
+use crate::structs::git_tree::GitObjectRef;
+use crate::structs::GitObjectType;
+
+const TYPE_MASK: u32 = 0o170000;
+pub const DEFAULT_SIMILARITY: u32 = 50;
+pub const DEFAULT_RENAME_LIMIT: usize = 1000;
+
+// A file on one side of a change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffEntry {
+    pub path: String,
+    pub mode: u32,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind { Added, Deleted, Modified, TypeChanged, Renamed, Copied, Unmerged }
+
+#[derive(Debug, Clone)]
+pub struct TreeChange {
+    pub kind: ChangeKind,
+    pub old: Option<DiffEntry>,
+    pub new: Option<DiffEntry>,
+    // Percentage of content shared by the two sides of a rename or copy
+    pub similarity: u32,
+}
+
+// Minimum similarity for rename and copy detection, None disables it. Past `limit` sources or
+// targets only identical files are paired, 0 means no limit
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenameOptions {
+    pub renames: Option<u32>,
+    pub copies: Option<u32>,
+    pub limit: usize,
+}
+
+impl ChangeKind {
+    pub fn code(&self) -> char {
+        match self {
+            ChangeKind::Added => 'A',
+            ChangeKind::Deleted => 'D',
+            ChangeKind::Modified => 'M',
+            ChangeKind::TypeChanged => 'T',
+            ChangeKind::Renamed => 'R',
+            ChangeKind::Copied => 'C',
+            ChangeKind::Unmerged => 'U',
+        }
+    }
+}
+
+impl TreeChange {
+    // Classifies the change between two versions of a path, None when they are the same
+    pub fn between(old: Option<DiffEntry>, new: Option<DiffEntry>) -> Option<Self> {
+        let kind =
+            match (&old, &new) {
+                (None, None) => return None,
+                (None, Some(_)) => ChangeKind::Added,
+                (Some(_), None) => ChangeKind::Deleted,
+                (Some(a), Some(b)) if a.mode == b.mode && a.hash == b.hash => return None,
+                (Some(a), Some(b)) if a.mode & TYPE_MASK != b.mode & TYPE_MASK => ChangeKind::TypeChanged,
+                _ => ChangeKind::Modified,
+            };
+
+        Some(Self { kind, old, new, similarity: 0 })
+    }
+
+    pub fn unmerged(path: &str) -> Self {
+        let entry = DiffEntry { path: path.to_string(), mode: 0, hash: String::new() };
+        Self { kind: ChangeKind::Unmerged, old: Some(entry.clone()), new: Some(entry), similarity: 0 }
+    }
+
+    // The path the change ends up at
+    pub fn path(&self) -> &str {
+        match (&self.new, &self.old) {
+            (Some(entry), _) | (None, Some(entry)) => &entry.path,
+            (None, None) => "",
+        }
+    }
+
+    pub fn old_path(&self) -> &str {
+        self.old.as_ref().map_or(self.path(), |entry| &entry.path)
+    }
+}
+
+// Recursively compares two trees, either of which may be missing. Subtrees with equal ids are skipped
+pub fn diff_trees(storage: &Storage, old: Option<&str>, new: Option<&str>) -> Vec<TreeChange> {
+    let mut changes = vec![];
+    walk(storage, old, new, "", &mut changes);
+    changes.sort_by(|a, b| a.path().cmp(b.path()));
+
+    changes
+}
+
+fn walk(storage: &Storage, old: Option<&str>, new: Option<&str>, prefix: &str, changes: &mut Vec<TreeChange>) {
+    if old == new { return; }
+
+    let old_refs = tree_refs(storage, old);
+    let new_refs = tree_refs(storage, new);
+    let mut names: Vec<&String> = old_refs.keys().chain(new_refs.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    for name in names {
+        let path = format!("{}{}", prefix, name);
+        let (old_ref, new_ref) = (old_refs.get(name), new_refs.get(name));
+        let old_tree = old_ref.filter(|r| r.ref_type == GitObjectType::Tree).map(|r| r.hash.as_str());
+        let new_tree = new_ref.filter(|r| r.ref_type == GitObjectType::Tree).map(|r| r.hash.as_str());
+
+        if old_tree.is_some() || new_tree.is_some() {
+            walk(storage, old_tree, new_tree, &format!("{}/", path), changes);
+        }
+
+        // A file replaced by a directory, or the other way around, is a deletion plus additions
+        let old_file = old_ref.filter(|_| old_tree.is_none()).map(|r| entry(&path, r));
+        let new_file = new_ref.filter(|_| new_tree.is_none()).map(|r| entry(&path, r));
+        changes.extend(TreeChange::between(old_file, new_file));
+    }
+}
+
+fn tree_refs(storage: &Storage, tree: Option<&str>) -> BTreeMap<String, GitObjectRef> {
+    match tree {
+        Some(hash) => storage.read_tree(hash).refs.into_iter().map(|r| (r.content.clone(), r)).collect(),
+        None => BTreeMap::new(),
+    }
+}
+
+fn entry(path: &str, object_ref: &GitObjectRef) -> DiffEntry {
+    DiffEntry { path: path.to_string(), mode: object_ref.permissions, hash: object_ref.hash.clone() }
+}
+
+// diff.renameLimit, or the given variable when it is set
+pub fn rename_limit(storage: &Storage, key: &str) -> usize {
+    let config = storage.config();
+    let limit = config.get_int(key).or_else(|| config.get_int("diff.renameLimit"));
+
+    limit.map_or(DEFAULT_RENAME_LIMIT, |limit| limit.max(0) as usize)
+}
+
+// Pairs added files with deleted (and, for copies, modified) files of similar content.
+// Exact matches are found first, the remaining pairs are ranked by similarity
+pub fn detect_renames(changes: Vec<TreeChange>, options: &RenameOptions, content: &dyn Fn(&DiffEntry) -> String) -> Vec<TreeChange> {
+    if options.renames.is_none() && options.copies.is_none() { return changes; }
+
+    let threshold = options.renames.or(options.copies).unwrap_or(DEFAULT_SIMILARITY);
+    let copy_threshold = options.copies.unwrap_or(threshold);
+    let is_source = |change: &TreeChange| {
+        change.kind == ChangeKind::Deleted
+            || (options.copies.is_some() && matches!(change.kind, ChangeKind::Modified | ChangeKind::TypeChanged))
+    };
+
+    let sources: Vec<usize> =
+        (0..changes.len()).filter(|&i| is_source(&changes[i]) && is_regular(changes[i].old.as_ref())).collect();
+    let targets: Vec<usize> =
+        (0..changes.len())
+            .filter(|&i| changes[i].kind == ChangeKind::Added && is_regular(changes[i].new.as_ref()))
+            .collect();
+    if sources.is_empty() || targets.is_empty() { return changes; }
+
+    // Comparing every pair of too many files takes forever, like Git only identical ones are paired then
+    let exhaustive = options.limit == 0 || sources.len() * targets.len() <= options.limit * options.limit;
+    let mut by_hash: HashMap<&str, Vec<usize>> = HashMap::new();
+    if !exhaustive {
+        eprintln!("warning: exhaustive rename detection was skipped due to too many files.");
+        eprintln!(
+            "warning: you may want to set your diff.renameLimit variable to at least {} and retry the command.",
+            sources.len().max(targets.len()));
+        for &source in &sources { by_hash.entry(changes[source].old.as_ref().unwrap().hash.as_str()).or_default().push(source); }
+    }
+
+    // (score, target, source), best candidates first
+    let mut candidates: Vec<(u32, usize, usize)> = vec![];
+    let mut contents: HashMap<usize, String> = HashMap::new();
+    let mut load = |i: usize, entry: &DiffEntry| contents.entry(i).or_insert_with(|| content(entry)).clone();
+
+    for &target in &targets {
+        let new = changes[target].new.as_ref().unwrap();
+        let compared: &[usize] = if exhaustive { &sources } else { by_hash.get(new.hash.as_str()).map_or(&[], |s| s) };
+
+        for &source in compared {
+            let old = changes[source].old.as_ref().unwrap();
+            let score =
+                if old.hash == new.hash {
+                    100
+                } else {
+                    similarity(&load(source, old), &load(target, new))
+                };
+
+            let minimum = if changes[source].kind == ChangeKind::Deleted { threshold.min(copy_threshold) } else { copy_threshold };
+            if score >= minimum { candidates.push((score, target, source)); }
+        }
+    }
+    // Exact matches with the same file name win over other exact matches
+    candidates.sort_by_key(|&(score, target, source)| {
+        let same_name = file_name(changes[target].path()) == file_name(changes[source].old_path());
+        (std::cmp::Reverse(score), !same_name, target, source)
+    });
+
+    let mut paired: HashMap<usize, (usize, u32, ChangeKind)> = HashMap::new();
+    let mut renamed: Vec<usize> = vec![];
+    for (score, target, source) in candidates {
+        if paired.contains_key(&target) { continue; }
+
+        let deleted = changes[source].kind == ChangeKind::Deleted;
+        let kind =
+            if deleted && options.renames.is_some() && score >= threshold && !renamed.contains(&source) {
+                renamed.push(source);
+                ChangeKind::Renamed
+            } else if options.copies.is_some() && score >= copy_threshold {
+                ChangeKind::Copied
+            } else {
+                continue;
+            };
+
+        paired.insert(target, (source, score, kind));
+    }
+
+    // A source renamed more than once keeps its first rename, the rest become copies
+    let mut result = vec![];
+    for (i, change) in changes.iter().enumerate() {
+        if renamed.contains(&i) { continue; }
+
+        match paired.get(&i) {
+            Some(&(source, similarity, kind)) => result.push(TreeChange {
+                kind,
+                old: changes[source].old.clone(),
+                new: change.new.clone(),
+                similarity,
+            }),
+            None => result.push(change.clone()),
+        }
+    }
+    result.sort_by(|a, b| a.path().cmp(b.path()));
+
+    result
+}
+
+fn is_regular(entry: Option<&DiffEntry>) -> bool {
+    entry.is_some_and(|e| e.mode & TYPE_MASK == 0o100000)
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+// Share of bytes in common lines, relative to the larger of the two contents
+pub fn similarity(old: &str, new: &str) -> u32 {
+    let larger = old.len().max(new.len());
+    if larger == 0 { return 100; }
+
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for line in old.split_inclusive('\n') { *counts.entry(line).or_default() += 1; }
+
+    let mut common = 0;
+    for line in new.split_inclusive('\n') {
+        if let Some(count) = counts.get_mut(line).filter(|c| **c > 0) {
+            *count -= 1;
+            common += line.len();
+        }
+    }
+
+    (common * 100 / larger) as u32
+}
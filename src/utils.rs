@@ -1,19 +1,40 @@
 pub mod hash {
-    use std::path::{PathBuf};
-    use crate::utils::files;
     use sha2::{Sha256, Digest};
 
-    pub fn from_path(path: impl Into<PathBuf>) -> String {
-        let content = files::read_object_file(path.into());
-        from_string(&content)
+    pub fn from_string(content: &str) -> String {
+        from_bytes(content.as_bytes())
     }
 
-    pub fn from_string(content: &String) -> String {
+    pub fn from_bytes(content: &[u8]) -> String {
         let mut hasher = Sha256::new();
 
         hasher.update(content);
         format!("{:x}", hasher.finalize())
     }
+
+    // The 32 bytes of a hash written as 64 hex characters
+    pub fn to_bytes(hex: &str) -> Result<[u8; 32], String> {
+        if hex.len() != 64 || !is_hex(hex) { return Err(format!("invalid object hash '{}'", hex)); }
+
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+        }
+
+        Ok(bytes)
+    }
+
+    pub fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    pub fn short(hash: &str) -> &str {
+        &hash[..hash.len().min(7)]
+    }
+
+    pub fn is_hex(value: &str) -> bool {
+        !value.is_empty() && value.chars().all(|c| c.is_ascii_hexdigit())
+    }
 }
 
 pub mod files {
@@ -33,10 +54,15 @@ pub mod files {
         let mut file = fs::File::create(file_path)
             .expect("Can't create file");
 
-        file.write_all(&*compressed_bytes)
+        file.write_all(&compressed_bytes)
             .expect("Can't write compressed content to file");
     }
 
+    pub fn write_file(file_path: &Path, content: &str) {
+        fs::write(file_path, content)
+            .expect("Can't write file");
+    }
+
     pub fn create_dir(path: &Path) {
         fs::create_dir_all(path).unwrap_or_else(|why| {
             println!("! {:?}", why.kind());
@@ -69,8 +95,256 @@ pub mod files {
         content
     }
 
+    pub fn read_stdin_bytes() -> Vec<u8> {
+        let mut content = vec![];
+
+        io::stdin()
+            .read_to_end(&mut content)
+            .expect("read_stdin_bytes: panic message");
+
+        content
+    }
+
     pub fn read_file(path: impl Into<PathBuf> + AsRef<Path>) -> String {
         let data = fs::read(path).unwrap();
         String::from_utf8(data).unwrap()
     }
+
+    // Relative paths of all files under `root`, sorted, skipping the repository directory
+    pub fn walk(root: &Path) -> Vec<String> {
+        walk_pruned(root, &|_| false)
+    }
+
+    // Same as `walk`, but does not descend into directories for which `prune` returns true
+    pub fn walk_pruned(root: &Path, prune: &dyn Fn(&str) -> bool) -> Vec<String> {
+        let mut paths = vec![];
+        walk_dir(root, "", prune, &mut paths);
+        paths.sort();
+
+        paths
+    }
+
+    fn walk_dir(dir: &Path, prefix: &str, prune: &dyn Fn(&str) -> bool, paths: &mut Vec<String>) {
+        let Ok(entries) = fs::read_dir(dir) else { return };
+
+        for entry in entries.filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name == ".rinit" { continue; }
+
+            let path = format!("{}{}", prefix, name);
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() => {
+                    if !prune(&path) { walk_dir(&entry.path(), &format!("{}/", path), prune, paths); }
+                }
+                Ok(_) => paths.push(path),
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+pub mod wildmatch {
+    // Shell-style matching of `*`, `?` and `[...]` classes, `*` does not cross `/` while `**` does
+    pub fn matches(pattern: &str, text: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let text: Vec<char> = text.chars().collect();
+
+        match_from(&pattern, &text, false)
+    }
+
+    // Same as `matches`, but wildcards also match `/` like they do in pathspecs
+    pub fn matches_across(pattern: &str, text: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let text: Vec<char> = text.chars().collect();
+
+        match_from(&pattern, &text, true)
+    }
+
+    fn match_from(pattern: &[char], text: &[char], across: bool) -> bool {
+        let wild = |c: char| across || c != '/';
+
+        match pattern.first() {
+            None => text.is_empty(),
+            Some('*') if pattern.get(1) == Some(&'*') && !across => match pattern.get(2) {
+                // "**" at the end matches everything that is left
+                None => true,
+                // "**/" matches zero or more directories
+                Some('/') => {
+                    (0..=text.len())
+                        .filter(|&i| i == 0 || text[i - 1] == '/')
+                        .any(|i| match_from(&pattern[3..], &text[i..], across))
+                }
+                Some(_) => match_from(&pattern[1..], text, across),
+            },
+            Some('*') => {
+                (0..=text.len())
+                    .take_while(|&i| i == 0 || wild(text[i - 1]))
+                    .any(|i| match_from(&pattern[1..], &text[i..], across))
+            }
+            Some('?') => !text.is_empty() && wild(text[0]) && match_from(&pattern[1..], &text[1..], across),
+            Some('[') => match (text.first(), class_end(pattern)) {
+                (Some(&c), Some(end)) => {
+                    wild(c) && class_matches(&pattern[1..end], c) && match_from(&pattern[end + 1..], &text[1..], across)
+                }
+                (Some(&c), None) => c == '[' && match_from(&pattern[1..], &text[1..], across),
+                (None, _) => false,
+            },
+            Some('\\') if pattern.len() > 1 => {
+                !text.is_empty() && text[0] == pattern[1] && match_from(&pattern[2..], &text[1..], across)
+            }
+            Some(&p) => !text.is_empty() && text[0] == p && match_from(&pattern[1..], &text[1..], across),
+        }
+    }
+
+    fn class_end(pattern: &[char]) -> Option<usize> {
+        let start = if matches!(pattern.get(1), Some('!' | '^')) { 2 } else { 1 };
+        pattern.iter().skip(start + 1).position(|&c| c == ']').map(|i| i + start + 1)
+    }
+
+    fn class_matches(class: &[char], c: char) -> bool {
+        let (negated, class) = match class.first() {
+            Some('!' | '^') => (true, &class[1..]),
+            _ => (false, class)
+        };
+
+        let mut found = false;
+        let mut i = 0;
+        while i < class.len() {
+            if i + 2 < class.len() && class[i + 1] == '-' {
+                found |= class[i] <= c && c <= class[i + 2];
+                i += 3;
+            } else {
+                found |= class[i] == c;
+                i += 1;
+            }
+        }
+
+        found != negated
+    }
+}
+
+pub mod process {
+    use std::fmt::Display;
+
+    pub fn die(message: impl Display) -> ! {
+        eprintln!("fatal: {}", message);
+        std::process::exit(128)
+    }
+}
+
+pub mod time {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] =
+        ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    pub fn now() -> i64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
+    }
+
+    // "+0200" -> 7200
+    pub fn tz_offset(tz: &str) -> i64 {
+        let sign = if tz.starts_with('-') { -1 } else { 1 };
+        let digits = tz.trim_start_matches(['+', '-']);
+        if digits.len() != 4 { return 0; }
+
+        let hours: i64 = digits[0..2].parse().unwrap_or(0);
+        let minutes: i64 = digits[2..4].parse().unwrap_or(0);
+
+        sign * (hours * 3600 + minutes * 60)
+    }
+
+    // Mon Oct 19 12:00:00 2026 +0200
+    pub fn format(timestamp: i64, tz: &str) -> String {
+        let local = timestamp + tz_offset(tz);
+        let days = local.div_euclid(86400);
+        let seconds = local.rem_euclid(86400);
+        let (year, month, day) = civil_from_days(days);
+
+        format!(
+            "{} {} {} {:02}:{:02}:{:02} {} {}",
+            DAYS[days.rem_euclid(7) as usize],
+            MONTHS[(month - 1) as usize],
+            day,
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60,
+            year,
+            tz
+        )
+    }
+
+    // 2026-10-19 12:00:00 +0200
+    pub fn format_iso(timestamp: i64, tz: &str) -> String {
+        let local = timestamp + tz_offset(tz);
+        let seconds = local.rem_euclid(86400);
+        let (year, month, day) = civil_from_days(local.div_euclid(86400));
+
+        format!(
+            "{}-{:02}-{:02} {:02}:{:02}:{:02} {}",
+            year, month, day, seconds / 3600, seconds % 3600 / 60, seconds % 60, tz
+        )
+    }
+
+    // Dates as Git options take them: "now", "2.weeks.ago", "3 days ago", "2026-10-19",
+    // "2026-10-19 12:00:00" (UTC) or a unix timestamp
+    pub fn parse_date(value: &str) -> Option<i64> {
+        let value = value.trim();
+        if value == "now" { return Some(now()); }
+        if let Ok(timestamp) = value.trim_start_matches('@').parse::<i64>() { return Some(timestamp); }
+
+        let words: Vec<&str> = value.split(['.', ' ']).filter(|w| !w.is_empty()).collect();
+        if let [count, unit, "ago"] = words.as_slice() {
+            let seconds = match unit.trim_end_matches('s') {
+                "second" => 1,
+                "minute" => 60,
+                "hour" => 3600,
+                "day" => 86400,
+                "week" => 7 * 86400,
+                "month" => 30 * 86400,
+                "year" => 365 * 86400,
+                _ => return None,
+            };
+            return Some(now() - count.parse::<i64>().ok()? * seconds);
+        }
+
+        let (date, clock) = value.split_once([' ', 'T']).unwrap_or((value, "00:00:00"));
+        let parts: Vec<i64> = date.split('-').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+        let times: Vec<i64> = clock.split(':').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+        match (parts.as_slice(), times.as_slice()) {
+            ([year, month, day], [hours, minutes, rest @ ..]) if (1..=12).contains(month) && (1..=31).contains(day) => {
+                let seconds = rest.first().copied().unwrap_or(0);
+                Some(days_from_civil(*year, *month, *day) * 86400 + hours * 3600 + minutes * 60 + seconds)
+            }
+            _ => None,
+        }
+    }
+
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let mp = if month > 2 { month - 3 } else { month + 9 };
+        let doy = (153 * mp + 2) / 5 + day - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+        era * 146097 + doe - 719468
+    }
+
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    fn civil_from_days(days: i64) -> (i64, i64, i64) {
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        (year, month, day)
+    }
 }
@@ -0,0 +1,224 @@
+use std::fs;
+use std::fs::Metadata;
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::fs::{symlink, MetadataExt, PermissionsExt};
+use std::path::Path;
+use crate::storage::Storage;
+use crate::structs::flags::{ExtendedFlags, Flags};
+use crate::structs::git_blob::GitBlob;
+use crate::structs::git_index::{GitIndex, GitIndexEntry};
+use crate::structs::git_object::GitObject;
+use crate::utils::{files, hash};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FileState { Unchanged, Modified, Deleted }
+
+// Git only records three kinds of files: regular, executable and symlinks
+pub fn file_mode(meta: &Metadata) -> u32 {
+    if meta.file_type().is_symlink() {
+        0o120000
+    } else if meta.permissions().mode() & 0o111 != 0 {
+        0o100755
+    } else {
+        0o100644
+    }
+}
+
+// Blob content of a work tree file, symlinks are stored as their target path
+pub fn read_content(path: &Path) -> Vec<u8> {
+    let meta = fs::symlink_metadata(path).unwrap();
+
+    if meta.file_type().is_symlink() {
+        fs::read_link(path).unwrap().into_os_string().into_vec()
+    } else {
+        fs::read(path).unwrap()
+    }
+}
+
+// Which cached stat fields are trusted, see `core.trustctime` and `core.checkStat`
+#[derive(Debug, Clone, Copy)]
+pub struct StatOptions {
+    pub trust_ctime: bool,
+    pub minimal: bool,
+}
+
+impl StatOptions {
+    pub fn load(storage: &Storage) -> Self {
+        let config = storage.config();
+
+        Self {
+            trust_ctime: config.get_bool("core.trustctime").unwrap_or(true),
+            minimal: config.get("core.checkStat").is_some_and(|v| v.eq_ignore_ascii_case("minimal")),
+        }
+    }
+}
+
+// Compares work tree files with index entries, trusting cached stat data where it is safe
+pub struct StatChecker<'a> {
+    storage: &'a Storage,
+    options: StatOptions,
+    timestamp: Option<(u32, u32)>,
+}
+
+impl<'a> StatChecker<'a> {
+    pub fn new(storage: &'a Storage, index: &GitIndex) -> Self {
+        Self { storage, options: StatOptions::load(storage), timestamp: index.timestamp }
+    }
+
+    pub fn check(&self, entry: &GitIndexEntry) -> FileState {
+        let path = self.storage.working_root.join(&entry.path);
+        let Ok(meta) = fs::symlink_metadata(&path) else { return FileState::Deleted };
+
+        if meta.is_dir() { return FileState::Deleted; }
+        if file_mode(&meta) != entry.mode { return FileState::Modified; }
+        if is_stat_clean(entry, &meta, &self.options) && !is_racy(entry, self.timestamp) {
+            return FileState::Unchanged;
+        }
+
+        // A zero size means the stat data is unknown (--cacheinfo) or was smudged, only content can tell
+        if entry.stats.size != 0 && meta.size() as u32 != entry.stats.size { return FileState::Modified; }
+
+        if content_matches(&path, entry) { FileState::Unchanged } else { FileState::Modified }
+    }
+}
+
+// Re-stats every entry and refreshes cached stat data of files whose content did not change.
+// Returns whether the index changed and the paths whose content differs from the index
+pub fn refresh_stats(storage: &Storage, index: &mut GitIndex) -> (bool, Vec<String>) {
+    let checker = StatChecker::new(storage, index);
+    let mut changed = false;
+    let mut needs_update = vec![];
+
+    for entry in index.entries.iter_mut() {
+        if entry.stage() != 0 || entry.flags.intersects(Flags::ASSUME_VALID) { continue; }
+        if entry.extended_flags.intersects(ExtendedFlags::SKIP_WORKTREE) { continue; }
+
+        let Ok(meta) = fs::symlink_metadata(storage.working_root.join(&entry.path)) else {
+            needs_update.push(entry.path.clone());
+            continue;
+        };
+        // Racy entries look clean whatever happened to the file, only their content can tell
+        if is_stat_clean(entry, &meta, &checker.options) && !is_racy(entry, checker.timestamp) { continue; }
+
+        match checker.check(entry) {
+            FileState::Unchanged => {
+                entry.stats = meta.into();
+                changed = true;
+            }
+            _ => needs_update.push(entry.path.clone()),
+        }
+    }
+
+    (changed, needs_update)
+}
+
+// True when the cached stat data still describes the file, which means its content is unchanged
+// unless the entry is racy
+pub fn is_stat_clean(entry: &GitIndexEntry, meta: &Metadata, options: &StatOptions) -> bool {
+    let stats = &entry.stats;
+
+    if meta.mtime() as u32 != stats.mtime_seconds { return false; }
+    if meta.size() as u32 != stats.size { return false; }
+    if options.minimal { return true; }
+
+    if meta.mtime_nsec() as u32 != stats.mtime_nanoseconds { return false; }
+    if options.trust_ctime
+        && (meta.ctime() as u32 != stats.ctime_seconds || meta.ctime_nsec() as u32 != stats.ctime_nanoseconds) {
+        return false;
+    }
+
+    meta.ino() as u32 == stats.ino && meta.uid() == stats.uid && meta.gid() == stats.gid
+}
+
+// A file modified in the same second the index was written can still match its cached stat
+// data, so entries not older than the index itself have to be compared by content. Seconds are
+// compared like Git does by default, `core.checkStat=minimal` ignores nanoseconds anyway
+pub fn is_racy(entry: &GitIndexEntry, timestamp: Option<(u32, u32)>) -> bool {
+    let Some((seconds, _)) = timestamp else { return false };
+
+    seconds <= entry.stats.mtime_seconds
+}
+
+// Racily clean entries whose file has actually changed get their cached size zeroed, otherwise
+// the index written after them would make them look clean for good. `index.timestamp` has to be
+// the one of that index file. Returns whether any entry was smudged
+pub fn smudge_racy_entries(storage: &Storage, index: &mut GitIndex) -> bool {
+    let timestamp = index.timestamp;
+    let mut smudged = false;
+
+    for entry in index.entries.iter_mut() {
+        if entry.stage() != 0 || entry.stats.size == 0 || !is_racy(entry, timestamp) { continue; }
+        if entry.flags.intersects(Flags::ASSUME_VALID) { continue; }
+
+        let path = storage.working_root.join(&entry.path);
+        if fs::symlink_metadata(&path).is_ok_and(|m| !m.is_dir()) && content_matches(&path, entry) { continue; }
+
+        entry.stats.size = 0;
+        smudged = true;
+    }
+
+    smudged
+}
+
+fn content_matches(path: &Path, entry: &GitIndexEntry) -> bool {
+    hash::from_bytes(&read_content(path)) == entry.object_hash()
+}
+
+// Hashes a work tree file into a blob and builds an index entry with its current stat data.
+// Objects are stored as text, so files that are not UTF-8 are refused
+pub fn stage_file(storage: &Storage, path: &str) -> Result<GitIndexEntry, String> {
+    let full_path = storage.working_root.join(path);
+    let meta = fs::symlink_metadata(&full_path).unwrap();
+    let content = String::from_utf8(read_content(&full_path)).map_err(|_| format!("unable to index file '{}': content is not UTF-8", path))?;
+    let blob = GitBlob::new(&content);
+    storage.persist_object(&blob);
+
+    Ok(GitIndexEntry::new(path, file_mode(&meta), &blob.hash(), meta.into()))
+}
+
+// Writes a blob into the work tree, replacing whatever is there, and builds its index entry
+pub fn checkout_file(storage: &Storage, path: &str, mode: u32, hash: &str) -> GitIndexEntry {
+    let full_path = storage.working_root.join(path);
+    let content = storage.read_object(hash).content();
+
+    match fs::symlink_metadata(&full_path) {
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(&full_path).unwrap(),
+        Ok(_) => fs::remove_file(&full_path).unwrap(),
+        Err(_) => files::create_dir(full_path.parent().unwrap()),
+    }
+
+    if mode == 0o120000 {
+        symlink(content.as_str(), &full_path).unwrap();
+    } else {
+        files::write_file(&full_path, &content);
+
+        let mut permissions = fs::metadata(&full_path).unwrap().permissions();
+        let bits = permissions.mode();
+        // Executable bits follow the read bits, like Git does with the umask applied
+        permissions.set_mode(if mode == 0o100755 { bits | (bits & 0o444) >> 2 } else { bits & !0o111 });
+        fs::set_permissions(&full_path, permissions).unwrap();
+    }
+
+    let meta = fs::symlink_metadata(&full_path).unwrap();
+    GitIndexEntry::new(path, mode, hash, meta.into())
+}
+
+// Deletes a work tree file along with the directories it leaves empty
+pub fn remove_file(storage: &Storage, path: &str) {
+    let full_path = storage.working_root.join(path);
+    if fs::symlink_metadata(&full_path).is_ok_and(|m| !m.is_dir()) { fs::remove_file(&full_path).unwrap(); }
+
+    let mut dir = full_path.parent();
+    while let Some(current) = dir.filter(|d| *d != storage.working_root) {
+        if fs::remove_dir(current).is_err() { break; }
+        dir = current.parent();
+    }
+}
+
+// Work tree files that have no entry in the index
+pub fn untracked(storage: &Storage, index: &GitIndex) -> Vec<String> {
+    files::walk(&storage.working_root)
+        .into_iter()
+        .filter(|path| !index.contains(path))
+        .collect()
+}
@@ -0,0 +1,59 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static REPOSITORIES: AtomicUsize = AtomicUsize::new(0);
+
+// A freshly initialized repository in its own temporary directory
+pub struct Repository {
+    pub root: PathBuf,
+}
+
+impl Repository {
+    pub fn new(name: &str) -> Self {
+        let count = REPOSITORIES.fetch_add(1, Ordering::SeqCst);
+        let root = std::env::temp_dir().join(format!("rinit-{}-{}-{}", name, std::process::id(), count));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+
+        let repository = Self { root };
+        repository.ok(&["init"]);
+
+        repository
+    }
+
+    pub fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_rinit"))
+            .args(args)
+            .current_dir(&self.root)
+            .env("RINIT_AUTHOR_NAME", "Test")
+            .env("RINIT_AUTHOR_EMAIL", "test@example.com")
+            .env("RINIT_COMMITTER_NAME", "Test")
+            .env("RINIT_COMMITTER_EMAIL", "test@example.com")
+            .output()
+            .unwrap()
+    }
+
+    // Standard output of a command that must succeed
+    pub fn ok(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(output.status.success(), "rinit {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+
+        String::from_utf8(output.stdout).unwrap().trim_end().to_string()
+    }
+
+    pub fn write(&self, path: &str, content: &str) {
+        fs::write(self.root.join(path), content).unwrap();
+    }
+
+    pub fn read(&self, path: &str) -> String {
+        fs::read_to_string(self.root.join(path)).unwrap()
+    }
+}
+
+impl Drop for Repository {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
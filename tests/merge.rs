@@ -0,0 +1,42 @@
+mod common;
+
+use common::Repository;
+
+// A commit of the current index on top of `parents`
+fn commit(repository: &Repository, parents: &[&str], message: &str) -> String {
+    let tree = repository.ok(&["write-tree"]);
+    let mut args = vec!["commit-tree", tree.as_str(), "-m", message];
+    for parent in parents { args.extend(["-p", parent]); }
+
+    repository.ok(&args)
+}
+
+#[test]
+fn abort_keeps_unrelated_local_changes() {
+    let repository = Repository::new("merge");
+    repository.write("conflicted", "base\n");
+    repository.write("unrelated", "clean\n");
+    repository.ok(&["add", "conflicted", "unrelated"]);
+    let base = commit(&repository, &[], "base");
+
+    repository.write("conflicted", "theirs\n");
+    repository.ok(&["add", "conflicted"]);
+    let theirs = commit(&repository, &[&base], "theirs");
+    repository.write(".rinit/refs/heads/topic", &format!("{}\n", theirs));
+
+    repository.write("conflicted", "ours\n");
+    repository.ok(&["add", "conflicted"]);
+    let ours = commit(&repository, &[&base], "ours");
+    repository.write(".rinit/refs/heads/master", &format!("{}\n", ours));
+
+    repository.write("unrelated", "local edit\n");
+    let output = repository.run(&["merge", "topic"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(repository.read("conflicted").contains("<<<<<<<"));
+
+    repository.ok(&["merge", "--abort"]);
+    assert_eq!(repository.read("conflicted"), "ours\n");
+    assert_eq!(repository.read("unrelated"), "local edit\n");
+    assert!(repository.ok(&["ls-files", "-s"]).lines().all(|line| line.contains(" 0\t")));
+    assert!(!repository.root.join(".rinit/MERGE_HEAD").exists());
+}
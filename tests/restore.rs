@@ -0,0 +1,30 @@
+mod common;
+
+use common::Repository;
+
+#[test]
+fn restore_refuses_unmerged_paths() {
+    let repository = Repository::new("restore");
+    repository.write("file", "base\n");
+    repository.write("other", "other\n");
+    repository.ok(&["add", "file", "other"]);
+    let base = repository.ok(&["write-tree"]);
+    repository.write("file", "ours\n");
+    repository.ok(&["add", "file"]);
+    let ours = repository.ok(&["write-tree"]);
+    repository.write("file", "theirs\n");
+    repository.ok(&["add", "file"]);
+    let theirs = repository.ok(&["write-tree"]);
+    repository.ok(&["read-tree", &ours]);
+    repository.ok(&["read-tree", "-m", &base, &ours, &theirs]);
+    repository.write("file", "resolving\n");
+
+    let output = repository.run(&["restore", "file"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("path 'file' is unmerged"));
+    assert_eq!(repository.read("file"), "resolving\n");
+
+    let output = repository.run(&["checkout", "--", "file"]);
+    assert!(!output.status.success());
+    assert_eq!(repository.read("file"), "resolving\n");
+}